@@ -0,0 +1,197 @@
+//! The converter's command line: which operation to run, never where or how.
+//!
+//! Paths, the cache directory and every other setting still come from the layered
+//! configuration (`docs/CONFIGURATION.md`); the arguments only pick an operation and, where it
+//! makes sense, narrow it to some editions. No arguments at all is `convert`, which is what the
+//! Dockerfile runs.
+//!
+//! Parsed by hand: eight verbs and one flag do not earn a parser dependency.
+
+use anyhow::{Result, anyhow, bail};
+use mp_stats_core::models::PlatformEdition;
+
+pub(crate) const USAGE: &str = "\
+Usage: converter [COMMAND] [--edition <java|bedrock>]...
+
+Commands:
+  convert        Convert the input into the output directory (the default)
+  validate       Check the input without converting it
+  inspect        Summarize the output directory
+  verify         Check the output directory against its integrity manifests
+  generations    List the generations of output, marking the current one
+  rollback [ID]  Serve generation ID again, or the one before the current
+  cache status   List the cached editions, their units and sizes
+  cache clear    Remove cached output and units
+  help           Print this message
+
+`--edition` may be repeated and narrows `convert`, `validate`, `inspect`,
+`verify` and `cache clear` to the named editions; without it every edition is covered.
+Everything else is read from the configuration: see docs/CONFIGURATION.md.";
+
+/// One parsed invocation. An empty `editions` means every edition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Command {
+    Convert {
+        editions: Vec<PlatformEdition>,
+    },
+    Validate {
+        editions: Vec<PlatformEdition>,
+    },
+    Inspect {
+        editions: Vec<PlatformEdition>,
+    },
+    Verify {
+        editions: Vec<PlatformEdition>,
+    },
+    Generations,
+    /// `None` rolls back to the generation before the current one.
+    Rollback {
+        to: Option<String>,
+    },
+    CacheStatus,
+    CacheClear {
+        editions: Vec<PlatformEdition>,
+    },
+    Help,
+}
+
+/// Parse the arguments after the program name.
+pub(crate) fn parse(args: impl IntoIterator<Item = String>) -> Result<Command> {
+    let mut words = Vec::new();
+    let mut editions = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--edition" => Some(
+                args.next()
+                    .ok_or_else(|| anyhow!("`--edition` needs a value"))?,
+            ),
+            _ => arg.strip_prefix("--edition=").map(str::to_owned),
+        };
+
+        match value {
+            Some(value) => {
+                let edition = value
+                    .parse::<PlatformEdition>()
+                    .map_err(|e| anyhow!("{e}"))?;
+                if !editions.contains(&edition) {
+                    editions.push(edition);
+                }
+            }
+            None if arg.starts_with('-') => bail!("unknown option `{arg}`"),
+            None => words.push(arg),
+        }
+    }
+
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let command = match words.as_slice() {
+        [] | ["convert"] => Command::Convert { editions },
+        ["validate"] => Command::Validate { editions },
+        ["inspect"] => Command::Inspect { editions },
+        ["verify"] => Command::Verify { editions },
+        ["generations" | "rollback", ..] if !editions.is_empty() => {
+            bail!("`{}` does not take `--edition`", words[0])
+        }
+        ["generations"] => Command::Generations,
+        ["rollback"] => Command::Rollback { to: None },
+        ["rollback", id] => Command::Rollback {
+            to: Some(id.to_string()),
+        },
+        ["cache", "clear"] => Command::CacheClear { editions },
+        ["cache", "status"] if editions.is_empty() => Command::CacheStatus,
+        ["cache", "status"] => bail!("`cache status` does not take `--edition`"),
+        ["cache"] => bail!("`cache` needs a subcommand: `status` or `clear`"),
+        ["help"] => Command::Help,
+        _ => bail!("unknown command `{}`", words.join(" ")),
+    };
+
+    Ok(command)
+}
+
+/// `editions`, or every edition when it is empty.
+pub(crate) fn editions_or_all(editions: &[PlatformEdition]) -> Vec<PlatformEdition> {
+    if editions.is_empty() {
+        PlatformEdition::VARIANTS.to_vec()
+    } else {
+        editions.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<Command> {
+        parse(line.split_whitespace().map(str::to_owned))
+    }
+
+    /// The Dockerfile runs the binary bare, so no arguments must stay a full conversion.
+    #[test]
+    fn no_arguments_is_a_full_conversion() {
+        assert_eq!(
+            parse_str("").unwrap(),
+            Command::Convert { editions: vec![] }
+        );
+    }
+
+    #[test]
+    fn edition_flags_narrow_the_command() {
+        assert_eq!(
+            parse_str("convert --edition java").unwrap(),
+            Command::Convert {
+                editions: vec![PlatformEdition::Java]
+            }
+        );
+        assert_eq!(
+            parse_str("verify --edition bedrock").unwrap(),
+            Command::Verify {
+                editions: vec![PlatformEdition::Bedrock]
+            }
+        );
+        assert_eq!(
+            parse_str("validate --edition=bedrock --edition java --edition java").unwrap(),
+            Command::Validate {
+                editions: vec![PlatformEdition::Bedrock, PlatformEdition::Java]
+            }
+        );
+    }
+
+    #[test]
+    fn cache_subcommands_are_parsed() {
+        assert_eq!(parse_str("cache status").unwrap(), Command::CacheStatus);
+        assert_eq!(
+            parse_str("cache clear").unwrap(),
+            Command::CacheClear { editions: vec![] }
+        );
+        assert!(parse_str("cache").is_err());
+        assert!(parse_str("cache status --edition java").is_err());
+    }
+
+    #[test]
+    fn rollback_takes_an_optional_generation() {
+        assert_eq!(
+            parse_str("rollback").unwrap(),
+            Command::Rollback { to: None }
+        );
+        assert_eq!(
+            parse_str("rollback 1760745600000").unwrap(),
+            Command::Rollback {
+                to: Some("1760745600000".into())
+            }
+        );
+        assert_eq!(parse_str("generations").unwrap(), Command::Generations);
+        assert!(parse_str("rollback 1 2").is_err());
+        assert!(parse_str("rollback --edition java").is_err());
+    }
+
+    #[test]
+    fn mistakes_are_rejected() {
+        assert!(parse_str("convret").is_err());
+        assert!(parse_str("convert --edition").is_err());
+        assert!(parse_str("convert --edition switch").is_err());
+        assert!(parse_str("convert --force").is_err());
+        assert_eq!(parse_str("inspect --help").unwrap(), Command::Help);
+    }
+}
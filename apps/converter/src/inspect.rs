@@ -0,0 +1,90 @@
+//! Summaries of a converted output tree, for `converter inspect`.
+//!
+//! Reads only what the converter wrote: the id map for the catalogue counts,
+//! and the directory layout from [`mp_stats_core::routes`] for everything else.
+
+use anyhow::{Context, Result};
+use mp_stats_common::compression::read_lzma_bin;
+use mp_stats_core::models::{IdMap, PlatformEdition};
+use mp_stats_core::routes;
+use std::path::{Component, Path};
+use walkdir::WalkDir;
+
+/// What one edition's output holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EditionSummary {
+    pub boards: usize,
+    pub games: usize,
+    pub stats: usize,
+    /// Board/game/stat combinations with a `latest` snapshot.
+    pub leaderboards: usize,
+    /// History snapshots across every leaderboard.
+    pub snapshots: usize,
+    pub player_shards: usize,
+    pub name_index_files: usize,
+    pub name_trigram_files: usize,
+    pub files: u64,
+    pub size_bytes: u64,
+}
+
+/// Summarize `edition` under `output_dir`, or `None` when the output has no
+/// such edition.
+pub fn inspect_edition(
+    output_dir: &Path,
+    edition: &PlatformEdition,
+) -> Result<Option<EditionSummary>> {
+    let edition_dir = output_dir.join(edition.directory_name());
+    if !edition_dir.is_dir() {
+        return Ok(None);
+    }
+
+    let map_path = output_dir.join(routes::meta_map_bin(edition));
+    let id_map: IdMap =
+        read_lzma_bin(&map_path).with_context(|| format!("reading {}", map_path.display()))?;
+
+    let mut summary = EditionSummary {
+        boards: id_map.boards.len(),
+        games: id_map.games.len(),
+        stats: id_map.stats.len(),
+        ..EditionSummary::default()
+    };
+
+    for entry in WalkDir::new(&edition_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let rel = entry
+            .path()
+            .strip_prefix(&edition_dir)
+            .unwrap_or(entry.path());
+        let parts: Vec<&str> = rel
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect();
+
+        if entry.file_type().is_dir() {
+            // leaderboards/<board>/<game>/<stat>/latest
+            // leaderboards/<board>/<game>/<stat>/history/<snapshot>
+            match parts.as_slice() {
+                ["leaderboards", _, _, _, "latest"] => summary.leaderboards += 1,
+                ["leaderboards", _, _, _, "history", _] => summary.snapshots += 1,
+                _ => {}
+            }
+            continue;
+        }
+
+        summary.files += 1;
+        summary.size_bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        match parts.first() {
+            Some(&"players") => summary.player_shards += 1,
+            Some(&"names_index") => summary.name_index_files += 1,
+            Some(&"names_trigram") => summary.name_trigram_files += 1,
+            _ => {}
+        }
+    }
+
+    Ok(Some(summary))
+}
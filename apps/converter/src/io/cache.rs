@@ -1,11 +1,15 @@
+use crate::io::units::{UNITS_DIR, UnitCache};
 use crate::io::{copy_dir_all, link_or_copy_dir_all};
 use anyhow::{Context, Result};
+use mp_stats_common::compression::Compression;
 use mp_stats_config::CacheConfig;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
-use std::time::UNIX_EPOCH;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
 /// Incremental conversion cache.
@@ -16,10 +20,18 @@ use walkdir::WalkDir;
 /// example on Docker rebuilds where only unrelated source code changed).
 ///
 /// Outputs are cached per logical key (one per platform edition) alongside a
-/// fingerprint of the corresponding input directory.
+/// fingerprint of the corresponding input directory. An edition whose input
+/// changed is converted unit by unit through its [`UnitCache`], kept under
+/// [`UNITS_DIR`] in the same root.
+///
+/// Every entry, edition or unit, has an [`EntryRecord`] beside it with its
+/// size and when it was last stored or restored. With a maximum size set, the
+/// least recently used entries are evicted once a run is done.
 pub struct ConversionCache {
     root: PathBuf,
     enabled: bool,
+    /// `None` when the cache may grow without bound.
+    max_size_bytes: Option<u64>,
 }
 
 /// Version of the converter's *output* schema/format.
@@ -29,7 +41,140 @@ pub struct ConversionCache {
 /// `LeaderboardMeta`) invalidates previously cached output, even when the raw
 /// input data is byte-for-byte unchanged. Bump this whenever the produced
 /// binaries change in a way that older readers/newer code cannot consume.
-const OUTPUT_SCHEMA_VERSION: u64 = 3;
+///
+/// Also recorded in each edition's integrity manifest.
+pub(crate) const OUTPUT_SCHEMA_VERSION: u64 = 13;
+
+/// What the cache records beside each entry.
+///
+/// Written when the entry is stored and rewritten each time it is restored,
+/// so eviction can order entries by last use without walking them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct EntryRecord {
+    /// The input fingerprint an edition's output was stored under. Units are
+    /// keyed by their path instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<u64>,
+    pub files: u64,
+    pub size_bytes: u64,
+    pub last_used_unix: u64,
+}
+
+impl EntryRecord {
+    /// Record the tree at `dir`, used now.
+    pub(crate) fn measure(dir: &Path, fingerprint: Option<u64>) -> Self {
+        let (files, size_bytes) = tree_size(dir);
+        Self {
+            fingerprint,
+            files,
+            size_bytes,
+            last_used_unix: now_unix(),
+        }
+    }
+
+    pub(crate) fn read(path: &Path) -> Option<Self> {
+        serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+    }
+
+    /// Write the record to `path` through a temporary file, so a reader never
+    /// sees half of it.
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let partial = path.with_extension(format!(
+            "{}.{}.partial",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = std::fs::write(&partial, serde_json::to_vec(self)?)
+            .and_then(|()| std::fs::rename(&partial, path));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        written.with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Mark the entry recorded at `path` as used now.
+    pub(crate) fn touch(mut self, path: &Path) {
+        self.last_used_unix = now_unix();
+        if let Err(e) = self.write(path) {
+            eprintln!("  Failed to record the use of a cache entry: {e:#}");
+        }
+    }
+}
+
+/// One cached edition, as reported by [`ConversionCache::status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedOutput {
+    pub key: String,
+    /// The input fingerprint the output was stored under, or `None` when its
+    /// record is missing or unreadable (the entry can never hit).
+    pub fingerprint: Option<u64>,
+    pub files: u64,
+    pub size_bytes: u64,
+    /// When the output was last stored or restored; `None` without a record.
+    pub last_used_unix: Option<u64>,
+}
+
+/// The units of one kind cached for one edition, as reported by
+/// [`ConversionCache::status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedUnits {
+    pub key: String,
+    pub kind: String,
+    pub entries: u64,
+    pub size_bytes: u64,
+    /// When the most recently used of them was last stored or restored.
+    pub last_used_unix: Option<u64>,
+}
+
+/// Everything in the cache directory, as reported by
+/// [`ConversionCache::status`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStatus {
+    /// Whole editions, sorted by key.
+    pub outputs: Vec<CachedOutput>,
+    /// Units, sorted by edition and kind.
+    pub units: Vec<CachedUnits>,
+    pub size_bytes: u64,
+}
+
+/// What [`ConversionCache::evict`] removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Eviction {
+    pub entries: u64,
+    pub freed_bytes: u64,
+    /// The size of the cache once the entries were removed.
+    pub size_bytes: u64,
+}
+
+/// One entry found in the cache directory.
+struct StoredEntry {
+    key: String,
+    /// `None` for an edition's output, the unit's kind otherwise.
+    kind: Option<String>,
+    dir: PathBuf,
+    /// `None` for a unit, whose record lives inside its directory.
+    record_path: Option<PathBuf>,
+    record: Option<EntryRecord>,
+    files: u64,
+    size_bytes: u64,
+}
+
+impl StoredEntry {
+    fn last_used_unix(&self) -> Option<u64> {
+        self.record.as_ref().map(|r| r.last_used_unix)
+    }
+
+    /// Remove the record first: an output without one never restores, so a
+    /// run that looks it up meanwhile misses instead of reading half a tree.
+    fn remove(&self) -> Result<()> {
+        if let Some(record) = &self.record_path {
+            remove_file(record)?;
+        }
+        remove_dir(&self.dir)
+    }
+}
 
 impl ConversionCache {
     /// Create an enabled cache rooted at `root`.
@@ -37,6 +182,7 @@ impl ConversionCache {
         Self {
             root,
             enabled: true,
+            max_size_bytes: None,
         }
     }
 
@@ -45,16 +191,25 @@ impl ConversionCache {
         Self {
             root: PathBuf::new(),
             enabled: false,
+            max_size_bytes: None,
         }
     }
 
+    /// Evict down to `max_size_bytes` in [`Self::evict`]; `None` never evicts.
+    pub fn with_max_size(mut self, max_size_bytes: Option<u64>) -> Self {
+        self.max_size_bytes = max_size_bytes;
+        self
+    }
+
     /// Build the cache described by a [`CacheConfig`].
     ///
     /// A disabled cache is a no-op restore/store rather than a missing directory, so the
     /// pipeline below stays identical either way.
     pub fn from_config(config: &CacheConfig) -> Self {
         if config.enabled {
-            Self::new(config.dir.clone())
+            Self::new(config.dir.clone()).with_max_size(
+                Some(config.max_size_mib.saturating_mul(1 << 20)).filter(|&bytes| bytes > 0),
+            )
         } else {
             Self::disabled()
         }
@@ -64,6 +219,169 @@ impl ConversionCache {
         self.enabled
     }
 
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn max_size_bytes(&self) -> Option<u64> {
+        self.max_size_bytes
+    }
+
+    /// The cache of the units `key` is converted in.
+    pub fn units(&self, key: &str) -> UnitCache {
+        if self.enabled {
+            UnitCache::new(self.root.join(UNITS_DIR).join(key), OUTPUT_SCHEMA_VERSION)
+        } else {
+            UnitCache::disabled()
+        }
+    }
+
+    /// List what is cached: each edition's output with the fingerprint it was
+    /// stored under, and its units by kind.
+    ///
+    /// Reads the cache directory whether or not the cache is enabled, so a
+    /// disabled cache can still be inspected before it is cleared.
+    pub fn status(&self) -> Result<CacheStatus> {
+        let mut status = CacheStatus::default();
+        for entry in self.entries()? {
+            status.size_bytes += entry.size_bytes;
+            let last_used_unix = entry.last_used_unix();
+
+            let Some(kind) = entry.kind else {
+                status.outputs.push(CachedOutput {
+                    fingerprint: entry.record.and_then(|r| r.fingerprint),
+                    key: entry.key,
+                    files: entry.files,
+                    size_bytes: entry.size_bytes,
+                    last_used_unix,
+                });
+                continue;
+            };
+
+            match status.units.last_mut() {
+                Some(units) if units.key == entry.key && units.kind == kind => {
+                    units.entries += 1;
+                    units.size_bytes += entry.size_bytes;
+                    units.last_used_unix = units.last_used_unix.max(last_used_unix);
+                }
+                _ => status.units.push(CachedUnits {
+                    key: entry.key,
+                    kind,
+                    entries: 1,
+                    size_bytes: entry.size_bytes,
+                    last_used_unix,
+                }),
+            }
+        }
+        Ok(status)
+    }
+
+    /// Remove the least recently used entries until the cache fits its
+    /// maximum size. Entries without a record, left by an older converter,
+    /// go first.
+    pub fn evict(&self) -> Result<Eviction> {
+        let mut eviction = Eviction::default();
+        let Some(max_size_bytes) = self.max_size_bytes.filter(|_| self.enabled) else {
+            return Ok(eviction);
+        };
+
+        let mut entries = self.entries()?;
+        eviction.size_bytes = entries.iter().map(|e| e.size_bytes).sum();
+        entries.sort_by_key(|e| e.last_used_unix());
+
+        for entry in entries {
+            if eviction.size_bytes <= max_size_bytes {
+                break;
+            }
+            entry.remove()?;
+            eviction.entries += 1;
+            eviction.freed_bytes += entry.size_bytes;
+            eviction.size_bytes -= entry.size_bytes;
+        }
+        Ok(eviction)
+    }
+
+    /// Every entry in the cache directory: outputs sorted by key, then units
+    /// sorted by edition, kind and hash.
+    fn entries(&self) -> Result<Vec<StoredEntry>> {
+        let mut entries = Vec::new();
+        for key in subdirs(&self.root)? {
+            if key == UNITS_DIR {
+                continue;
+            }
+            let dir = self.output_path(&key);
+            let record_path = self.record_path(&key);
+            let record = EntryRecord::read(&record_path);
+            let (files, size_bytes) = match &record {
+                Some(record) => (record.files, record.size_bytes),
+                None => tree_size(&dir),
+            };
+            entries.push(StoredEntry {
+                key,
+                kind: None,
+                dir,
+                record_path: Some(record_path),
+                record,
+                files,
+                size_bytes,
+            });
+        }
+
+        let units = self.root.join(UNITS_DIR);
+        for key in subdirs(&units)? {
+            for kind in subdirs(&units.join(&key))? {
+                for hash in subdirs(&units.join(&key).join(&kind))? {
+                    // Entries still being stored are not the cache's yet.
+                    if hash.starts_with('.') {
+                        continue;
+                    }
+                    let dir = units.join(&key).join(&kind).join(&hash);
+                    let record = UnitCache::record(&dir);
+                    let (files, size_bytes) = match &record {
+                        Some(record) => (record.files, record.size_bytes),
+                        None => tree_size(&dir),
+                    };
+                    entries.push(StoredEntry {
+                        key: key.clone(),
+                        kind: Some(kind.clone()),
+                        dir,
+                        record_path: None,
+                        record,
+                        files,
+                        size_bytes,
+                    });
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Remove the cached output and units for `key`, or the whole cache when
+    /// `key` is `None`. Clearing an entry that does not exist is not an error.
+    pub fn clear(&self, key: Option<&str>) -> Result<()> {
+        match key {
+            None => remove_dir(&self.root),
+            Some(key) => {
+                remove_file(&self.record_path(key))?;
+                remove_dir(&self.output_path(key))?;
+                remove_dir(&self.root.join(UNITS_DIR).join(key))
+            }
+        }
+    }
+
+    /// Fingerprint `input` as [`Self::fingerprint_dir`] does, bound to the
+    /// settings the output is compressed with.
+    ///
+    /// Only whether the output is encoded in parallel blocks changes its
+    /// bytes, not how many threads encode them, so a run on a machine with
+    /// more cores still hits the cache.
+    pub fn fingerprint(input: &Path, compression: &Compression) -> Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        Self::fingerprint_dir(input)?.hash(&mut hasher);
+        compression.encoding().hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
     /// Compute a stable fingerprint of an input directory from the relative
     /// path, byte length and modification time of every file it contains.
     pub fn fingerprint_dir(input: &Path) -> Result<u64> {
@@ -117,20 +435,14 @@ impl ConversionCache {
         Ok(hasher.finish())
     }
 
-    fn fingerprint_path(&self, key: &str) -> PathBuf {
-        self.root.join(format!("{key}.fingerprint"))
+    fn record_path(&self, key: &str) -> PathBuf {
+        self.root.join(format!("{key}.entry.json"))
     }
 
     fn output_path(&self, key: &str) -> PathBuf {
         self.root.join(key)
     }
 
-    fn stored_fingerprint(&self, key: &str) -> Option<u64> {
-        std::fs::read_to_string(self.fingerprint_path(key))
-            .ok()
-            .and_then(|raw| raw.trim().parse::<u64>().ok())
-    }
-
     /// Restore a cached output for `key` into `dest` when the stored
     /// fingerprint matches `fingerprint`. Returns `true` on a cache hit.
     pub fn restore(&self, key: &str, fingerprint: u64, dest: &Path) -> Result<bool> {
@@ -139,12 +451,15 @@ impl ConversionCache {
         }
 
         let output = self.output_path(key);
-        if !output.exists() || self.stored_fingerprint(key) != Some(fingerprint) {
-            return Ok(false);
-        }
+        let record_path = self.record_path(key);
+        let record = match EntryRecord::read(&record_path) {
+            Some(record) if output.exists() && record.fingerprint == Some(fingerprint) => record,
+            _ => return Ok(false),
+        };
 
         link_or_copy_dir_all(&output, dest)
             .with_context(|| format!("restoring cached output for '{key}'"))?;
+        record.touch(&record_path);
         Ok(true)
     }
 
@@ -154,7 +469,11 @@ impl ConversionCache {
             return Ok(());
         }
 
+        // Drop the old record first, so the output is never restored while
+        // it is being replaced.
         let output = self.output_path(key);
+        let record_path = self.record_path(key);
+        remove_file(&record_path)?;
         if output.exists() {
             let _ = std::fs::remove_dir_all(&output);
         }
@@ -163,8 +482,61 @@ impl ConversionCache {
         }
 
         copy_dir_all(src, &output).with_context(|| format!("caching output for '{key}'"))?;
-        std::fs::write(self.fingerprint_path(key), fingerprint.to_string())?;
-        Ok(())
+        EntryRecord::measure(&output, Some(fingerprint)).write(&record_path)
+    }
+}
+
+pub(crate) fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The number of files under `dir` and their total size.
+fn tree_size(dir: &Path) -> (u64, u64) {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .fold((0, 0), |(files, size), meta| (files + 1, size + meta.len()))
+}
+
+/// The names of the directories in `dir`, sorted; none when it is missing.
+fn subdirs(dir: &Path) -> Result<Vec<String>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", dir.display())),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn remove_dir(path: &Path) -> Result<()> {
+    match std::fs::remove_dir_all(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("removing {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("removing {}", path.display()))
+        }
+        _ => Ok(()),
     }
 }
 
@@ -209,6 +581,120 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dest);
     }
 
+    /// Status reports what `store` wrote, and `clear` removes one key or all.
+    #[test]
+    fn status_lists_and_clear_removes_entries() {
+        let root = unique_dir("status");
+        let src = unique_dir("status_src");
+        std::fs::create_dir_all(src.join("games")).unwrap();
+        std::fs::write(src.join("games/a.bin.xz"), b"12345").unwrap();
+        std::fs::write(src.join("map.bin.xz"), b"123").unwrap();
+
+        let cache = ConversionCache::new(root.clone());
+        assert_eq!(cache.status().unwrap(), CacheStatus::default());
+
+        cache.store("java", 7, &src).unwrap();
+        cache.store("bedrock", 9, &src).unwrap();
+        let units = cache.units("java");
+        let key = units.key("players", |k| k.dir(&src)).unwrap().unwrap();
+        units.store(&key, &1u8, None).unwrap();
+
+        let status = cache.status().unwrap();
+        assert_eq!(status.outputs.len(), 2);
+        let java = &status.outputs[1];
+        assert_eq!(
+            (
+                java.key.as_str(),
+                java.fingerprint,
+                java.files,
+                java.size_bytes
+            ),
+            ("java", Some(7), 2, 8)
+        );
+        assert!(java.last_used_unix.is_some());
+        assert_eq!(status.units.len(), 1);
+        assert_eq!(
+            (status.units[0].key.as_str(), status.units[0].kind.as_str()),
+            ("java", "players")
+        );
+        assert_eq!(status.units[0].entries, 1);
+        assert_eq!(status.size_bytes, 16 + status.units[0].size_bytes);
+
+        cache.clear(Some("java")).unwrap();
+        let status = cache.status().unwrap();
+        let keys: Vec<_> = status.outputs.into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, ["bedrock"]);
+        assert!(status.units.is_empty());
+
+        cache.clear(None).unwrap();
+        assert_eq!(cache.status().unwrap(), CacheStatus::default());
+        // Clearing what is already gone is a no-op.
+        cache.clear(None).unwrap();
+
+        let _ = std::fs::remove_dir_all(&src);
+    }
+
+    /// Eviction removes the least recently used entries, outputs and units
+    /// alike, until the cache fits, and restoring an entry counts as a use.
+    #[test]
+    fn eviction_removes_the_least_recently_used_entries() {
+        let root = unique_dir("evict");
+        let src = unique_dir("evict_src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("map.bin.xz"), [0u8; 100]).unwrap();
+
+        let cache = ConversionCache::new(root.clone());
+        for key in ["bedrock", "java"] {
+            cache.store(key, 1, &src).unwrap();
+        }
+        let units = cache.units("java");
+        let unit = units.key("players", |k| k.dir(&src)).unwrap().unwrap();
+        units.store(&unit, &1u8, Some(&src)).unwrap();
+
+        // Age every entry, then use one output and the unit again.
+        let age = |path: &Path, last_used_unix| {
+            let record = EntryRecord::read(path).unwrap();
+            EntryRecord {
+                last_used_unix,
+                ..record
+            }
+            .write(path)
+            .unwrap();
+        };
+        let unit_record = root
+            .join("units/java/players")
+            .join(unit.hash())
+            .join("entry.json");
+        age(&cache.record_path("bedrock"), 10);
+        age(&cache.record_path("java"), 20);
+        age(&unit_record, 30);
+        let dest = unique_dir("evict_dest");
+        assert!(cache.restore("java", 1, &dest).unwrap());
+        assert_eq!(units.restore::<u8>(&unit, None), Some(1));
+
+        // Unbounded, nothing goes.
+        assert_eq!(cache.evict().unwrap().entries, 0);
+
+        let total = cache.status().unwrap().size_bytes;
+        let bounded = ConversionCache::new(root.clone()).with_max_size(Some(total - 1));
+        let eviction = bounded.evict().unwrap();
+        assert_eq!(eviction.entries, 1);
+        assert_eq!(eviction.freed_bytes, 100);
+        assert_eq!(eviction.size_bytes, total - 100);
+
+        let status = bounded.status().unwrap();
+        let keys: Vec<_> = status.outputs.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["java"]);
+        assert_eq!(status.units.len(), 1);
+
+        // A cache with room evicts nothing more.
+        assert_eq!(bounded.evict().unwrap().entries, 0);
+
+        for dir in [root, src, dest] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
     /// The schema version is part of the fingerprint, so two otherwise-identical
     /// inputs must hash differently across schema versions. We verify the
     /// version contributes by recomputing the hash with and without it.
@@ -258,4 +744,25 @@ mod tests {
 
         let _ = std::fs::remove_dir_all(&input);
     }
+
+    #[test]
+    fn fingerprint_depends_on_how_output_is_compressed() {
+        let input = unique_dir("fp_compression");
+        std::fs::create_dir_all(&input).unwrap();
+        std::fs::write(input.join("a.bin"), b"hello").unwrap();
+
+        let fingerprint =
+            |compression: Compression| ConversionCache::fingerprint(&input, &compression).unwrap();
+        let standard = Compression::standard();
+        let parallel = |threads| Compression {
+            threads,
+            ..standard
+        };
+
+        assert_ne!(fingerprint(standard), fingerprint(Compression::fast()));
+        assert_ne!(fingerprint(standard), fingerprint(parallel(2)));
+        assert_eq!(fingerprint(parallel(2)), fingerprint(parallel(16)));
+
+        let _ = std::fs::remove_dir_all(&input);
+    }
 }
@@ -1,7 +1,11 @@
 pub mod cache;
 pub mod reader;
+pub mod spill;
+pub mod units;
 pub mod writer;
 
-pub use cache::ConversionCache;
+pub use cache::{CacheStatus, CachedOutput, CachedUnits, ConversionCache, Eviction};
 pub use reader::*;
+pub use spill::{SPILL_DIR, ShardedSpill, SpillDir};
+pub use units::{KeyHasher, UnitCache, UnitKey};
 pub use writer::*;
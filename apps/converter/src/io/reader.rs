@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Safely read and parse JSON file with validation
 pub fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
@@ -54,14 +54,75 @@ pub fn validate_directory(path: &Path, description: &str) -> Result<()> {
 
 /// Check if two paths are different (safety check for in-place operations)
 pub fn validate_different_paths(in_path: &Path, out_path: &Path) -> Result<()> {
-    if let (Ok(in_canon), Ok(out_canon)) = (in_path.canonicalize(), out_path.canonicalize()) {
-        if in_canon == out_canon {
-            anyhow::bail!(
-                "Input and output directories must be different for safety: {} == {}",
-                in_canon.display(),
-                out_canon.display()
-            );
-        }
+    if let (Ok(in_canon), Ok(out_canon)) = (in_path.canonicalize(), out_path.canonicalize())
+        && in_canon == out_canon
+    {
+        anyhow::bail!(
+            "Input and output directories must be different for safety: {} == {}",
+            in_canon.display(),
+            out_canon.display()
+        );
     }
     Ok(())
 }
+
+/// Whether one path is the other or lies inside it, compared after resolving
+/// symlinks. Either path may not exist yet: its nearest existing ancestor is
+/// resolved and the remaining components appended.
+pub fn paths_nest(a: &Path, b: &Path) -> bool {
+    let (a, b) = (resolve(a), resolve(b));
+    a.starts_with(&b) || b.starts_with(&a)
+}
+
+fn resolve(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut existing = absolute.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            return rest
+                .iter()
+                .rev()
+                .fold(resolved, |path, part| path.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return absolute,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_paths_are_detected_in_either_direction() {
+        let root = std::env::temp_dir().join(format!("mp_stats_nest_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("output")).unwrap();
+
+        assert!(paths_nest(&root.join("output"), &root.join("output")));
+        assert!(paths_nest(
+            &root.join("output/staging"),
+            &root.join("output")
+        ));
+        assert!(paths_nest(
+            &root.join("output"),
+            &root.join("output/staging")
+        ));
+        assert!(paths_nest(
+            &root.join("output/./a/../b"),
+            &root.join("output")
+        ));
+        assert!(!paths_nest(&root.join("staging"), &root.join("output")));
+        assert!(!paths_nest(
+            &root.join("output-staging"),
+            &root.join("output")
+        ));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
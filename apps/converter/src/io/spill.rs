@@ -0,0 +1,283 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The directory under a run's staging directory that holds its scratch files.
+pub const SPILL_DIR: &str = ".spill";
+
+/// How much a [`ShardedSpill`] buffers before appending to its files.
+const BUFFER_BYTES: usize = 4 << 20;
+
+/// Scratch space for what a stage would otherwise hold in memory until it is
+/// done: a directory under the run's staging directory, removed when dropped.
+/// A run that dies before then leaves it inside its staging directory, which
+/// the next run removes as an orphan.
+#[derive(Debug)]
+pub struct SpillDir {
+    root: PathBuf,
+    next: AtomicU64,
+}
+
+impl SpillDir {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            next: AtomicU64::new(0),
+        }
+    }
+
+    /// A new, empty directory under the root, named after `tag` for whoever
+    /// looks at a leftover one.
+    pub fn scratch(&self, tag: &str) -> Result<PathBuf> {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        let dir = self.root.join(format!("{tag}_{n}"));
+        fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        Ok(dir)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Records filed by shard, buffered in memory and appended to one file per
+/// shard under `dir` whenever the buffers pass a few MiB, so the records held
+/// at once stay bounded however many are pushed. A shard's records read back
+/// in the order they were pushed.
+///
+/// The first error appending is kept and returned by [`flush`](Self::flush);
+/// records pushed after it are dropped.
+pub struct ShardedSpill {
+    dir: PathBuf,
+    buffers: HashMap<String, Vec<u8>>,
+    buffered: usize,
+    error: Option<anyhow::Error>,
+}
+
+impl ShardedSpill {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            buffers: HashMap::new(),
+            buffered: 0,
+            error: None,
+        }
+    }
+
+    pub fn push<T: Serialize>(&mut self, shard: &str, record: &T) {
+        if self.error.is_some() {
+            return;
+        }
+        let bytes = match postcard::to_stdvec(record) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.error = Some(e.into());
+                return;
+            }
+        };
+
+        let buffer = match self.buffers.get_mut(shard) {
+            Some(buffer) => buffer,
+            None => self.buffers.entry(shard.to_string()).or_default(),
+        };
+        buffer.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(&bytes);
+        self.buffered += bytes.len() + 4;
+
+        if self.buffered >= BUFFER_BYTES
+            && let Err(e) = self.append()
+        {
+            self.error = Some(e);
+        }
+    }
+
+    /// Append everything buffered, or say why a record was lost.
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.append()
+    }
+
+    /// The file `shard`'s records are appended to, once flushed.
+    pub fn path(&self, shard: &str) -> PathBuf {
+        shard_path(&self.dir, shard)
+    }
+
+    fn append(&mut self) -> Result<()> {
+        for (shard, buffer) in self.buffers.drain() {
+            let path = shard_path(&self.dir, &shard);
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(&buffer))
+                .with_context(|| format!("appending to {}", path.display()))?;
+        }
+        self.buffered = 0;
+        Ok(())
+    }
+}
+
+fn shard_path(dir: &Path, shard: &str) -> PathBuf {
+    dir.join(format!("{shard}.spill"))
+}
+
+/// The shards spilled to `dir`, as `(shard, file)`, in name order.
+pub fn spilled_shards(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut shards = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if let Some(shard) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".spill"))
+        {
+            shards.push((shard.to_string(), path));
+        }
+    }
+    shards.sort();
+    Ok(shards)
+}
+
+/// Append every shard spilled to `from` to the same shard's file under
+/// `into`. Merges into one directory hold the same `lock`, so the records of
+/// one are never interleaved with another's.
+pub fn merge_spilled(from: &Path, into: &Path, lock: &Mutex<()>) -> Result<()> {
+    let shards = spilled_shards(from)?;
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    for (shard, spilled) in shards {
+        let path = shard_path(into, &shard);
+        File::open(&spilled)
+            .and_then(|mut spilled| {
+                let mut merged = OpenOptions::new().create(true).append(true).open(&path)?;
+                io::copy(&mut spilled, &mut merged)
+            })
+            .with_context(|| format!("appending {} to {}", spilled.display(), path.display()))?;
+    }
+    Ok(())
+}
+
+/// The records of one spilled shard, in the order they were appended. A shard
+/// never written reads as empty.
+pub fn read_spilled<T: DeserializeOwned>(path: &Path) -> Result<SpilledRecords<T>> {
+    let reader = match File::open(path) {
+        Ok(file) => Some(BufReader::new(file)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("opening {}", path.display())),
+    };
+    Ok(SpilledRecords {
+        reader,
+        record: Vec::new(),
+        _records: PhantomData,
+    })
+}
+
+/// Iterator over a spilled shard's records; see [`read_spilled`].
+pub struct SpilledRecords<T> {
+    reader: Option<BufReader<File>>,
+    record: Vec<u8>,
+    _records: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Iterator for SpilledRecords<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = self.reader.as_mut()?;
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return None,
+            Err(e) => return Some(Err(e.into())),
+        }
+        self.record.resize(u32::from_le_bytes(len) as usize, 0);
+        let read = reader
+            .read_exact(&mut self.record)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| postcard::from_bytes(&self.record).map_err(Into::into));
+        if read.is_err() {
+            self.reader = None;
+        }
+        Some(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spilled_shards_merge_in_order() {
+        let spill = SpillDir::new(std::env::temp_dir().join(format!(
+            "mp_stats_spill_{}_{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        )));
+        let (first, second, merged) = (
+            spill.scratch("test").unwrap(),
+            spill.scratch("test").unwrap(),
+            spill.scratch("test").unwrap(),
+        );
+
+        let (mut a, mut b) = (
+            ShardedSpill::new(first.clone()),
+            ShardedSpill::new(second.clone()),
+        );
+        for n in 0..3u64 {
+            a.push("A", &(n, "first".to_string()));
+            b.push(
+                if n % 2 == 0 { "A" } else { "B" },
+                &(n, "second".to_string()),
+            );
+        }
+        a.flush().unwrap();
+        b.flush().unwrap();
+        let lock = Mutex::new(());
+        merge_spilled(&first, &merged, &lock).unwrap();
+        merge_spilled(&second, &merged, &lock).unwrap();
+
+        let shards: Vec<String> = spilled_shards(&merged)
+            .unwrap()
+            .into_iter()
+            .map(|(shard, _)| shard)
+            .collect();
+        assert_eq!(shards, ["A", "B"]);
+        let records: Vec<(u64, String)> = read_spilled(&merged.join("A.spill"))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            records,
+            [
+                (0, "first".into()),
+                (1, "first".into()),
+                (2, "first".into()),
+                (0, "second".into()),
+                (2, "second".into())
+            ]
+        );
+        assert_eq!(
+            read_spilled::<u64>(&merged.join("C.spill"))
+                .unwrap()
+                .count(),
+            0
+        );
+
+        let root = spill.root.clone();
+        drop(spill);
+        assert!(!root.exists());
+    }
+}
@@ -0,0 +1,313 @@
+use crate::io::cache::EntryRecord;
+use crate::io::{copy_dir_all, link_or_copy_dir_all};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use walkdir::WalkDir;
+
+/// The directory under the cache root that holds every edition's units.
+pub const UNITS_DIR: &str = "units";
+
+/// What a unit entry keeps besides its output tree.
+const DATA_FILE: &str = "unit.bin";
+const OUTPUT_DIR: &str = "output";
+const RECORD_FILE: &str = "entry.json";
+
+/// Content-addressed cache of the units an edition is converted in.
+///
+/// [`ConversionCache`](crate::ConversionCache) reuses an edition only when
+/// none of its input changed. Below it, each unit of work - a board/game/stat
+/// leaderboard, a player file, the dictionary - is cached on its own under a
+/// hash of the bytes it reads, so a run whose input changed in a few places
+/// converts those and restores the rest.
+///
+/// An entry holds what the unit hands to the steps after it, postcard-encoded,
+/// for units that write output, the tree they wrote, and its [`EntryRecord`].
+/// Entries live under `<cache dir>/units/<edition>/<kind>/<key>`.
+#[derive(Debug, Clone)]
+pub struct UnitCache {
+    /// `None` when the conversion cache is disabled.
+    root: Option<PathBuf>,
+    /// Mixed into every key: the output schema version, and the keys of the
+    /// units this cache's units depend on.
+    salt: Vec<u8>,
+}
+
+/// The content hash one unit is cached under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitKey {
+    kind: &'static str,
+    hash: String,
+}
+
+impl UnitKey {
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+}
+
+/// Hashes the inputs of one unit into its [`UnitKey`].
+pub struct KeyHasher {
+    sha: Sha256,
+}
+
+impl KeyHasher {
+    /// Hash `bytes`, length-prefixed so consecutive values cannot run together.
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.sha.update((bytes.len() as u64).to_le_bytes());
+        self.sha.update(bytes);
+        self
+    }
+
+    /// Hash the contents of the file at `path`.
+    pub fn file(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        self.sha.update(file.metadata()?.len().to_le_bytes());
+
+        let mut buffer = vec![0; 1 << 16];
+        loop {
+            match file.read(&mut buffer)? {
+                0 => break,
+                read => self.sha.update(&buffer[..read]),
+            }
+        }
+        Ok(())
+    }
+
+    /// Hash every file under `dir`, path and contents, in path order. A
+    /// missing directory hashes as an empty one.
+    pub fn dir(&mut self, dir: &Path) -> io::Result<()> {
+        let mut files = Vec::new();
+        for entry in WalkDir::new(dir).sort_by_file_name() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.io_error().map(io::Error::kind) == Some(io::ErrorKind::NotFound) => {
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if entry.file_type().is_file() {
+                files.push(entry.into_path());
+            }
+        }
+
+        self.sha.update((files.len() as u64).to_le_bytes());
+        for path in files {
+            let rel = path.strip_prefix(dir).unwrap_or(&path);
+            self.bytes(rel.to_string_lossy().replace('\\', "/").as_bytes());
+            self.file(&path)?;
+        }
+        Ok(())
+    }
+}
+
+impl UnitCache {
+    /// Cache units under `root`, keyed by content and `schema_version`.
+    pub fn new(root: PathBuf, schema_version: u64) -> Self {
+        Self {
+            root: Some(root),
+            salt: schema_version.to_le_bytes().to_vec(),
+        }
+    }
+
+    /// A cache that never hits and never stores, and hashes nothing.
+    pub fn disabled() -> Self {
+        Self {
+            root: None,
+            salt: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.root.is_some()
+    }
+
+    /// The same cache, with `key` mixed into every key it hands out, for units
+    /// whose output follows from that unit's as well as their own input.
+    pub fn depending_on(&self, key: Option<&UnitKey>) -> Self {
+        let mut salt = self.salt.clone();
+        if let Some(key) = key {
+            salt.extend_from_slice(key.kind.as_bytes());
+            salt.extend_from_slice(key.hash.as_bytes());
+        }
+        Self {
+            root: self.root.clone(),
+            salt,
+        }
+    }
+
+    /// Key a unit of `kind` by what `inputs` hashes, or `None` when the cache
+    /// is disabled.
+    pub fn key(
+        &self,
+        kind: &'static str,
+        inputs: impl FnOnce(&mut KeyHasher) -> io::Result<()>,
+    ) -> Result<Option<UnitKey>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+
+        let mut hasher = KeyHasher { sha: Sha256::new() };
+        hasher.bytes(&self.salt).bytes(kind.as_bytes());
+        inputs(&mut hasher).with_context(|| format!("hashing the input of a {kind} unit"))?;
+
+        let hash = hasher
+            .sha
+            .finalize()
+            .iter()
+            .fold(String::with_capacity(64), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            });
+        Ok(Some(UnitKey { kind, hash }))
+    }
+
+    fn entry_path(&self, key: &UnitKey) -> Option<PathBuf> {
+        Some(self.root.as_ref()?.join(key.kind).join(&key.hash))
+    }
+
+    /// The record of the entry at `entry`.
+    pub(crate) fn record(entry: &Path) -> Option<EntryRecord> {
+        EntryRecord::read(&entry.join(RECORD_FILE))
+    }
+
+    /// The data stored for `key`, with its output tree materialized at
+    /// `output` when one is given.
+    ///
+    /// An entry that cannot be read is a miss, and leaves nothing at
+    /// `output`.
+    pub fn restore<T: DeserializeOwned>(&self, key: &UnitKey, output: Option<&Path>) -> Option<T> {
+        let entry = self.entry_path(key)?;
+        let bytes = fs::read(entry.join(DATA_FILE)).ok()?;
+        let data = postcard::from_bytes(&bytes).ok()?;
+
+        if let Some(output) = output
+            && let Err(e) = link_or_copy_dir_all(entry.join(OUTPUT_DIR), output)
+        {
+            eprintln!(
+                "  Failed to restore the cached {} unit {}: {e:#}",
+                key.kind, key.hash
+            );
+            let _ = fs::remove_dir_all(output);
+            return None;
+        }
+
+        // An entry whose record was lost is measured again.
+        Self::record(&entry)
+            .unwrap_or_else(|| EntryRecord::measure(&entry, None))
+            .touch(&entry.join(RECORD_FILE));
+        Some(data)
+    }
+
+    /// Store `data` for `key`, with the tree at `output` when one is given.
+    ///
+    /// The entry is assembled beside its final path and renamed into place,
+    /// so a unit being stored is never restored half written.
+    pub fn store<T: Serialize>(
+        &self,
+        key: &UnitKey,
+        data: &T,
+        output: Option<&Path>,
+    ) -> Result<()> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let Some(entry) = self.entry_path(key) else {
+            return Ok(());
+        };
+        let partial = entry.with_file_name(format!(
+            ".{}.{}.{}.partial",
+            key.hash,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let written = (|| -> Result<()> {
+            fs::create_dir_all(&partial)?;
+            fs::write(partial.join(DATA_FILE), postcard::to_stdvec(data)?)?;
+            if let Some(output) = output {
+                copy_dir_all(output, partial.join(OUTPUT_DIR))?;
+            }
+            EntryRecord::measure(&partial, None).write(&partial.join(RECORD_FILE))?;
+            if entry.exists() {
+                fs::remove_dir_all(&entry)?;
+            }
+            fs::rename(&partial, &entry)?;
+            Ok(())
+        })();
+
+        if written.is_err() {
+            let _ = fs::remove_dir_all(&partial);
+        }
+        written.with_context(|| format!("caching the {} unit {}", key.kind, key.hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn unique_dir(tag: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("mp_stats_units_test_{tag}_{nanos}"))
+    }
+
+    #[test]
+    fn a_unit_is_keyed_by_content_and_restored_with_its_output() {
+        let root = unique_dir("root");
+        let input = unique_dir("input");
+        let output = unique_dir("output");
+        fs::create_dir_all(input.join("latest")).unwrap();
+        fs::write(input.join("latest/chunk_0000.xz"), b"rows").unwrap();
+        fs::create_dir_all(&output).unwrap();
+        fs::write(output.join("page.bin"), b"page").unwrap();
+
+        let units = UnitCache::new(root.clone(), 1);
+        let key = |units: &UnitCache| {
+            units
+                .key("leaderboard", |k| k.dir(&input))
+                .unwrap()
+                .unwrap()
+        };
+        let first = key(&units);
+        assert!(units.restore::<u32>(&first, None).is_none());
+
+        units.store(&first, &7u32, Some(&output)).unwrap();
+        let restored = unique_dir("restored");
+        assert_eq!(units.restore::<u32>(&first, Some(&restored)), Some(7));
+        assert_eq!(fs::read(restored.join("page.bin")).unwrap(), b"page");
+
+        // Touching a file without changing it keeps the key; changing it,
+        // another schema version or a dependency does not.
+        fs::write(input.join("latest/chunk_0000.xz"), b"rows").unwrap();
+        assert_eq!(key(&units), first);
+        assert_ne!(key(&UnitCache::new(root.clone(), 2)), first);
+        assert_ne!(key(&units.depending_on(Some(&first))), first);
+        fs::write(input.join("latest/chunk_0000.xz"), b"other rows").unwrap();
+        assert_ne!(key(&units), first);
+
+        assert!(
+            UnitCache::disabled()
+                .key("leaderboard", |_| Ok(()))
+                .unwrap()
+                .is_none()
+        );
+
+        for dir in [root, input, output, restored] {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
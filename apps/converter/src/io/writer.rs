@@ -1,7 +1,12 @@
-use anyhow::{Context, Result};
+use crate::report::REPORT_FILE;
+use anyhow::{Context, Result, bail};
+use mp_stats_common::formats::GENERATIONS_DIR;
+use mp_stats_common::generation;
+use mp_stats_core::models::PlatformEdition;
 use rayon::prelude::*;
-use std::fs;
+use std::fs::{self, File, TryLockError};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
 /// Safely copy a directory tree.
@@ -94,27 +99,428 @@ pub fn setup_staging_directory(staging_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Finalize output by moving staging to target.
+/// Prefix of every staging directory a run creates, and of the lock file it
+/// holds beside it.
+pub const STAGING_PREFIX: &str = "converter_staging_";
+const STAGING_LOCK_EXTENSION: &str = "lock";
+
+/// Where runs stage when `converter.staging_dir` is left empty:
+/// `.<name>.staging` beside `output_dir`, so publishing the output is a rename
+/// within one filesystem.
+pub fn default_staging_root(output_dir: &Path) -> PathBuf {
+    let name = output_dir
+        .file_name()
+        .map_or_else(|| "output".into(), |name| name.to_string_lossy());
+    output_dir
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(format!(".{name}.staging"))
+}
+
+/// A staging directory in use by this process. Its lock file is held for as
+/// long as the claim lives; dropping the claim removes both.
+#[derive(Debug)]
+pub struct StagingClaim {
+    dir: PathBuf,
+    lock_path: PathBuf,
+    _lock: File,
+}
+
+impl Drop for StagingClaim {
+    fn drop(&mut self) {
+        // Published output was renamed away; anything still here is a run
+        // that failed part way.
+        let _ = fs::remove_dir_all(&self.dir);
+        let _ = fs::remove_file(&self.lock_path);
+    }
+}
+
+/// Claim `staging_dir` as a clean slate for one run, first removing the
+/// staging directories beside it whose runs are gone.
+///
+/// A run holds an exclusive lock on `<staging dir>.lock` while it stages, so a
+/// staging directory whose lock file is missing or can be locked was left by a
+/// run that crashed. The lock file is locked before it is renamed into place,
+/// so it is never seen unlocked while its run is alive.
+pub fn claim_staging_directory(staging_dir: &Path) -> Result<StagingClaim> {
+    let root = staging_dir.parent().unwrap_or(Path::new(""));
+    fs::create_dir_all(root).with_context(|| format!("creating {}", root.display()))?;
+
+    let lock_path = staging_dir.with_extension(STAGING_LOCK_EXTENSION);
+    let pending = root.join(format!(
+        ".{}",
+        lock_path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let lock = File::create(&pending)?;
+    lock.try_lock()
+        .with_context(|| format!("locking {}", pending.display()))?;
+    fs::rename(&pending, &lock_path)?;
+
+    let claim = StagingClaim {
+        dir: staging_dir.to_path_buf(),
+        lock_path,
+        _lock: lock,
+    };
+    match remove_orphaned_staging(root) {
+        Ok(removed) if !removed.is_empty() => {
+            println!("  Removed {} orphaned staging directories", removed.len());
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("  Failed to remove orphaned staging directories: {e:#}"),
+    }
+
+    setup_staging_directory(staging_dir)?;
+    Ok(claim)
+}
+
+/// Remove the staging directories under `root`, and their lock files, that no
+/// live run holds. Returns the directories removed.
+///
+/// Lock files still under their pending name, left by a run that died before
+/// renaming one into place, are removed too once nothing holds them.
+pub fn remove_orphaned_staging(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut names = Vec::new();
+    let mut pending = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&format!(".{STAGING_PREFIX}"))
+            && name.ends_with(&format!(".{STAGING_LOCK_EXTENSION}"))
+        {
+            pending.push(root.join(name));
+        } else if let Some(rest) = name.strip_prefix(STAGING_PREFIX) {
+            let run = rest
+                .strip_suffix(&format!(".{STAGING_LOCK_EXTENSION}"))
+                .unwrap_or(rest);
+            names.push(format!("{STAGING_PREFIX}{run}"));
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    for lock_path in pending {
+        if !is_held(&lock_path)? {
+            let _ = fs::remove_file(&lock_path);
+        }
+    }
+
+    let mut removed = Vec::new();
+    for name in names {
+        let dir = root.join(&name);
+        let lock_path = dir.with_extension(STAGING_LOCK_EXTENSION);
+        if is_held(&lock_path)? {
+            continue;
+        }
+
+        if dir.exists() {
+            fs::remove_dir_all(&dir).with_context(|| format!("removing {}", dir.display()))?;
+            removed.push(dir);
+        }
+        let _ = fs::remove_file(&lock_path);
+    }
+    Ok(removed)
+}
+
+/// Whether a live run holds the lock file at `lock_path`. A missing one is not.
+fn is_held(lock_path: &Path) -> Result<bool> {
+    match File::open(lock_path) {
+        Ok(lock) => match lock.try_lock() {
+            Ok(()) => Ok(false),
+            Err(TryLockError::WouldBlock) => Ok(true),
+            Err(TryLockError::Error(e)) => {
+                Err(e).with_context(|| format!("locking {}", lock_path.display()))
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("opening {}", lock_path.display())),
+    }
+}
+
+/// Publish `staging_dir` as a new generation of `output_dir` and make it the
+/// current one, returning its id.
+///
+/// The output directory holds complete outputs side by side under
+/// `generations/<id>`, with a `current` pointer naming the one being served
+/// (see [`mp_stats_common::generation`]). The staging tree is moved into a
+/// generation of its own and the pointer only moves once that is complete, so
+/// a crash or a failed copy leaves the previous output current and untouched.
 ///
 /// Prefer an atomic `rename`, which is effectively instant and performs no data
 /// copy at all. This works whenever staging and output live on the same
 /// filesystem (the common case). When they don't (e.g. crossing a device/mount
-/// boundary, which surfaces as an OS error), fall back to a recursive copy plus
-/// cleanup.
-pub fn finalize_output(staging_dir: &Path, output_dir: &Path) -> Result<()> {
-    if output_dir.exists() {
-        fs::remove_dir_all(output_dir)?;
+/// boundary, which surfaces as an OS error), fall back to a recursive copy into
+/// a hidden directory, renamed into place once the copy is complete.
+///
+/// An output directory still in the flat layout, from before generations, is
+/// cleared of it once the pointer has moved.
+pub fn finalize_output(staging_dir: &Path, output_dir: &Path) -> Result<String> {
+    let generations = output_dir.join(GENERATIONS_DIR);
+    fs::create_dir_all(&generations)?;
+
+    let id = new_generation_id(output_dir);
+    let target = generation::generation_dir(output_dir, &id);
+
+    if fs::rename(staging_dir, &target).is_err() {
+        let partial = generations.join(format!(".{id}.partial"));
+        let copied = copy_dir_all(staging_dir, &partial)
+            .and_then(|()| fs::rename(&partial, &target).map_err(Into::into));
+        if let Err(e) = copied {
+            let _ = fs::remove_dir_all(&partial);
+            return Err(e.context(format!("writing generation {id}")));
+        }
+        fs::remove_dir_all(staging_dir)?;
     }
-    if let Some(parent) = output_dir.parent() {
-        fs::create_dir_all(parent)?;
+
+    generation::publish(output_dir, &id)?;
+    remove_flat_output(output_dir)?;
+    Ok(id)
+}
+
+/// The ids of the generations under `output_dir`, oldest first.
+pub fn list_generations(output_dir: &Path) -> Result<Vec<String>> {
+    let generations = output_dir.join(GENERATIONS_DIR);
+    let entries = match fs::read_dir(&generations) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", generations.display())),
+    };
+
+    let mut ids = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let id = entry.file_name().to_string_lossy().into_owned();
+        // Generations still being copied in are hidden until they are complete.
+        if entry.file_type()?.is_dir() && !id.starts_with('.') {
+            ids.push(id);
+        }
     }
+    ids.sort_by(|a, b| generation_order(a).cmp(&generation_order(b)));
+    Ok(ids)
+}
+
+/// Remove all but the `keep` newest generations, returning the ids removed.
+/// The current generation is always kept, even when a rollback made it older
+/// than `keep` others.
+pub fn prune_generations(output_dir: &Path, keep: usize) -> Result<Vec<String>> {
+    let current = generation::read_current(output_dir)?;
+    let mut ids = list_generations(output_dir)?;
+    let expired = ids.len().saturating_sub(keep);
 
-    if fs::rename(staging_dir, output_dir).is_ok() {
-        return Ok(());
+    ids.truncate(expired);
+    ids.retain(|id| current.as_deref() != Some(id.as_str()));
+    for id in &ids {
+        let dir = generation::generation_dir(output_dir, id);
+        fs::remove_dir_all(&dir).with_context(|| format!("removing {}", dir.display()))?;
     }
+    Ok(ids)
+}
+
+/// Make generation `to` current again, or without one, the newest generation
+/// older than the current. Returns the id now current.
+pub fn rollback_output(output_dir: &Path, to: Option<&str>) -> Result<String> {
+    let ids = list_generations(output_dir)?;
+    let id = match to {
+        Some(id) if ids.iter().any(|listed| listed == id) => id.to_string(),
+        Some(id) => bail!("no generation {id} in {}", output_dir.display()),
+        None => {
+            let current = generation::read_current(output_dir)?
+                .context("the output directory has no current generation to roll back from")?;
+            ids.into_iter()
+                .rfind(|id| generation_order(id) < generation_order(&current))
+                .with_context(|| format!("no generation is older than the current {current}"))?
+        }
+    };
+
+    generation::publish(output_dir, &id)?;
+    Ok(id)
+}
+
+/// A generation id for a new output: the time in milliseconds, moved past
+/// any generation already written in the same millisecond.
+fn new_generation_id(output_dir: &Path) -> String {
+    let mut millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    while generation::generation_dir(output_dir, &millis.to_string()).exists() {
+        millis += 1;
+    }
+    millis.to_string()
+}
 
-    copy_dir_all(staging_dir, output_dir)?;
-    fs::remove_dir_all(staging_dir)?;
+/// Orders millisecond ids numerically without parsing them, and any other
+/// names after them.
+fn generation_order(id: &str) -> (bool, usize, &str) {
+    let numeric = id.bytes().all(|b| b.is_ascii_digit());
+    (!numeric, id.len(), id)
+}
 
+/// Remove what a flat-layout output left directly in `output_dir`: the
+/// editions' directories and the report beside them. Anything else kept there
+/// is left alone.
+fn remove_flat_output(output_dir: &Path) -> Result<()> {
+    let editions = PlatformEdition::iter().map(|edition| edition.directory_name());
+    for name in editions.chain([REPORT_FILE]) {
+        let path = output_dir.join(name);
+        let removed = match fs::symlink_metadata(&path) {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(&path),
+            Ok(_) => fs::remove_file(&path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => Err(e),
+        };
+        removed.with_context(|| format!("removing {}", path.display()))?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique_dir(tag: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("mp_stats_writer_test_{tag}_{nanos}"))
+    }
+
+    fn stage(staging: &Path, contents: &str) {
+        fs::create_dir_all(staging.join("java")).unwrap();
+        fs::write(staging.join("java/map.bin.xz"), contents).unwrap();
+    }
+
+    fn current_contents(output: &Path) -> String {
+        let root = generation::resolve(output).unwrap();
+        fs::read_to_string(root.join("java/map.bin.xz")).unwrap()
+    }
+
+    #[test]
+    fn staging_defaults_beside_the_output() {
+        assert_eq!(
+            default_staging_root(Path::new("/app/data-dist")),
+            Path::new("/app/.data-dist.staging")
+        );
+        assert_eq!(
+            default_staging_root(Path::new("out")),
+            Path::new(".out.staging")
+        );
+    }
+
+    /// Staging left by a run that is gone is removed; a live run's is not, and
+    /// goes once its claim is dropped.
+    #[test]
+    fn orphaned_staging_is_removed_and_live_staging_kept() {
+        let root = unique_dir("staging_root");
+        let orphan = root.join(format!("{STAGING_PREFIX}1_1_0"));
+        let unlocked = root.join(format!("{STAGING_PREFIX}2_2_0"));
+        fs::create_dir_all(orphan.join("java")).unwrap();
+        fs::create_dir_all(&unlocked).unwrap();
+        fs::write(unlocked.with_extension(STAGING_LOCK_EXTENSION), "").unwrap();
+
+        let live = root.join(format!("{STAGING_PREFIX}3_3_0"));
+        let claim = claim_staging_directory(&live).unwrap();
+        assert!(live.is_dir());
+        assert!(!orphan.exists());
+        assert!(!unlocked.exists());
+        assert!(!unlocked.with_extension(STAGING_LOCK_EXTENSION).exists());
+
+        assert!(remove_orphaned_staging(&root).unwrap().is_empty());
+        assert!(live.is_dir());
+
+        fs::write(live.join("report.json"), "{}").unwrap();
+        drop(claim);
+        assert!(!live.exists());
+        assert!(!live.with_extension(STAGING_LOCK_EXTENSION).exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// A lock file left under its pending name by a run that died before
+    /// renaming it is removed; one a run still holds is not.
+    #[test]
+    fn unlocked_pending_lock_files_are_removed() {
+        let root = unique_dir("pending_root");
+        fs::create_dir_all(&root).unwrap();
+        let left = root.join(format!(".{STAGING_PREFIX}1_1_0.{STAGING_LOCK_EXTENSION}"));
+        fs::write(&left, "").unwrap();
+        let held = root.join(format!(".{STAGING_PREFIX}2_2_0.{STAGING_LOCK_EXTENSION}"));
+        let lock = File::create(&held).unwrap();
+        lock.try_lock().unwrap();
+
+        assert!(remove_orphaned_staging(&root).unwrap().is_empty());
+        assert!(!left.exists());
+        assert!(held.exists());
+
+        drop(lock);
+        remove_orphaned_staging(&root).unwrap();
+        assert!(!held.exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn each_output_is_published_as_a_generation_and_old_ones_pruned() {
+        let output = unique_dir("output");
+        let staging = unique_dir("staging");
+
+        // A flat output from before generations is replaced once the pointer moves.
+        stage(&output, "flat");
+        fs::write(output.join("conversion_report.json"), "{}").unwrap();
+        fs::write(output.join("notes.txt"), "kept").unwrap();
+        fs::write(output.join(crate::report::FAILED_REPORT_FILE), "{}").unwrap();
+
+        let mut ids = Vec::new();
+        for run in ["first", "second", "third"] {
+            stage(&staging, run);
+            ids.push(finalize_output(&staging, &output).unwrap());
+            assert_eq!(current_contents(&output), run);
+            assert!(!staging.exists());
+        }
+        assert!(!output.join("java").exists());
+        assert!(!output.join("conversion_report.json").exists());
+        assert_eq!(
+            fs::read_to_string(output.join("notes.txt")).unwrap(),
+            "kept"
+        );
+        assert!(output.join(crate::report::FAILED_REPORT_FILE).exists());
+        assert_eq!(list_generations(&output).unwrap(), ids);
+
+        assert_eq!(prune_generations(&output, 2).unwrap(), ids[..1]);
+        assert_eq!(list_generations(&output).unwrap(), ids[1..]);
+
+        let _ = fs::remove_dir_all(&output);
+    }
+
+    #[test]
+    fn rollback_moves_the_pointer_back_and_pruning_keeps_it() {
+        let output = unique_dir("rollback");
+        let staging = unique_dir("rollback_staging");
+        assert!(
+            rollback_output(&output, None).is_err(),
+            "nothing to roll back"
+        );
+
+        let mut ids = Vec::new();
+        for run in ["first", "second", "third"] {
+            stage(&staging, run);
+            ids.push(finalize_output(&staging, &output).unwrap());
+        }
+
+        assert_eq!(rollback_output(&output, None).unwrap(), ids[1]);
+        assert_eq!(current_contents(&output), "second");
+        assert_eq!(rollback_output(&output, Some(&ids[0])).unwrap(), ids[0]);
+        assert_eq!(current_contents(&output), "first");
+        assert!(rollback_output(&output, None).is_err(), "nothing older");
+        assert!(rollback_output(&output, Some("missing")).is_err());
+
+        // The current generation survives pruning even when it is the oldest.
+        assert_eq!(prune_generations(&output, 1).unwrap(), ids[1..2]);
+        assert_eq!(
+            list_generations(&output).unwrap(),
+            [ids[0].as_str(), ids[2].as_str()]
+        );
+        assert_eq!(current_contents(&output), "first");
+
+        let _ = fs::remove_dir_all(&output);
+    }
+}
@@ -1,22 +1,39 @@
+pub mod inspect;
 pub mod io;
 pub mod models;
 pub mod pipeline;
-
-use anyhow::Result;
-use mp_stats_config::ConverterConfig;
+pub mod report;
+pub mod validate;
+
+use anyhow::{Context, Result, bail};
+use io::cache::OUTPUT_SCHEMA_VERSION;
+use io::spill::{SPILL_DIR, SpillDir};
+use io::writer::STAGING_PREFIX;
+use mp_stats_common::compression::{self, Compression};
+use mp_stats_common::generation;
+use mp_stats_common::manifest::Manifest;
+use mp_stats_config::{CompressionConfig, ConverterConfig, StrictConfig};
 use mp_stats_core::models::{IdMap, PlatformEdition};
+use std::num::NonZeroU64;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 pub use io::{
-    ConversionCache, copy_dir_all, finalize_output, read_json, setup_staging_directory,
-    validate_different_paths, validate_directory,
+    CacheStatus, CachedOutput, CachedUnits, ConversionCache, Eviction, StagingClaim,
+    claim_staging_directory, copy_dir_all, default_staging_root, finalize_output,
+    link_or_copy_dir_all, list_generations, paths_nest, prune_generations, read_json,
+    remove_orphaned_staging, rollback_output, setup_staging_directory, validate_different_paths,
+    validate_directory,
 };
 use mp_stats_core::routes;
 pub use pipeline::{
-    build_names_archive, process_dictionary_and_names, process_game_metadata,
-    process_java_leaderboards, process_java_players,
+    HistorySpool, build_name_trigrams, build_names_archive, name_histories,
+    process_dictionary_and_names, process_game_metadata, process_leaderboards, process_players,
+    write_player_histories,
+};
+pub use report::{
+    ConversionReport, EditionReport, EditionStatus, Stage, StageCounts, Tally, UnitCounts,
 };
 
 /// Build a process-unique staging directory name.
@@ -34,7 +51,7 @@ fn unique_staging_name() -> String {
         .unwrap_or(0);
     let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
 
-    format!("converter_staging_{}_{}_{}", std::process::id(), nanos, seq)
+    format!("{STAGING_PREFIX}{}_{}_{}", std::process::id(), nanos, seq)
 }
 
 /// Main conversion orchestrator
@@ -43,16 +60,37 @@ pub struct Converter {
     pub output_dir: PathBuf,
     pub staging_dir: PathBuf,
     pub cache: ConversionCache,
+    /// The editions this run converts. Every edition unless narrowed with
+    /// [`Converter::with_editions`].
+    pub editions: Vec<PlatformEdition>,
+    /// When a run that lost work must fail instead of replacing the output.
+    /// Disabled unless set with [`Converter::with_strict`].
+    pub strict: StrictConfig,
+    /// How the output is XZ-compressed. [`Compression::standard`] unless set
+    /// with [`Converter::with_compression`].
+    pub compression: Compression,
+    /// How many generations of output to keep. Three unless set with
+    /// [`Converter::with_keep_generations`].
+    pub keep_generations: usize,
 }
 
 impl Converter {
     /// Build a converter from its configuration block.
     pub fn from_config(config: &ConverterConfig) -> Result<Self> {
-        Self::with_cache(
+        let mut converter = Self::with_cache(
             config.input_dir.clone(),
             config.output_dir.clone(),
             ConversionCache::from_config(&config.cache),
-        )
+        )?;
+        if !config.staging_dir.as_os_str().is_empty() {
+            validate_staging_root(config)?;
+            converter = converter.with_staging_root(config.staging_dir.clone());
+        }
+
+        Ok(converter
+            .with_strict(config.strict.clone())
+            .with_compression(compression_from_config(&config.compression)?)
+            .with_keep_generations(keep_generations_from_config(config)?))
     }
 
     pub fn with_cache(
@@ -72,30 +110,95 @@ impl Converter {
         // a process-local lock cannot serialize access - the integration tests
         // would race over the same staging area and produce corrupt/partial
         // output. A unique path keeps concurrent runs fully isolated while
-        // staying under `target/` so the final `rename` into the output stays
-        // on the same filesystem (and thus cheap) in the common case.
-        let staging_dir = PathBuf::from("target").join(unique_staging_name());
+        // staying beside `output_dir` so the final `rename` into the output
+        // stays on the same filesystem (and thus atomic).
+        let staging_dir = default_staging_root(&output_dir).join(unique_staging_name());
 
         Ok(Self {
             input_dir,
             output_dir,
             staging_dir,
             cache,
+            editions: PlatformEdition::VARIANTS.to_vec(),
+            strict: StrictConfig::default(),
+            compression: Compression::standard(),
+            keep_generations: 3,
         })
     }
 
-    /// Run the full conversion pipeline
-    pub fn convert(&self) -> Result<()> {
+    /// Narrow the run to `editions`.
+    ///
+    /// A narrowed run replaces only the output of the editions it converts and
+    /// leaves every other edition's output untouched, so a single edition can
+    /// be rebuilt without re-converting the rest. An empty list keeps every
+    /// edition.
+    pub fn with_editions(mut self, editions: Vec<PlatformEdition>) -> Self {
+        if !editions.is_empty() {
+            self.editions = editions;
+        }
+        self
+    }
+
+    /// Judge the run by `strict`: see [`Converter::convert`].
+    pub fn with_strict(mut self, strict: StrictConfig) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Write the output with `compression`. The settings are part of the cache
+    /// fingerprint, so output cached under other settings is converted afresh.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Stage under `root` instead of beside the output directory. Publishing
+    /// is only atomic when `root` is on the output directory's filesystem.
+    pub fn with_staging_root(mut self, root: PathBuf) -> Self {
+        self.staging_dir = root.join(unique_staging_name());
+        self
+    }
+
+    /// Keep `keep` generations of output, the current one included; at least
+    /// one always is.
+    pub fn with_keep_generations(mut self, keep: usize) -> Self {
+        self.keep_generations = keep.max(1);
+        self
+    }
+
+    /// Whether this run covers every edition, and so owns the whole output
+    /// rather than only its editions' subdirectories.
+    fn converts_every_edition(&self) -> bool {
+        PlatformEdition::iter().all(|edition| self.editions.contains(edition))
+    }
+
+    /// Run the full conversion pipeline.
+    ///
+    /// The output is published as a new generation of the output directory,
+    /// with the returned report written to [`report::REPORT_FILE`] in it. In
+    /// strict mode a run that lost more work than allowed fails after writing
+    /// the report to [`report::FAILED_REPORT_FILE`] in the output directory
+    /// itself, leaving the current generation as the previous run left it.
+    pub fn convert(&self) -> Result<ConversionReport> {
         println!("Starting data conversion...");
         println!("Input: {:?}", self.input_dir);
         println!("Output: {:?}", self.output_dir);
 
-        // Setup staging
-        setup_staging_directory(&self.staging_dir)?;
+        let started = Instant::now();
+        let started_at_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
 
-        let edition_iter = PlatformEdition::iter();
-        for edition in edition_iter {
+        // Setup staging. The claim removes what is left of it however the run
+        // ends; only a crash leaves it behind, for the next run to clean up.
+        let _staging = claim_staging_directory(&self.staging_dir)?;
+
+        let mut editions = Vec::with_capacity(self.editions.len());
+        for edition in &self.editions {
             println!("Processing {}", edition.display_name());
+            let edition_started = Instant::now();
+            let tally = Tally::default();
 
             // Setup directories
             let directory_in = self.input_dir.join(edition.directory_name());
@@ -106,6 +209,11 @@ impl Converter {
                     directory_in,
                     edition.display_name()
                 );
+                editions.push(tally.into_report(
+                    edition,
+                    EditionStatus::MissingInput,
+                    edition_started.elapsed(),
+                ));
                 continue;
             }
 
@@ -113,7 +221,7 @@ impl Converter {
             // for this edition is byte-for-byte unchanged.
             let edition_key = edition.directory_name();
             let staging_edition = self.staging_dir.join(edition_key);
-            let fingerprint = ConversionCache::fingerprint_dir(&directory_in)?;
+            let fingerprint = ConversionCache::fingerprint(&directory_in, &self.compression)?;
 
             if self
                 .cache
@@ -123,9 +231,19 @@ impl Converter {
                     "  Cache hit for {} - reusing previous output",
                     edition.display_name()
                 );
+                editions.push(tally.into_report(
+                    edition,
+                    EditionStatus::Cached,
+                    edition_started.elapsed(),
+                ));
                 continue;
             }
 
+            // Below the edition, each leaderboard, player file and the dictionary
+            // is cached on its own, so only the units whose input changed are
+            // converted again.
+            let units = self.cache.units(edition_key);
+
             // Step 1: Process Metadata & Build ID Maps
             println!("Step 1: Processing Metadata...");
             let mut id_map = self.process_metadata(edition, &directory_in, &self.staging_dir)?;
@@ -135,17 +253,40 @@ impl Converter {
             // raw names map. The names index is written later (Step 4) once we
             // know which players actually have a profile.
             println!("Step 2: Processing Dictionary & Names...");
-            let (lookup_map, names_map) =
-                process_dictionary_and_names(edition, &directory_in, &self.staging_dir)?;
+            let (lookup_map, names, dictionary) =
+                process_dictionary_and_names(edition, &directory_in, &units)?;
 
             // Step 3: Process Leaderboards
+            // What the pages and the player histories would otherwise hold in
+            // memory until they are written is spilled to scratch files,
+            // removed with `spill` before the edition is done.
             println!("Step 3: Processing Leaderboards...");
-            process_java_leaderboards(edition, &directory_in, &self.staging_dir, &lookup_map)?;
+            let spill = SpillDir::new(self.staging_dir.join(SPILL_DIR));
+            let player_histories = HistorySpool::new(spill.scratch("player_history")?);
+            let outcome = process_leaderboards(
+                edition,
+                &directory_in,
+                &self.staging_dir,
+                &lookup_map,
+                // Pages carry the names and uuids the dictionary resolves.
+                &units.depending_on(dictionary.as_ref()),
+                &spill,
+                &player_histories,
+                &self.compression,
+                &tally,
+            )?;
 
             // Step 3b: Process Game Metadata
             println!("Step 3b: Processing Game Metadata...");
-            let snapshot_totals =
-                process_game_metadata(edition, &directory_in, &self.staging_dir, &id_map)?;
+            let snapshot_totals = process_game_metadata(
+                edition,
+                &directory_in,
+                &self.staging_dir,
+                &id_map,
+                &outcome,
+                &self.compression,
+                &tally,
+            )?;
 
             // Enrich the edition metadata with per-game snapshot counts and
             // re-persist the map so the frontend can show total snapshots.
@@ -156,44 +297,150 @@ impl Converter {
             }
             self.write_metadata(edition, &self.staging_dir, &id_map)?;
 
-            // Step 3c: Process Java Players
+            // Step 3c: Process Players, each with every name it was ranked under
             println!("Step 3c: Processing Players...");
-            let profiled_uuids = process_java_players(
+            let histories = name_histories(&names, &lookup_map, &outcome.sightings);
+            let profiled = process_players(
                 edition,
                 &directory_in,
                 &self.staging_dir,
                 &id_map,
                 &lookup_map,
+                &histories,
+                &units,
+                &self.compression,
+                &tally,
+            )?;
+
+            // Step 3d: Per-player score history, spooled by the leaderboard pass
+            println!("Step 3d: Writing Player Histories...");
+            write_player_histories(
+                edition,
+                &self.staging_dir,
+                player_histories,
+                &self.compression,
+                &tally,
             )?;
+            drop(spill);
 
             // Step 4: Build Names Index (with has_profile flag)
             // Done after players so each name entry can record whether the
             // player actually has a profile, letting the frontend filter out
             // suggestions that would lead to an empty profile page.
             println!("Step 4: Building Names Index...");
-            build_names_archive(edition, &self.staging_dir, names_map, &profiled_uuids)?;
+            build_name_trigrams(
+                edition,
+                &self.staging_dir,
+                &names,
+                &profiled,
+                &self.compression,
+                &tally,
+            )?;
+            build_names_archive(
+                edition,
+                &self.staging_dir,
+                names,
+                &profiled,
+                &self.compression,
+                &tally,
+            )?;
 
-            // Persist this edition's output for future incremental runs.
-            if let Err(e) = self.cache.store(edition_key, fingerprint, &staging_edition) {
+            // Step 4b: Integrity manifest, last so it lists every file above. Written
+            // before the cache store so restored output carries it too.
+            println!("Step 4b: Writing Integrity Manifest...");
+            Manifest::build(&staging_edition, OUTPUT_SCHEMA_VERSION, started_at_unix)?
+                .write(&staging_edition)?;
+
+            // Persist this edition's output for future incremental runs. Output
+            // that lost work is not cached: a cache hit would replay the loss on
+            // every later run until the input changed.
+            if tally.failures() > 0 {
+                println!("  Not caching {edition_key}: the conversion lost work");
+            } else if let Err(e) = self.cache.store(edition_key, fingerprint, &staging_edition) {
                 eprintln!("  Failed to update conversion cache for {edition_key}: {e}");
             }
+
+            editions.push(tally.into_report(
+                edition,
+                EditionStatus::Converted,
+                edition_started.elapsed(),
+            ));
+        }
+
+        let report = ConversionReport {
+            started_at_unix,
+            duration_ms: started.elapsed().as_millis() as u64,
+            editions,
+        };
+        let failures = report.failures();
+
+        if self.strict.enabled && failures > self.strict.max_errors {
+            let path = report.write_as(&self.output_dir, report::FAILED_REPORT_FILE)?;
+            bail!(
+                "conversion lost {failures} unit(s) of work, more than the {} allowed; \
+                 the output was left unchanged, see {}",
+                self.strict.max_errors,
+                path.display()
+            );
+        }
+
+        // Keep the cache within its maximum size, now that this run's entries
+        // are stored and its hits marked as used.
+        match self.cache.evict() {
+            Ok(eviction) if eviction.entries > 0 => println!(
+                "  Evicted {} cache entries ({} bytes), {} bytes remain",
+                eviction.entries, eviction.freed_bytes, eviction.size_bytes
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("  Failed to evict from the conversion cache: {e:#}"),
         }
 
         // Step 5: Finalize
         println!("Step 5: Finalizing Output...");
-        finalize_output(&self.staging_dir, &self.output_dir)?;
+        if !self.converts_every_edition() {
+            // A narrowed run's generation carries over, linked rather than
+            // copied, the editions it did not produce from the current one.
+            let current = generation::resolve(&self.output_dir)?;
+            for edition in PlatformEdition::iter() {
+                let key = edition.directory_name();
+                let (previous, staged) = (current.join(key), self.staging_dir.join(key));
+                if !staged.exists() && previous.is_dir() {
+                    link_or_copy_dir_all(&previous, &staged)
+                        .with_context(|| format!("carrying over the {key} output"))?;
+                }
+            }
+        }
 
-        println!("Conversion Complete!");
-        Ok(())
+        report.write(&self.staging_dir)?;
+        let id = finalize_output(&self.staging_dir, &self.output_dir)?;
+        println!("  Published generation {id}");
+        match prune_generations(&self.output_dir, self.keep_generations) {
+            Ok(pruned) if !pruned.is_empty() => {
+                println!("  Removed generation(s) {}", pruned.join(", "));
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("  Failed to remove old generations: {e:#}"),
+        }
+
+        let path = generation::generation_dir(&self.output_dir, &id).join(report::REPORT_FILE);
+        if failures > 0 {
+            println!(
+                "Conversion Complete with {failures} failure(s), see {}",
+                path.display()
+            );
+        } else {
+            println!("Conversion Complete!");
+        }
+        Ok(report)
     }
 
     fn process_metadata(
         &self,
         platform: &PlatformEdition,
-        java_in: &Path,
+        directory_in: &Path,
         output_dir: &Path,
     ) -> Result<IdMap> {
-        let map_path = java_in.join("meta/map.json");
+        let map_path = directory_in.join("meta/map.json");
         if !map_path.exists() {
             anyhow::bail!("map.json not found at {:?}", map_path);
         }
@@ -215,7 +462,60 @@ impl Converter {
     ) -> Result<()> {
         let relative_path = routes::meta_map_bin(platform);
         let map_out = output_dir.join(relative_path);
-        mp_stats_common::compression::write_lzma_bin(&map_out, id_map)?;
+        compression::write_lzma_bin_with(&map_out, id_map, &self.compression)?;
         Ok(())
     }
 }
+
+/// `converter.staging_dir`, which must not overlap either directory it sits
+/// between: staging in the input would convert the output's own files, and in
+/// the output directory would be cleared as leftovers of the flat layout.
+/// Nesting either way is rejected, not just the same path.
+fn validate_staging_root(config: &ConverterConfig) -> Result<()> {
+    for (dir, name) in [(&config.input_dir, "input"), (&config.output_dir, "output")] {
+        if paths_nest(&config.staging_dir, dir) {
+            bail!(
+                "converter.staging_dir must lie outside the {name} directory and not contain it: {} and {}",
+                config.staging_dir.display(),
+                dir.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `converter.keep_generations`, which cannot be zero: the current generation
+/// is always kept.
+fn keep_generations_from_config(config: &ConverterConfig) -> Result<usize> {
+    if config.keep_generations == 0 {
+        bail!("converter.keep_generations must be at least 1: the current generation is kept");
+    }
+    Ok(config.keep_generations as usize)
+}
+
+/// Resolve the `[converter.compression]` block: the fast preset, or the level,
+/// threads and block size it spells out.
+fn compression_from_config(config: &CompressionConfig) -> Result<Compression> {
+    if config.fast {
+        return Ok(Compression::fast());
+    }
+    if config.level > compression::MAX_LEVEL {
+        bail!(
+            "converter.compression.level must be 0 to {}, not {}",
+            compression::MAX_LEVEL,
+            config.level
+        );
+    }
+
+    let threads = match config.threads {
+        0 => compression::available_threads(),
+        threads => threads,
+    };
+    let block_size = NonZeroU64::new(config.block_size_mib.saturating_mul(1 << 20));
+
+    Ok(Compression {
+        level: config.level,
+        threads,
+        block_size,
+    })
+}
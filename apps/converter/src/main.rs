@@ -1,6 +1,13 @@
-use anyhow::{Context, Result};
+mod cli;
+
+use crate::cli::Command;
+use anyhow::{Context, Result, bail};
+use mp_stats_common::{generation, manifest};
 use mp_stats_config::ConverterConfig;
-use mp_stats_converter::Converter;
+use mp_stats_converter::inspect::inspect_edition;
+use mp_stats_converter::validate::validate_edition;
+use mp_stats_converter::{ConversionCache, Converter, list_generations, rollback_output};
+use mp_stats_core::models::PlatformEdition;
 use serde::Deserialize;
 
 /// Everything the converter reads.
@@ -14,9 +21,215 @@ struct Config {
 }
 
 fn main() -> Result<()> {
+    // Parsed before the configuration is loaded, so `--help` and a mistyped command answer
+    // even when the configuration itself is broken.
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(command) => command,
+        Err(e) => bail!("{e}\n\n{}", cli::USAGE),
+    };
+
     // Layered: struct defaults, then `$MP_STATS_CONFIG`, then `MP_STATS_*`. See
     // `docs/CONFIGURATION.md`.
     let config: Config = mp_stats_config::load().context("loading configuration")?;
+    let config = config.converter;
+
+    match command {
+        Command::Convert { editions } => Converter::from_config(&config)?
+            .with_editions(editions)
+            .convert()
+            .map(|_| ()),
+        Command::Validate { editions } => validate(&config, &editions),
+        Command::Inspect { editions } => inspect(&config, &editions),
+        Command::Verify { editions } => verify(&config, &editions),
+        Command::Generations => generations(&config),
+        Command::Rollback { to } => rollback(&config, to.as_deref()),
+        Command::CacheStatus => cache_status(&config),
+        Command::CacheClear { editions } => cache_clear(&config, &editions),
+        Command::Help => unreachable!("answered before the configuration is loaded"),
+    }
+}
+
+fn validate(config: &ConverterConfig, editions: &[PlatformEdition]) -> Result<()> {
+    let mut total = 0;
+    for edition in cli::editions_or_all(editions) {
+        // A conversion skips an edition whose input is absent, so validating every edition
+        // does too; only an edition asked for by name has to be there.
+        let edition_dir = config.input_dir.join(edition.directory_name());
+        if editions.is_empty() && !edition_dir.exists() {
+            println!("{}: no input, skipped", edition.display_name());
+            continue;
+        }
+
+        let violations = validate_edition(&config.input_dir, &edition)?;
+        println!(
+            "{}: {} problem(s)",
+            edition.display_name(),
+            violations.len()
+        );
+        for violation in &violations {
+            println!("  {violation}");
+        }
+        total += violations.len();
+    }
+
+    if total > 0 {
+        bail!("validation found {total} problem(s)");
+    }
+    Ok(())
+}
+
+fn inspect(config: &ConverterConfig, editions: &[PlatformEdition]) -> Result<()> {
+    let output_dir = generation::resolve(&config.output_dir)?;
+    println!("Output: {}", output_dir.display());
+
+    for edition in cli::editions_or_all(editions) {
+        let Some(summary) = inspect_edition(&output_dir, &edition)? else {
+            println!("{}: not converted", edition.display_name());
+            continue;
+        };
+
+        println!("{}:", edition.display_name());
+        println!(
+            "  {} games, {} boards, {} stats",
+            summary.games, summary.boards, summary.stats
+        );
+        println!(
+            "  {} leaderboards, {} history snapshots",
+            summary.leaderboards, summary.snapshots
+        );
+        println!(
+            "  {} player shards, {} name index files, {} name trigram files",
+            summary.player_shards, summary.name_index_files, summary.name_trigram_files
+        );
+        println!("  {} files, {} bytes", summary.files, summary.size_bytes);
+    }
+
+    Ok(())
+}
+
+fn verify(config: &ConverterConfig, editions: &[PlatformEdition]) -> Result<()> {
+    let output_dir = generation::resolve(&config.output_dir)?;
+    let mut failed = 0;
+    for edition in cli::editions_or_all(editions) {
+        // Like `validate`: an edition that was never converted is only an error when asked for.
+        let edition_dir = output_dir.join(edition.directory_name());
+        if editions.is_empty() && !edition_dir.exists() {
+            println!("{}: not converted, skipped", edition.display_name());
+            continue;
+        }
+
+        match manifest::verify(&edition_dir) {
+            Ok(manifest) => println!(
+                "{}: {} files match the manifest (schema {}, converted at {})",
+                edition.display_name(),
+                manifest.files.len(),
+                manifest.schema_version,
+                manifest.created_at_unix
+            ),
+            Err(e) => {
+                println!("{}: {e}", edition.display_name());
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} edition(s) failed verification");
+    }
+    Ok(())
+}
+
+fn generations(config: &ConverterConfig) -> Result<()> {
+    let current = generation::read_current(&config.output_dir)?;
+    let ids = list_generations(&config.output_dir)?;
+
+    println!("Output: {}", config.output_dir.display());
+    if ids.is_empty() {
+        println!("  no generations");
+    }
+    for id in ids {
+        let marker = if current.as_deref() == Some(id.as_str()) {
+            " (current)"
+        } else {
+            ""
+        };
+        println!("  {id}{marker}");
+    }
+
+    Ok(())
+}
+
+fn rollback(config: &ConverterConfig, to: Option<&str>) -> Result<()> {
+    let id = rollback_output(&config.output_dir, to)?;
+    println!("Generation {id} is current");
+    Ok(())
+}
+
+fn cache_status(config: &ConverterConfig) -> Result<()> {
+    let cache = ConversionCache::new(config.cache.dir.clone());
+    let status = cache.status()?;
+
+    println!(
+        "Cache: {} ({})",
+        cache.root().display(),
+        if config.cache.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+    let limit = match config.cache.max_size_mib {
+        0 => "unbounded".to_string(),
+        mib => format!("at most {mib} MiB"),
+    };
+    println!("  {} bytes in total, {limit}", status.size_bytes);
+    if status.outputs.is_empty() && status.units.is_empty() {
+        println!("  empty");
+    }
+
+    let last_used = |unix: Option<u64>| unix.map_or_else(|| "never".to_string(), |t| t.to_string());
+    for entry in status.outputs {
+        let fingerprint = entry
+            .fingerprint
+            .map_or_else(|| "missing".to_string(), |f| format!("{f:016x}"));
+        println!(
+            "  {}: {} files, {} bytes, fingerprint {fingerprint}, last used {}",
+            entry.key,
+            entry.files,
+            entry.size_bytes,
+            last_used(entry.last_used_unix)
+        );
+    }
+    for units in status.units {
+        println!(
+            "  {} {} units: {} entries, {} bytes, last used {}",
+            units.key,
+            units.kind,
+            units.entries,
+            units.size_bytes,
+            last_used(units.last_used_unix)
+        );
+    }
+
+    Ok(())
+}
+
+fn cache_clear(config: &ConverterConfig, editions: &[PlatformEdition]) -> Result<()> {
+    let cache = ConversionCache::new(config.cache.dir.clone());
+
+    if editions.is_empty() {
+        cache.clear(None)?;
+        println!("Cleared {}", cache.root().display());
+    } else {
+        for edition in editions {
+            cache.clear(Some(edition.directory_name()))?;
+            println!("Cleared {}", edition.display_name());
+        }
+    }
 
-    Converter::from_config(&config.converter)?.convert()
+    Ok(())
 }
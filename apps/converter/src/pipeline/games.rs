@@ -1,5 +1,7 @@
+use crate::pipeline::leaderboards::LeaderboardOutcome;
+use crate::report::{Stage, Tally};
 use anyhow::Result;
-use mp_stats_common::compression::{read_lzma_bin, read_lzma_raw, write_lzma_bin};
+use mp_stats_common::compression::{Compression, read_lzma_bin, write_lzma_bin_with};
 use mp_stats_core::models::{
     GLOBAL_BOARD, GameLeaderboardData, IdMap, LeaderboardMeta, LeaderboardPage, MetaFile,
     PlatformEdition, TopEntry,
@@ -13,41 +15,6 @@ use std::io::BufReader;
 use std::path::Path;
 use walkdir::WalkDir;
 
-fn read_history_data(history_in: &Path) -> Result<Vec<HistoricalSnapshot>> {
-    // Decompress the .xz file first
-    let decompressed_tar = read_lzma_raw(&*history_in)?;
-
-    // Now extract the tar archive
-    let mut archive = tar::Archive::new(std::io::Cursor::new(decompressed_tar));
-
-    let mut snapshots = Vec::new();
-
-    // Extract all files and group by snapshot
-    for entry_result in archive.entries()? {
-        let entry = entry_result?;
-        let path = entry.path()?;
-        let path_str = path.to_string_lossy().to_string();
-
-        if let Some(slash_pos) = path_str.find('/') {
-            let snapshot_name = path_str[..slash_pos].to_string();
-            let file_name = path_str[slash_pos + 1..].to_string();
-
-            if file_name == "_meta.json" {
-                if let Ok(meta) = serde_json::from_reader::<_, MetaFile>(BufReader::new(entry)) {
-                    snapshots.push(HistoricalSnapshot {
-                        snapshot_id: SmolStr::new(&snapshot_name),
-                        timestamp: meta.save_time_unix,
-                        total_pages: meta.total_pages,
-                        total_entries: meta.total_entries,
-                    });
-                }
-            }
-        }
-    }
-
-    Ok(snapshots)
-}
-
 /// Read the `#1 holder` (highest score) from the already-produced latest
 /// leaderboard page (`chunk_0000`) for a given board/game/stat.
 ///
@@ -79,11 +46,18 @@ fn read_top_entry(
 ///
 /// Returns a map of `game_id -> total distinct snapshots` so callers can
 /// enrich the edition-level metadata with snapshot counts.
+///
+/// The history snapshots are the ones `leaderboards` read from each archive,
+/// so no archive is read again here, and each snapshot carries the page score
+/// ranges and distribution recorded for it.
 pub fn process_game_metadata(
     platform: &PlatformEdition,
     in_path: &Path,
     base_out: &Path,
     id_map: &IdMap,
+    leaderboards: &LeaderboardOutcome,
+    compression: &Compression,
+    tally: &Tally,
 ) -> Result<HashMap<SmolStr, u64>> {
     let lb_in = in_path.join("leaderboards");
 
@@ -132,6 +106,8 @@ pub fn process_game_metadata(
                         timestamp: meta.save_time_unix,
                         total_pages: meta.total_pages,
                         total_entries: meta.total_entries,
+                        page_scores: Vec::new(),
+                        distribution: None,
                     });
                 }
 
@@ -144,9 +120,28 @@ pub fn process_game_metadata(
                     None
                 };
 
-                let history_in = stat_path.join("history.tar.xz");
-                if let Ok(history_snapshots) = read_history_data(&history_in) {
-                    all_snapshots.extend(history_snapshots);
+                if let Some(history) =
+                    leaderboards
+                        .history
+                        .get(&(board.clone(), game_id.clone(), stat.clone()))
+                {
+                    all_snapshots.extend(history.iter().cloned());
+                }
+
+                // What the leaderboard pass recorded of each snapshot's scores: the
+                // page ranges for placing an arbitrary score without reading every
+                // page, and the distribution for the chart.
+                for snapshot in &mut all_snapshots {
+                    let key = (
+                        board.clone(),
+                        game_id.clone(),
+                        stat.clone(),
+                        snapshot.snapshot_id.to_string(),
+                    );
+                    if let Some(recorded) = leaderboards.scores.get(&key) {
+                        snapshot.page_scores = recorded.page_scores.clone();
+                        snapshot.distribution = recorded.distribution.clone();
+                    }
                 }
 
                 total_snapshots = total_snapshots.saturating_add(all_snapshots.len() as u64);
@@ -183,7 +178,13 @@ pub fn process_game_metadata(
 
             let relative_out_path = routes::game_bin(platform, game_id);
             let out_path = base_out.join(relative_out_path);
-            let _ = write_lzma_bin(&out_path, &game_data);
+            match write_lzma_bin_with(&out_path, &game_data, compression) {
+                Ok(()) => tally.processed(Stage::Game),
+                Err(e) => tally.failed(
+                    Stage::Game,
+                    format!("Failed to write game metadata {:?}: {}", out_path, e),
+                ),
+            }
 
             (SmolStr::new(game_id), total_snapshots)
         })
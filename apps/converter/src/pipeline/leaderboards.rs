@@ -1,26 +1,134 @@
+use crate::io::spill::{read_spilled, spilled_shards};
+use crate::io::{ShardedSpill, SpillDir, UnitCache};
 use crate::models::leaderboard::binary_leaderboard;
+use crate::pipeline::players::{HistorySpool, HistoryWriter};
+use crate::report::{Stage, Tally, UnitCounts};
 use anyhow::Result;
-use mp_stats_common::compression::{decompress_file_auto, read_lzma_raw, write_lzma_bin};
+use mp_stats_common::compression::{Compression, lzma_reader, read_lzma_bin, write_lzma_bin_with};
+use mp_stats_common::formats::FILE_META;
 use mp_stats_common::formats::raw::ENTRIES_PER_PAGE;
-use mp_stats_core::models::{CompetitionRanker, LeaderboardPage, PlatformEdition};
+use mp_stats_common::shard::position_shard;
+use mp_stats_core::models::{
+    CompetitionRanker, GLOBAL_BOARD, HistoricalSnapshot, HistoryPoint, LeaderboardPage,
+    LeaderboardPosition, MetaFile, PageScoreRange, PlatformEdition, ScoreDistribution,
+};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 use std::collections::HashMap;
 use std::fs::{self};
-use std::io::Read;
+use std::io::{self, BufReader, Read};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 const LEADERBOARD_SIZE: usize = crate::models::leaderboard::BINARY_LEADERBOARD_SIZE;
 
-/// Process all Java leaderboards
-pub fn process_java_leaderboards(
+/// `player_id -> (first, last)`: the earliest and latest snapshot, as
+/// `save_time_unix`, that a dictionary id was ranked in on any board.
+///
+/// A dictionary id stands for one `(uuid, name)` pair, so this dates the names
+/// a player has carried (see
+/// [`name_histories`](crate::pipeline::metadata::name_histories)).
+pub type Sightings = HashMap<String, (u64, u64)>;
+
+/// What the game metadata carries about the scores of one written snapshot.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotScores {
+    /// The score range of each page, in page order.
+    pub page_scores: Vec<PageScoreRange>,
+    /// How the written rows' scores are spread; `None` without a row.
+    pub distribution: Option<ScoreDistribution>,
+}
+
+/// `(board, game, stat, snapshot_id) -> scores` for every snapshot written,
+/// `latest` included.
+pub type ScoreSummaries = HashMap<(String, String, String, String), SnapshotScores>;
+
+/// `snapshot_id -> scores` for the history snapshots of one leaderboard.
+type HistoryScores = HashMap<String, SnapshotScores>;
+
+/// `(board, game, stat) -> snapshots` for every leaderboard with a history,
+/// each snapshot as its `_meta.json` describes it, in archive order.
+pub type HistorySnapshots = HashMap<(String, String, String), Vec<HistoricalSnapshot>>;
+
+/// What the leaderboard pass learns beyond the pages it writes, for the steps
+/// that run after it.
+///
+/// It is also what a leaderboard restored from the unit cache hands on in
+/// place of being converted, so whatever a later step needs of the input
+/// belongs here rather than being read again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LeaderboardOutcome {
+    /// When each dictionary id was ranked, across every board and snapshot
+    /// with a readable timestamp.
+    pub sightings: Sightings,
+    /// The page score ranges and score distribution of every snapshot written.
+    pub scores: ScoreSummaries,
+    /// The history snapshots of every leaderboard.
+    pub history: HistorySnapshots,
+}
+
+impl LeaderboardOutcome {
+    fn merge(mut self, other: Self) -> Self {
+        merge_sightings(&mut self.sightings, other.sightings);
+        self.scores.extend(other.scores);
+        self.history.extend(other.history);
+        self
+    }
+}
+
+/// Record that `player_id` was ranked in a snapshot taken at `at`.
+fn sighted(sightings: &mut Sightings, player_id: &str, at: u64) {
+    match sightings.get_mut(player_id) {
+        Some((first, last)) => {
+            *first = (*first).min(at);
+            *last = (*last).max(at);
+        }
+        None => {
+            sightings.insert(player_id.to_string(), (at, at));
+        }
+    }
+}
+
+fn merge_sightings(into: &mut Sightings, from: Sightings) {
+    for (player_id, (first, last)) in from {
+        let span = into.entry(player_id).or_insert((first, last));
+        span.0 = span.0.min(first);
+        span.1 = span.1.max(last);
+    }
+}
+
+/// What a [`PageWriter`] wrote for one snapshot.
+struct WrittenPages {
+    pages: u32,
+    entries: u32,
+    scores: SnapshotScores,
+}
+
+/// Process all leaderboards of one edition.
+///
+/// Java and Bedrock chunks share one binary layout; the only difference is
+/// that a Bedrock row's player id resolves to a gamertag rather than a UUID,
+/// which the output carries in the `uuid` column all the same.
+///
+/// Every row of the global board goes to `histories` as it is ranked, for the
+/// player histories; only the global board is tracked, matching the stats a
+/// profile shows. Rows ranked before their snapshot's `_meta.json` dates
+/// them, and what the pages need until they are finished, wait in scratch
+/// files under `spill`.
+#[allow(clippy::too_many_arguments)]
+pub fn process_leaderboards(
     platform: &PlatformEdition,
-    java_in: &Path,
+    directory_in: &Path,
     output_dir: &Path,
     lookup_map: &HashMap<String, (String, String)>,
-) -> Result<()> {
-    let lb_in = java_in.join("leaderboards");
+    units: &UnitCache,
+    spill: &SpillDir,
+    histories: &HistorySpool,
+    compression: &Compression,
+    tally: &Tally,
+) -> Result<LeaderboardOutcome> {
+    let lb_in = directory_in.join("leaderboards");
 
     let walker = WalkDir::new(&lb_in).into_iter();
     // Filter for .../latest directories
@@ -35,56 +143,301 @@ pub fn process_java_leaderboards(
         latest_dirs.len()
     );
 
-    latest_dirs.par_iter().for_each(|latest_dir| {
-        let _ = process_single_leaderboard(platform, latest_dir, output_dir, lookup_map);
-    });
+    let outcome = latest_dirs
+        .par_iter()
+        .map(|latest_dir| {
+            match process_single_leaderboard(
+                platform,
+                latest_dir,
+                output_dir,
+                lookup_map,
+                units,
+                spill,
+                histories,
+                compression,
+                tally,
+            ) {
+                Ok(outcome) => {
+                    tally.processed(Stage::Leaderboard);
+                    outcome
+                }
+                Err(e) => {
+                    tally.failed(
+                        Stage::Leaderboard,
+                        format!("Failed to process leaderboard {:?}: {:#}", latest_dir, e),
+                    );
+                    LeaderboardOutcome::default()
+                }
+            }
+        })
+        .reduce(LeaderboardOutcome::default, LeaderboardOutcome::merge);
 
-    Ok(())
+    Ok(outcome)
 }
 
-/// Process a single leaderboard directory
+/// Process a single leaderboard directory, or restore it from `units` when
+/// none of its input changed.
+///
+/// A leaderboard is one unit, keyed by its board/game/stat names, every file
+/// under its stat directory and how its output is compressed; the dictionary
+/// its players are resolved through is already part of every key `units`
+/// hands out. Only a leaderboard converted without losing anything is stored.
+/// A restored global board spools its points from the pages it restored.
+#[allow(clippy::too_many_arguments)]
 fn process_single_leaderboard(
     platform: &PlatformEdition,
     latest_in: &Path,
     output_dir: &Path,
     lookup_map: &HashMap<String, (String, String)>,
-) -> Result<()> {
+    units: &UnitCache,
+    spill: &SpillDir,
+    histories: &HistorySpool,
+    compression: &Compression,
+    tally: &Tally,
+) -> Result<LeaderboardOutcome> {
     // Structure: .../[board]/[game]/[stat]/latest
     let stat_dir = latest_in.parent().unwrap();
     let game_dir = stat_dir.parent().unwrap();
     let board_dir = game_dir.parent().unwrap();
+    let names = [board_dir, game_dir, stat_dir].map(|dir| dir.file_name().unwrap());
+
+    // Output Paths
+    // TODO: Correctly migrate to routes
+    let out_stat_dir = names.iter().fold(
+        output_dir
+            .join(platform.directory_name())
+            .join("leaderboards"),
+        |path, name| path.join(name),
+    );
+
+    // Points are only spooled for the board the player histories cover.
+    let mut points = names[0]
+        .eq_ignore_ascii_case(GLOBAL_BOARD)
+        .then(|| {
+            let [board, game, stat] = names.map(|name| name.to_string_lossy());
+            histories.writer(spill, &board, &game, &stat)
+        })
+        .transpose()?;
+
+    let key = units.key("leaderboard", |key| {
+        for name in names {
+            key.bytes(name.as_encoded_bytes());
+        }
+        key.bytes(format!("{:?}", compression.encoding()).as_bytes());
+        key.dir(stat_dir)
+    })?;
+    if let Some(key) = &key
+        && let Some((counts, outcome)) =
+            units.restore::<(UnitCounts, LeaderboardOutcome)>(key, Some(&out_stat_dir))
+    {
+        tally.reused(&counts);
+        if let Some(mut points) = points {
+            spool_written_points(
+                &out_stat_dir,
+                outcome.history.values().flatten(),
+                &mut points,
+            )?;
+            points.finish()?;
+        }
+        return Ok(outcome);
+    }
+
+    let unit = Tally::default();
+    let outcome = convert_leaderboard(
+        latest_in,
+        &out_stat_dir,
+        lookup_map,
+        spill,
+        points.as_mut(),
+        compression,
+        &unit,
+    );
+    let (counts, lost) = (unit.counts(), unit.failures());
+    tally.absorb(unit);
+    let outcome = outcome?;
+    if let Some(points) = points {
+        points.finish()?;
+    }
+
+    if let Some(key) = &key
+        && lost == 0
+        && let Err(e) = units.store(key, &(&counts, &outcome), Some(&out_stat_dir))
+    {
+        eprintln!(
+            "  Failed to cache leaderboard {}: {e:#}",
+            stat_dir.display()
+        );
+    }
+    Ok(outcome)
+}
+
+/// Convert the leaderboard `latest_in` is the latest snapshot of into
+/// `out_stat_dir`, handing each dated row to `points` when there is one.
+fn convert_leaderboard(
+    latest_in: &Path,
+    out_stat_dir: &Path,
+    lookup_map: &HashMap<String, (String, String)>,
+    spill: &SpillDir,
+    mut points: Option<&mut HistoryWriter<'_>>,
+    compression: &Compression,
+    tally: &Tally,
+) -> Result<LeaderboardOutcome> {
+    let stat_dir = latest_in.parent().unwrap();
+    let game_dir = stat_dir.parent().unwrap();
+    let board_dir = game_dir.parent().unwrap();
 
     let stat_name = stat_dir.file_name().unwrap();
     let game_name = game_dir.file_name().unwrap();
     let board_name = board_dir.file_name().unwrap();
 
-    // Output Paths
-    // TODO: Correctly migrate to routes
-    let out_stat_dir = output_dir
-        .join(platform.directory_name())
-        .join("leaderboards")
-        .join(board_name)
-        .join(game_name)
-        .join(stat_name);
-    std::fs::create_dir_all(&out_stat_dir)?;
+    std::fs::create_dir_all(out_stat_dir)?;
     let out_latest = out_stat_dir.join("latest");
     std::fs::create_dir_all(&out_latest)?;
 
     // Process Latest Chunks
-    process_latest_chunks(latest_in, &out_latest, lookup_map)?;
+    let (mut sightings, latest_scores) = process_latest_chunks(
+        latest_in,
+        &out_latest,
+        lookup_map,
+        spill,
+        points.as_deref_mut(),
+        compression,
+        tally,
+    )?;
 
     // Process History (now using rich format with lookup_map)
-    process_history(stat_dir, &out_stat_dir, lookup_map)?;
+    let history = process_history(
+        stat_dir,
+        out_stat_dir,
+        lookup_map,
+        spill,
+        points,
+        compression,
+        tally,
+    )?;
+    merge_sightings(&mut sightings, history.sightings);
+
+    let leaderboard = (
+        board_name.to_string_lossy().to_string(),
+        game_name.to_string_lossy().to_string(),
+        stat_name.to_string_lossy().to_string(),
+    );
+    let key = |snapshot_id: String| {
+        let (board, game, stat) = leaderboard.clone();
+        (board, game, stat, snapshot_id)
+    };
+    let scores = history
+        .scores
+        .into_iter()
+        .chain([("latest".to_string(), latest_scores)])
+        .map(|(snapshot_id, scores)| (key(snapshot_id), scores))
+        .collect();
 
+    let history = if history.snapshots.is_empty() {
+        HashMap::new()
+    } else {
+        HashMap::from([(leaderboard, history.snapshots)])
+    };
+
+    Ok(LeaderboardOutcome {
+        sightings,
+        scores,
+        history,
+    })
+}
+
+/// Record a row `(player_id, uuid, score, rank)` of `snapshot_id`, taken at
+/// `timestamp`: its player id is sighted, and its point handed to `points`.
+fn record_row(
+    sightings: &mut Sightings,
+    points: Option<&mut HistoryWriter<'_>>,
+    snapshot_id: &str,
+    timestamp: u64,
+    (player_id, uuid, score, rank): (&str, &str, u64, u32),
+) {
+    sighted(sightings, player_id, timestamp);
+    if let Some(points) = points {
+        points.push(
+            uuid,
+            HistoryPoint {
+                snapshot_id: SmolStr::new(snapshot_id),
+                timestamp,
+                score,
+                rank,
+            },
+        );
+    }
+}
+
+/// Spool the points of a leaderboard restored from the unit cache, read back
+/// from the pages it restored: those of its latest snapshot, dated by the
+/// `_meta.json` beside them, and those of each history snapshot in
+/// `snapshots`.
+fn spool_written_points<'s>(
+    out_stat_dir: &Path,
+    snapshots: impl IntoIterator<Item = &'s HistoricalSnapshot>,
+    points: &mut HistoryWriter<'_>,
+) -> Result<()> {
+    let latest = out_stat_dir.join("latest");
+    let latest = fs::File::open(latest.join(FILE_META))
+        .ok()
+        .and_then(read_meta)
+        .map(|meta| (SmolStr::new("latest"), latest, meta.save_time_unix));
+    let history = snapshots.into_iter().map(|snapshot| {
+        (
+            snapshot.snapshot_id.clone(),
+            out_stat_dir
+                .join("history")
+                .join(snapshot.snapshot_id.as_str()),
+            snapshot.timestamp,
+        )
+    });
+
+    for (snapshot_id, dir, timestamp) in latest.into_iter().chain(history) {
+        for number in 0.. {
+            let path = page_path(&dir, number);
+            if !path.exists() {
+                break;
+            }
+            let page: LeaderboardPage = read_lzma_bin(&path)?;
+            for ((uuid, &score), &rank) in page.uuids.iter().zip(&page.scores).zip(&page.ranks) {
+                points.push(
+                    uuid,
+                    HistoryPoint {
+                        snapshot_id: snapshot_id.clone(),
+                        timestamp,
+                        score,
+                        rank,
+                    },
+                );
+            }
+        }
+    }
     Ok(())
 }
 
-/// Process latest leaderboard chunks
+/// Read a snapshot's metadata file.
+fn read_meta(reader: impl Read) -> Option<MetaFile> {
+    serde_json::from_reader(BufReader::new(reader)).ok()
+}
+
+/// Process latest leaderboard chunks.
+///
+/// Returns the [`Sightings`] of the snapshot, empty without a readable
+/// timestamp, and what its scores look like. With a timestamp, every row is
+/// handed to `points` when there is one.
+///
+/// Chunks are decompressed as they are read, one at a time in file name
+/// order, so none of them is held whole.
 fn process_latest_chunks(
     latest_in: &Path,
     out_latest: &Path,
     lookup_map: &HashMap<String, (String, String)>,
-) -> Result<()> {
+    spill: &SpillDir,
+    mut points: Option<&mut HistoryWriter<'_>>,
+    compression: &Compression,
+    tally: &Tally,
+) -> Result<(Sightings, SnapshotScores)> {
     let mut chunk_files = Vec::new();
 
     for entry in fs::read_dir(latest_in)? {
@@ -100,212 +453,874 @@ fn process_latest_chunks(
     // Sort chunks by filename to ensure correct order - CRITICAL for maintaining rank order
     chunk_files.sort();
 
-    // Load compressed chunks and decompress them
-    let decompressed_chunks: Vec<Vec<u8>> = chunk_files
-        .iter()
-        .filter_map(|path| match decompress_file_auto(path) {
-            Ok(data) => {
-                println!(
-                    "Processing chunk {:?} with {} entries...",
-                    path,
-                    data.len() / LEADERBOARD_SIZE
-                );
-                Some(data)
-            }
-            Err(e) => {
-                eprintln!("Failed to decompress chunk {:?}: {}", path, e);
-                None
-            }
-        })
-        .collect();
+    let timestamp = fs::File::open(latest_in.join(FILE_META))
+        .ok()
+        .and_then(read_meta)
+        .map(|meta| meta.save_time_unix);
 
-    // Process chunks using shared logic
-    process_binary_chunks(&decompressed_chunks, out_latest, lookup_map)?;
+    let mut sightings = Sightings::new();
+    let mut pages = PageWriter::new(
+        out_latest.to_path_buf(),
+        spill.scratch("latest")?,
+        lookup_map,
+        compression,
+        tally,
+    )?;
+    for path in &chunk_files {
+        let streamed = lzma_reader(path).map_err(Into::into).and_then(|reader| {
+            pages.push_chunk(reader, |player_id, uuid, score, rank| {
+                if let Some(timestamp) = timestamp {
+                    record_row(
+                        &mut sightings,
+                        points.as_deref_mut(),
+                        "latest",
+                        timestamp,
+                        (player_id, uuid, score, rank),
+                    );
+                }
+            })
+        });
+        match streamed {
+            Ok(count) => println!("Processed chunk {:?} with {} entries", path, count),
+            Err(e) => tally.failed(
+                Stage::Chunk,
+                format!("Failed to decompress chunk {:?}: {}", path, e),
+            ),
+        }
+    }
+    let written = pages.finish();
 
-    Ok(())
+    Ok((sightings, written.scores))
 }
 
-/// Shared logic to process binary chunks and convert to rich format
-fn process_binary_chunks(
-    chunks: &[Vec<u8>],
-    output_dir: &Path,
-    lookup_map: &HashMap<String, (String, String)>,
-) -> Result<(u32, u32)> {
-    let mut output_index = 0;
-    let mut current_page = LeaderboardPage {
-        ranks: Vec::with_capacity(ENTRIES_PER_PAGE),
-        uuids: Vec::with_capacity(ENTRIES_PER_PAGE),
-        names: Vec::with_capacity(ENTRIES_PER_PAGE),
-        scores: Vec::with_capacity(ENTRIES_PER_PAGE),
-    };
+/// Turns one snapshot's ranked records into pages as they stream in.
+///
+/// Each record is resolved through the dictionary and ranked as it arrives,
+/// and each page is written as soon as it fills, so the rows held at once are
+/// one page's worth. Each written row's place on the pages and its score go
+/// to scratch files under `scratch`, to be read back for the position index
+/// and the score distribution. Rows whose player id the dictionary does not
+/// know are left out and counted in the tally, as is every chunk read and
+/// every page written or lost.
+///
+/// [`finish`](Self::finish) writes the last page and, next to the pages, the
+/// snapshot's position index: where on them each player sits, sharded by
+/// [`position_shard`] under `positions/`.
+struct PageWriter<'a> {
+    output_dir: PathBuf,
+    scratch: PathBuf,
+    lookup_map: &'a HashMap<String, (String, String)>,
+    compression: &'a Compression,
+    tally: &'a Tally,
+    page: LeaderboardPage,
+    /// Rows at the front of `page` already spilled, when it was read back to
+    /// be filled further.
+    filed: usize,
     // Standard competition ranking ("1224"): entries sharing the same score
     // receive the same rank, and the next distinct score jumps to its positional
     // index. The shared `CompetitionRanker` keeps this identical to the
     // player-profile pipeline.
-    let mut ranker = CompetitionRanker::new();
-    let mut total_entries_written = 0u32;
-
-    for chunk_data in chunks {
-        let count = chunk_data.len() / LEADERBOARD_SIZE;
-
-        for i in 0..count {
-            let offset = i * LEADERBOARD_SIZE;
-            if offset + LEADERBOARD_SIZE > chunk_data.len() {
-                eprintln!(
-                    "Invalid chunk data: offset {} + size {} > chunk size {}",
-                    offset,
-                    LEADERBOARD_SIZE,
-                    chunk_data.len()
-                );
-                break;
+    ranker: CompetitionRanker,
+    chunks: u32,
+    pages: u32,
+    entries: u32,
+    score_ranges: Vec<PageScoreRange>,
+    /// `(uuid, position)` of every written row, by position shard.
+    positions: ShardedSpill,
+    /// The score of every written row, in page order.
+    scores: ShardedSpill,
+    scored: usize,
+    /// The page written short when the snapshot was set aside.
+    short_page: Option<u32>,
+}
+
+/// The one shard of a [`PageWriter`]'s spilled scores.
+const SCORES: &str = "scores";
+
+impl<'a> PageWriter<'a> {
+    fn new(
+        output_dir: PathBuf,
+        scratch: PathBuf,
+        lookup_map: &'a HashMap<String, (String, String)>,
+        compression: &'a Compression,
+        tally: &'a Tally,
+    ) -> Result<Self> {
+        let (positions, scores) = (scratch.join("positions"), scratch.join(SCORES));
+        fs::create_dir_all(&positions)?;
+        fs::create_dir_all(&scores)?;
+        Ok(Self {
+            output_dir,
+            scratch,
+            lookup_map,
+            compression,
+            tally,
+            page: empty_page(),
+            filed: 0,
+            ranker: CompetitionRanker::new(),
+            chunks: 0,
+            pages: 0,
+            entries: 0,
+            score_ranges: Vec::new(),
+            positions: ShardedSpill::new(positions),
+            scores: ShardedSpill::new(scores),
+            scored: 0,
+            short_page: None,
+        })
+    }
+
+    /// Add the records of one chunk, read from `reader` in order, and return
+    /// how many there were. `on_entry` sees every row kept as
+    /// `(player_id, uuid, score, rank)`.
+    ///
+    /// A read error leaves the rows before it in place.
+    fn push_chunk(
+        &mut self,
+        reader: impl Read,
+        mut on_entry: impl FnMut(&str, &str, u64, u32),
+    ) -> Result<usize> {
+        let mut reader = BufReader::with_capacity(LEADERBOARD_SIZE * ENTRIES_PER_PAGE, reader);
+        let mut record = [0u8; LEADERBOARD_SIZE];
+        let mut count = 0;
+        while read_record(&mut reader, &mut record)? {
+            self.push(&record, &mut on_entry);
+            count += 1;
+        }
+        self.chunks += 1;
+        self.tally.processed(Stage::Chunk);
+        Ok(count)
+    }
+
+    fn push(&mut self, record: &[u8], on_entry: &mut impl FnMut(&str, &str, u64, u32)) {
+        let view = binary_leaderboard::View::new(record);
+        let pid = view.player_id().read();
+        let score = view.score().read();
+
+        if pid == 0 {
+            self.tally.unresolved("0");
+            return;
+        }
+
+        // Resolve Name/UUID
+        let pid_str = pid.to_string();
+        let Some((uuid, name)) = self.lookup_map.get(&pid_str) else {
+            self.tally.unresolved(&pid_str);
+            return;
+        };
+
+        // Compute rank: same score shares the rank of the first entry
+        // that achieved it, otherwise it takes the current position.
+        let rank = self.ranker.next_rank(score);
+
+        // Add to current page (columnar format)
+        self.page.ranks.push(rank);
+        self.page.uuids.push(SmolStr::new(uuid));
+        self.page.names.push(SmolStr::new(name));
+        self.page.scores.push(score);
+        on_entry(&pid_str, uuid, score, rank);
+        self.entries += 1;
+
+        if self.page.ranks.len() >= ENTRIES_PER_PAGE {
+            self.write_page(false);
+        }
+    }
+
+    /// Write the page being filled, if it holds anything, and start the next.
+    ///
+    /// A page written `short` is one the snapshot was set aside on; it is read
+    /// back should the snapshot resume, and counted once it is known to be
+    /// final.
+    fn write_page(&mut self, short: bool) {
+        if self.page.ranks.is_empty() {
+            return;
+        }
+        let page = std::mem::replace(&mut self.page, empty_page());
+        let filed = std::mem::take(&mut self.filed);
+
+        let dest_path = page_path(&self.output_dir, self.pages);
+        if let Err(e) = write_lzma_bin_with(&dest_path, &page, self.compression) {
+            self.tally.failed(
+                Stage::Page,
+                format!("Failed to write page {:?}: {}", dest_path, e),
+            );
+            return;
+        }
+        if short {
+            self.short_page = Some(self.pages);
+        } else {
+            self.tally.processed(Stage::Page);
+        }
+        self.file_rows(&page, filed);
+        self.score_ranges.extend(score_range(&page));
+        self.pages += 1;
+    }
+
+    /// Spill where each row of `written`, page number `self.pages`, sits and
+    /// its score, from row `from` on.
+    fn file_rows(&mut self, written: &LeaderboardPage, from: usize) {
+        let rows = written.uuids.iter().zip(&written.ranks).enumerate();
+        for (row, (uuid, &rank)) in rows.skip(from) {
+            let position = LeaderboardPosition {
+                page: self.pages,
+                row: row as u32,
+                rank,
+            };
+            match position_shard(uuid) {
+                Ok(shard) => self.positions.push(&shard, &(uuid, position)),
+                Err(e) => self
+                    .tally
+                    .failed(Stage::PositionIndex, format!("{uuid:?}: {e}")),
             }
+        }
+        for score in &written.scores[from..] {
+            self.scores.push(SCORES, score);
+        }
+        self.scored += written.scores.len() - from;
+    }
 
-            let view =
-                binary_leaderboard::View::new(&chunk_data[offset..offset + LEADERBOARD_SIZE]);
-            let pid = view.player_id().read();
-            let score = view.score().read();
+    /// Put the snapshot aside while the archive moves on to others: the page
+    /// being filled is written short and what was spilled is flushed, so a
+    /// snapshot set aside holds next to nothing.
+    fn set_aside(&mut self) {
+        self.write_page(true);
+        self.page = LeaderboardPage {
+            ranks: Vec::new(),
+            uuids: Vec::new(),
+            names: Vec::new(),
+            scores: Vec::new(),
+        };
+        if let Err(e) = self.positions.flush().and(self.scores.flush()) {
+            self.tally.failed(
+                Stage::Snapshot,
+                format!("Failed to spill the rows of {:?}: {:#}", self.output_dir, e),
+            );
+        }
+    }
 
-            if pid == 0 {
-                eprintln!("Invalid player ID: {}", pid);
-                continue;
+    /// Carry on with a snapshot [set aside](Self::set_aside): its short page
+    /// is read back and filled further under the same number.
+    fn resume(&mut self) {
+        self.page = empty_page();
+        let Some(short_page) = self.short_page.take() else {
+            return;
+        };
+        let path = page_path(&self.output_dir, short_page);
+        match read_lzma_bin::<LeaderboardPage>(&path) {
+            Ok(page) => {
+                self.filed = page.ranks.len();
+                self.page = page;
+                self.score_ranges.pop();
+                self.pages = short_page;
             }
+            // It stays as written, and the rows that follow start a new page.
+            Err(e) => self.tally.failed(
+                Stage::Page,
+                format!("Failed to read back page {:?}: {}", path, e),
+            ),
+        }
+    }
+
+    fn finish(mut self) -> WrittenPages {
+        self.write_page(false);
+        if self.short_page.is_some() {
+            // Set aside and never resumed: the short page is its last.
+            self.tally.processed(Stage::Page);
+        }
+        self.write_positions();
+        let distribution = self.distribution();
+        let _ = fs::remove_dir_all(&self.scratch);
+
+        println!(
+            "Processed {} chunks with {} total entries",
+            self.chunks, self.entries
+        );
+
+        WrittenPages {
+            pages: self.pages,
+            entries: self.entries,
+            scores: SnapshotScores {
+                page_scores: self.score_ranges,
+                distribution,
+            },
+        }
+    }
 
-            // Resolve Name/UUID
-            let pid_str = pid.to_string();
-            if let Some((uuid, name)) = lookup_map.get(&pid_str) {
-                // Compute rank: same score shares the rank of the first entry
-                // that achieved it, otherwise it takes the current position.
-                let rank = ranker.next_rank(score);
-
-                // Add to current page (columnar format)
-                current_page.ranks.push(rank);
-                current_page.uuids.push(SmolStr::new(uuid));
-                current_page.names.push(SmolStr::new(name));
-                current_page.scores.push(score);
-
-                total_entries_written += 1;
-
-                // If page full, write it
-                if current_page.ranks.len() >= ENTRIES_PER_PAGE {
-                    let dest_name = format!("chunk_{:04}.bin.xz", output_index);
-                    let dest_path = output_dir.join(dest_name);
-                    if let Err(e) = write_lzma_bin(&dest_path, &current_page) {
-                        eprintln!("Failed to write page {:?}: {}", dest_path, e);
-                    } else {
-                        output_index += 1;
+    /// Write the position index as `positions/{shard}.bin.xz` under the
+    /// output directory, each shard a `uuid -> position` map read back from
+    /// the spill. A player listed twice keeps their first, and so best, place.
+    fn write_positions(&mut self) {
+        let spilled = self.scratch.join("positions");
+        let shards = match self
+            .positions
+            .flush()
+            .and_then(|()| spilled_shards(&spilled))
+        {
+            Ok(shards) => shards,
+            Err(e) => {
+                self.tally.failed(
+                    Stage::PositionIndex,
+                    format!(
+                        "Failed to spill the position index of {:?}: {:#}",
+                        self.output_dir, e
+                    ),
+                );
+                return;
+            }
+        };
+
+        let positions_dir = self.output_dir.join("positions");
+        for (shard, spilled) in shards {
+            let dest_path = positions_dir.join(format!("{shard}.bin.xz"));
+            let written =
+                read_spilled::<(SmolStr, LeaderboardPosition)>(&spilled).and_then(|records| {
+                    let mut positions = HashMap::new();
+                    for record in records {
+                        let (uuid, position) = record?;
+                        positions.entry(uuid).or_insert(position);
                     }
-                    // Reset page
-                    current_page = LeaderboardPage {
-                        ranks: Vec::with_capacity(ENTRIES_PER_PAGE),
-                        uuids: Vec::with_capacity(ENTRIES_PER_PAGE),
-                        names: Vec::with_capacity(ENTRIES_PER_PAGE),
-                        scores: Vec::with_capacity(ENTRIES_PER_PAGE),
-                    };
-                }
-            } else {
-                eprintln!("Failed to resolve player ID: {}", pid_str);
+                    Ok(write_lzma_bin_with(
+                        &dest_path,
+                        &positions,
+                        self.compression,
+                    )?)
+                });
+            match written {
+                Ok(()) => self.tally.processed(Stage::PositionIndex),
+                Err(e) => self.tally.failed(
+                    Stage::PositionIndex,
+                    format!("Failed to write position index {:?}: {:#}", dest_path, e),
+                ),
             }
         }
     }
 
-    // Write remaining entries
-    if !current_page.ranks.is_empty() {
-        let dest_name = format!("chunk_{:04}.bin.xz", output_index);
-        let dest_path = output_dir.join(dest_name);
-        if let Err(e) = write_lzma_bin(&dest_path, &current_page) {
-            eprintln!("Failed to write final page {:?}: {}", dest_path, e);
-        } else {
-            output_index += 1;
+    /// How the written rows' scores are spread, read back from the spill
+    /// highest first, as the pages hold them; `None` without a row.
+    fn distribution(&mut self) -> Option<ScoreDistribution> {
+        let (high, low) = (
+            self.score_ranges.first()?.max,
+            self.score_ranges.last()?.min,
+        );
+        let path = self.scores.path(SCORES);
+        let mut lost = None;
+        let distribution = self
+            .scores
+            .flush()
+            .and_then(|()| read_spilled::<u64>(&path))
+            .map(|scores| {
+                let scores = scores.map_while(|score| score.map_err(|e| lost = Some(e)).ok());
+                ScoreDistribution::from_descending(self.scored, high, low, scores)
+            });
+        match (distribution, lost) {
+            (Ok(distribution), None) => distribution,
+            (Err(e), _) | (Ok(_), Some(e)) => {
+                self.tally.failed(
+                    Stage::Snapshot,
+                    format!(
+                        "Failed to read back the scores of {:?}: {:#}",
+                        self.output_dir, e
+                    ),
+                );
+                None
+            }
         }
     }
+}
 
-    println!(
-        "Processed {} chunks with {} total entries",
-        chunks.len(),
-        total_entries_written
-    );
+fn empty_page() -> LeaderboardPage {
+    LeaderboardPage {
+        ranks: Vec::with_capacity(ENTRIES_PER_PAGE),
+        uuids: Vec::with_capacity(ENTRIES_PER_PAGE),
+        names: Vec::with_capacity(ENTRIES_PER_PAGE),
+        scores: Vec::with_capacity(ENTRIES_PER_PAGE),
+    }
+}
 
-    Ok((output_index, total_entries_written))
+/// Where page `number` of the snapshot written to `dir` is.
+fn page_path(dir: &Path, number: u32) -> PathBuf {
+    dir.join(format!("chunk_{:04}.bin.xz", number))
 }
 
-/// Process historical leaderboard data using rich format (same as latest)
+/// Fill `record` from `reader`. `false` at the end of the stream, including
+/// one that ends partway through a record.
+fn read_record(reader: &mut impl Read, record: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < record.len() {
+        match reader.read(&mut record[filled..]) {
+            Ok(0) => return Ok(false),
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// The score range of a page, whose rows run best first.
+fn score_range(page: &LeaderboardPage) -> Option<PageScoreRange> {
+    Some(PageScoreRange {
+        max: *page.scores.first()?,
+        min: *page.scores.last()?,
+        entries: page.scores.len() as u32,
+    })
+}
+
+/// What [`process_history`] gathered from one history archive.
+#[derive(Default)]
+struct HistoryOutcome {
+    sightings: Sightings,
+    scores: HistoryScores,
+    /// Every snapshot with a readable `_meta.json`, in archive order.
+    snapshots: Vec<HistoricalSnapshot>,
+}
+
+/// Process historical leaderboard data using rich format (same as latest).
+///
+/// Returns the [`Sightings`] of every snapshot, and hands each of its rows to
+/// `points` when there is one; snapshots without a readable `_meta.json` have
+/// no timestamp and are left out of both. What the scores of every snapshot
+/// written look like is kept by snapshot id, and what every `_meta.json` says
+/// for the game metadata.
+///
+/// The archive is decompressed and read as a stream. A snapshot's chunks are
+/// taken in archive order, which is their rank order; one whose chunks are
+/// split by another snapshot's carries on where it left off.
 fn process_history(
     stat_dir: &Path,
     out_stat_dir: &Path,
     lookup_map: &HashMap<String, (String, String)>,
-) -> Result<()> {
+    spill: &SpillDir,
+    points: Option<&mut HistoryWriter<'_>>,
+    compression: &Compression,
+    tally: &Tally,
+) -> Result<HistoryOutcome> {
     let history_in = stat_dir.join("history.tar.xz");
     if !history_in.exists() {
         println!("No history found for {}", stat_dir.display());
-        return Ok(());
+        return Ok(HistoryOutcome::default());
     }
 
     let history_out = out_stat_dir.join("history");
     fs::create_dir_all(&history_out)?;
 
-    println!("Extracting history archive: {}", history_in.display());
-
-    // Decompress the .xz file first
-    let decompressed_tar = read_lzma_raw(&*history_in)?;
+    println!("Streaming history archive: {}", history_in.display());
 
-    // Now extract the tar archive
-    let mut archive = tar::Archive::new(std::io::Cursor::new(decompressed_tar));
+    let mut archive = tar::Archive::new(lzma_reader(&history_in)?);
+    let mut stream =
+        HistoryStream::new(&history_out, lookup_map, spill, points, compression, tally)?;
+    let mut snapshots = Vec::new();
 
-    let mut snapshot_data: HashMap<String, Vec<Vec<u8>>> = HashMap::new();
-
-    // Extract all files and group by snapshot
     for entry_result in archive.entries()? {
         let mut entry = entry_result?;
-        let path = entry.path()?;
-        let path_str = path.to_string_lossy().to_string();
-
-        if let Some(slash_pos) = path_str.find('/') {
-            let snapshot_name = path_str[..slash_pos].to_string();
-            let file_name = path_str[slash_pos + 1..].to_string();
-
-            if file_name.starts_with("chunk_") && file_name.ends_with(".bin") {
-                // Read chunk data (uncompressed in the tar)
-                let mut contents = Vec::new();
-                entry.read_to_end(&mut contents)?;
-                snapshot_data
-                    .entry(snapshot_name)
-                    .or_insert_with(|| Vec::new())
-                    .push(contents);
+        let path_str = entry.path()?.to_string_lossy().to_string();
+        let Some((snapshot_name, file_name)) = path_str.split_once('/') else {
+            continue;
+        };
+
+        if file_name.starts_with("chunk_") && file_name.ends_with(".bin") {
+            // Chunks are stored uncompressed in the tar.
+            stream.chunk(snapshot_name, &path_str, &mut entry);
+        } else if file_name == FILE_META
+            && let Some(meta) = read_meta(&mut entry)
+        {
+            stream.meta(snapshot_name, meta.save_time_unix);
+            snapshots.push(HistoricalSnapshot {
+                snapshot_id: SmolStr::new(snapshot_name),
+                timestamp: meta.save_time_unix,
+                total_pages: meta.total_pages,
+                total_entries: meta.total_entries,
+                page_scores: Vec::new(),
+                distribution: None,
+            });
+        }
+    }
+
+    Ok(HistoryOutcome {
+        snapshots,
+        ..stream.finish()
+    })
+}
+
+/// A history snapshot [`HistoryStream`] has started.
+struct OpenSnapshot<'a> {
+    name: String,
+    /// `None` when its output directory could not be created.
+    pages: Option<PageWriter<'a>>,
+}
+
+/// What [`process_history`] keeps while a history archive streams past: the
+/// snapshot being filled, and those set aside until the end of the archive in
+/// case more of their chunks come.
+struct HistoryStream<'a, 'h> {
+    history_out: &'a Path,
+    lookup_map: &'a HashMap<String, (String, String)>,
+    spill: &'a SpillDir,
+    points: Option<&'a mut HistoryWriter<'h>>,
+    compression: &'a Compression,
+    tally: &'a Tally,
+    /// `snapshot_id -> save_time_unix` for every `_meta.json` read so far.
+    timestamps: HashMap<String, u64>,
+    sightings: Sightings,
+    /// Rows ranked before their snapshot's `_meta.json` came by, as
+    /// `(player_id, uuid, score, rank)` by snapshot, to be recorded once its
+    /// timestamp is known.
+    undated: ShardedSpill,
+    scratch: PathBuf,
+    open: Option<OpenSnapshot<'a>>,
+    /// Snapshots the archive moved past, in the order it did.
+    aside: Vec<OpenSnapshot<'a>>,
+}
+
+impl<'a, 'h> HistoryStream<'a, 'h> {
+    fn new(
+        history_out: &'a Path,
+        lookup_map: &'a HashMap<String, (String, String)>,
+        spill: &'a SpillDir,
+        points: Option<&'a mut HistoryWriter<'h>>,
+        compression: &'a Compression,
+        tally: &'a Tally,
+    ) -> Result<Self> {
+        let scratch = spill.scratch("undated")?;
+        Ok(Self {
+            history_out,
+            lookup_map,
+            spill,
+            points,
+            compression,
+            tally,
+            timestamps: HashMap::new(),
+            sightings: Sightings::new(),
+            undated: ShardedSpill::new(scratch.clone()),
+            scratch,
+            open: None,
+            aside: Vec::new(),
+        })
+    }
+
+    /// Add one chunk of `snapshot_name`, read from `reader`.
+    fn chunk(&mut self, snapshot_name: &str, path: &str, reader: impl Read) {
+        if self
+            .open
+            .as_ref()
+            .is_none_or(|open| open.name != snapshot_name)
+        {
+            self.set_aside();
+            let resumed = self
+                .aside
+                .iter()
+                .position(|aside| aside.name == snapshot_name)
+                .map(|at| self.aside.remove(at));
+            self.open = Some(match resumed {
+                Some(mut snapshot) => {
+                    println!("Resuming history snapshot: {}", snapshot_name);
+                    if let Some(pages) = snapshot.pages.as_mut() {
+                        pages.resume();
+                    }
+                    snapshot
+                }
+                None => self.start(snapshot_name),
+            });
+        }
+
+        let open = self.open.as_mut().expect("a snapshot is open");
+        let Some(pages) = open.pages.as_mut() else {
+            return;
+        };
+        let (sightings, points, undated) =
+            (&mut self.sightings, &mut self.points, &mut self.undated);
+        let timestamp = self.timestamps.get(snapshot_name).copied();
+        let streamed = pages.push_chunk(reader, |player_id, uuid, score, rank| {
+            let row = (player_id, uuid, score, rank);
+            match timestamp {
+                Some(timestamp) => record_row(
+                    sightings,
+                    points.as_deref_mut(),
+                    snapshot_name,
+                    timestamp,
+                    row,
+                ),
+                None => undated.push(snapshot_name, &row),
             }
+        });
+        if let Err(e) = streamed {
+            self.tally.failed(
+                Stage::Chunk,
+                format!("Failed to read chunk {}: {}", path, e),
+            );
         }
     }
 
-    println!(
-        "Processing {} history snapshots in parallel...",
-        snapshot_data.len()
-    );
+    /// Date `snapshot_name` from its `_meta.json`, recording the rows of it
+    /// ranked before.
+    fn meta(&mut self, snapshot_name: &str, timestamp: u64) {
+        self.timestamps.insert(snapshot_name.to_string(), timestamp);
 
-    snapshot_data
-        .par_iter()
-        .for_each(|(snapshot_name, chunks)| {
-            let snapshot_out = history_out.join(snapshot_name);
-            if let Err(e) = fs::create_dir_all(&snapshot_out) {
-                eprintln!("Failed to create directory {:?}: {}", snapshot_out, e);
-                return;
+        let spilled = self.undated.path(snapshot_name);
+        let recorded = self
+            .undated
+            .flush()
+            .and_then(|()| read_spilled::<(String, SmolStr, u64, u32)>(&spilled))
+            .and_then(|rows| {
+                for row in rows {
+                    let (player_id, uuid, score, rank) = row?;
+                    record_row(
+                        &mut self.sightings,
+                        self.points.as_deref_mut(),
+                        snapshot_name,
+                        timestamp,
+                        (&player_id, &uuid, score, rank),
+                    );
+                }
+                Ok(())
+            });
+        if let Err(e) = recorded {
+            self.tally.failed(
+                Stage::Snapshot,
+                format!("Failed to date the rows of history snapshot {snapshot_name}: {e:#}"),
+            );
+        }
+        let _ = fs::remove_file(&spilled);
+    }
+
+    fn start(&self, snapshot_name: &str) -> OpenSnapshot<'a> {
+        println!("Processing history snapshot: {}", snapshot_name);
+
+        let snapshot_out = self.history_out.join(snapshot_name);
+        let pages = fs::create_dir_all(&snapshot_out)
+            .map_err(Into::into)
+            .and_then(|()| self.spill.scratch("snapshot"))
+            .and_then(|scratch| {
+                PageWriter::new(
+                    snapshot_out.clone(),
+                    scratch,
+                    self.lookup_map,
+                    self.compression,
+                    self.tally,
+                )
+            });
+        let pages = match pages {
+            Ok(pages) => Some(pages),
+            Err(e) => {
+                self.tally.failed(
+                    Stage::Snapshot,
+                    format!("Failed to create directory {:?}: {:#}", snapshot_out, e),
+                );
+                None
             }
+        };
+        OpenSnapshot {
+            name: snapshot_name.to_string(),
+            pages,
+        }
+    }
 
-            println!("Processing history snapshot: {}", snapshot_name);
+    /// Set the snapshot being filled aside, in case more of its chunks come.
+    fn set_aside(&mut self) {
+        let Some(mut open) = self.open.take() else {
+            return;
+        };
+        if let Some(pages) = open.pages.as_mut() {
+            pages.set_aside();
+        }
+        self.aside.push(open);
+    }
 
-            // Process chunks using shared logic
-            let (output_index, total_entries_written) =
-                match process_binary_chunks(chunks, &snapshot_out, lookup_map) {
-                    Ok(result) => result,
-                    Err(e) => {
-                        eprintln!("Failed to process chunks for {}: {}", snapshot_name, e);
-                        return;
-                    }
-                };
+    /// Finish every snapshot, writing its last page and its position index.
+    fn finish(mut self) -> HistoryOutcome {
+        self.set_aside();
 
+        let mut scores = HashMap::new();
+        for snapshot in self.aside {
+            let Some(pages) = snapshot.pages else {
+                continue;
+            };
+            let written = pages.finish();
+            self.tally.processed(Stage::Snapshot);
             println!(
                 "  {} - Wrote {} pages with {} total entries",
-                snapshot_name, output_index, total_entries_written
+                snapshot.name, written.pages, written.entries
             );
-        });
+            scores.insert(snapshot.name, written.scores);
+        }
+        // Rows of snapshots that never got a `_meta.json` stay undated.
+        let _ = fs::remove_dir_all(&self.scratch);
 
-    Ok(())
+        HistoryOutcome {
+            sightings: self.sightings,
+            scores,
+            snapshots: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::players::write_player_histories;
+    use mp_stats_common::compression::write_lzma_raw;
+    use mp_stats_common::shard::uuid_shard;
+    use mp_stats_core::models::PlayerHistory;
+    use mp_stats_core::routes;
+
+    fn records(rows: &[(u64, u64)]) -> Vec<u8> {
+        rows.iter()
+            .flat_map(|(player_id, score)| [player_id.to_be_bytes(), score.to_be_bytes()])
+            .flatten()
+            .collect()
+    }
+
+    fn meta(save_time_unix: u64) -> Vec<u8> {
+        serde_json::to_vec(&MetaFile {
+            save_time: String::new(),
+            save_time_unix,
+            save_id: 0,
+            total_entries: 0,
+            total_pages: 0,
+        })
+        .unwrap()
+    }
+
+    /// Ranks run on across chunk boundaries, and a `_meta.json` stored after its
+    /// snapshot's chunks still dates the players seen in them.
+    #[test]
+    fn history_is_streamed_snapshot_by_snapshot() {
+        let dir = std::env::temp_dir().join(format!(
+            "mp_stats_history_{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let (stat_dir, out_dir) = (dir.join("in"), dir.join("out"));
+
+        let mut tar = tar::Builder::new(Vec::new());
+        let entries: [(&str, Vec<u8>); 5] = [
+            ("100/chunk_0000.bin", records(&[(1, 50), (2, 40)])),
+            ("100/chunk_0001.bin", records(&[(3, 40)])),
+            ("100/_meta.json", meta(100)),
+            ("200/_meta.json", meta(200)),
+            ("200/chunk_0000.bin", records(&[(3, 60), (1, 10)])),
+        ];
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, path, data.as_slice()).unwrap();
+        }
+        write_lzma_raw(&stat_dir.join("history.tar.xz"), &tar.into_inner().unwrap()).unwrap();
+
+        let lookup: HashMap<String, (String, String)> = (1..=3)
+            .map(|id| (id.to_string(), (format!("uuid-{id}"), format!("name-{id}"))))
+            .collect();
+        let tally = Tally::default();
+        let spill = SpillDir::new(dir.join("spill"));
+        let spool = HistorySpool::new(spill.scratch("player_history").unwrap());
+        let mut points = spool.writer(&spill, "All", "game", "stat").unwrap();
+        let history = process_history(
+            &stat_dir,
+            &out_dir,
+            &lookup,
+            &spill,
+            Some(&mut points),
+            &Compression::default(),
+            &tally,
+        )
+        .unwrap();
+        points.finish().unwrap();
+
+        assert_eq!(tally.failures(), 0);
+        assert_eq!(history.sightings["1"], (100, 200));
+        assert_eq!(history.sightings["2"], (100, 100));
+        assert_eq!(history.scores["100"].page_scores[0].entries, 3);
+        let described: Vec<&str> = history
+            .snapshots
+            .iter()
+            .map(|snapshot| snapshot.snapshot_id.as_str())
+            .collect();
+        assert_eq!(described, ["100", "200"]);
+
+        let page: LeaderboardPage =
+            read_lzma_bin(&out_dir.join("history/200/chunk_0000.bin.xz")).unwrap();
+        assert_eq!(page.uuids, ["uuid-3", "uuid-1"]);
+
+        let edition = PlatformEdition::Java;
+        write_player_histories(&edition, &out_dir, spool, &Compression::default(), &tally).unwrap();
+        let shard = uuid_shard("uuid-1").unwrap();
+        let histories: HashMap<String, PlayerHistory> =
+            read_lzma_bin(&out_dir.join(routes::player_history_bin(&edition, &shard))).unwrap();
+        let ranks = |uuid: &str| -> Vec<(u64, u32)> {
+            histories[uuid].stats[0]
+                .points
+                .iter()
+                .map(|point| (point.timestamp, point.rank))
+                .collect()
+        };
+        assert_eq!(ranks("uuid-1"), [(100, 1), (200, 2)]);
+        assert_eq!(ranks("uuid-2"), [(100, 2)]);
+        assert_eq!(ranks("uuid-3"), [(100, 2), (200, 1)]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    /// A snapshot whose chunks are split by another snapshot's carries on
+    /// filling its last page rather than losing the chunk that comes late.
+    #[test]
+    fn a_split_snapshot_resumes_where_it_left_off() {
+        let dir = std::env::temp_dir().join(format!(
+            "mp_stats_split_history_{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let (stat_dir, out_dir) = (dir.join("in"), dir.join("out"));
+
+        let mut tar = tar::Builder::new(Vec::new());
+        let entries: [(&str, Vec<u8>); 5] = [
+            ("100/_meta.json", meta(100)),
+            ("100/chunk_0000.bin", records(&[(1, 50), (2, 40)])),
+            ("200/chunk_0000.bin", records(&[(3, 60)])),
+            ("100/chunk_0001.bin", records(&[(3, 40)])),
+            ("200/_meta.json", meta(200)),
+        ];
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, path, data.as_slice()).unwrap();
+        }
+        write_lzma_raw(&stat_dir.join("history.tar.xz"), &tar.into_inner().unwrap()).unwrap();
+
+        let lookup: HashMap<String, (String, String)> = (1..=3)
+            .map(|id| (id.to_string(), (format!("uuid-{id}"), format!("name-{id}"))))
+            .collect();
+        let tally = Tally::default();
+        let spill = SpillDir::new(dir.join("spill"));
+        let history = process_history(
+            &stat_dir,
+            &out_dir,
+            &lookup,
+            &spill,
+            None,
+            &Compression::default(),
+            &tally,
+        )
+        .unwrap();
+
+        assert_eq!(tally.failures(), 0);
+        assert_eq!(tally.counts().stages[&Stage::Page].processed, 2);
+        let snapshot = out_dir.join("history/100");
+        let page: LeaderboardPage = read_lzma_bin(&snapshot.join("chunk_0000.bin.xz")).unwrap();
+        assert_eq!(page.uuids, ["uuid-1", "uuid-2", "uuid-3"]);
+        assert_eq!(page.ranks, [1, 2, 2]);
+        assert!(!snapshot.join("chunk_0001.bin.xz").exists());
+
+        let scores = &history.scores["100"];
+        assert_eq!(scores.page_scores.len(), 1);
+        assert_eq!(scores.distribution.as_ref().unwrap().p50, 40);
+        let shard = position_shard("uuid-3").unwrap();
+        let positions: HashMap<SmolStr, LeaderboardPosition> =
+            read_lzma_bin(&snapshot.join(format!("positions/{shard}.bin.xz"))).unwrap();
+        assert_eq!(positions["uuid-3"].row, 2);
+        assert_eq!(history.sightings["3"], (100, 200));
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
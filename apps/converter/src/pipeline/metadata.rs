@@ -1,42 +1,119 @@
-use anyhow::Result;
-use mp_stats_common::compression::write_lzma_bin;
-use mp_stats_common::formats::raw;
-use mp_stats_core::models::PlatformEdition;
+use crate::io::{UnitCache, UnitKey};
+use crate::pipeline::leaderboards::Sightings;
+use crate::pipeline::players::ProfiledPlayers;
+use crate::report::{Stage, Tally};
+use anyhow::{Context, Result};
+use mp_stats_common::compression::{Compression, read_lzma_raw, write_lzma_bin_with};
+use mp_stats_common::shard::{name_shard, name_trigrams};
+use mp_stats_core::models::{NameCandidate, NameMatch, NameRecord, PlatformEdition};
 use mp_stats_core::routes;
 use rayon::prelude::*;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
 use std::path::Path;
 use walkdir::WalkDir;
 
+/// `player_id -> (uuid, name)`, as read from the dictionary.
+pub type PlayerLookup = HashMap<String, (String, String)>;
+
+/// `name prefix -> [(name, uuid)]`, the raw material of the names index.
+pub type NamesByPrefix = HashMap<String, Vec<(String, String)>>;
+
+/// `uuid -> name history`, oldest name first, as a profile carries it.
+pub type NameHistories = HashMap<String, Vec<NameRecord>>;
+
+/// Every name the input knows, filed the way the names index is split.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Names {
+    /// Names of at least three characters, by [`name_shard`].
+    pub by_prefix: NamesByPrefix,
+    /// `[(name, uuid)]` for names of one or two characters, which have no
+    /// prefix to be filed under and go to the short-name index instead.
+    pub short: Vec<(String, String)>,
+}
+
+impl Names {
+    fn insert(&mut self, name: String, uuid: String) {
+        match name_shard(&name) {
+            Ok(prefix) => self.by_prefix.entry(prefix).or_default().push((name, uuid)),
+            Err(_) if !name.is_empty() => self.short.push((name, uuid)),
+            Err(_) => {}
+        }
+    }
+
+    fn merge(&mut self, other: Names) {
+        for (prefix, entries) in other.by_prefix {
+            self.by_prefix.entry(prefix).or_default().extend(entries);
+        }
+        self.short.extend(other.short);
+    }
+}
+
 /// Process the dictionary and gather the raw names map.
 ///
 /// Returns a tuple of:
 /// * `lookup_map`: player_id -> (uuid, name) used by later pipeline steps.
-/// * `names_map`: name prefix -> [(name, uuid)] used to build the names index.
+/// * `names`: every name, by prefix or as a short name, used to build the names
+///   index.
+///
+/// Java names come from the dictionary, which pairs every id with a UUID and
+/// a name. Bedrock players have no UUID: their dictionary pairs every id with
+/// the gamertag twice, and the gamertag is the id the output is keyed by. Its
+/// names come from `names.tar.xz` (see [`read_names_archive`]) when the input
+/// has one, and from the dictionary otherwise.
 ///
 /// The names index itself is intentionally *not* written here: it is built
 /// later (via [`build_names_archive`]) once the set of players that actually
 /// received a profile is known, so each entry can be stamped with a
-/// `has_profile` flag.
+/// `has_profile` flag and when the player was last seen.
+///
+/// The dictionary is one unit of `units`: when neither it nor the names
+/// archive changed, both maps are restored rather than parsed again. The key
+/// it was cached under comes last, for the units that depend on it; `None`
+/// when the cache is disabled.
 pub fn process_dictionary_and_names(
     platform: &PlatformEdition,
-    java_in: &Path,
-    output_dir: &Path,
-) -> Result<(
-    HashMap<String, (String, String)>,
-    HashMap<String, Vec<(String, String)>>,
-)> {
-    let _ = (platform, output_dir);
-    let dict_in = java_in.join("dictionary/ids");
+    directory_in: &Path,
+    units: &UnitCache,
+) -> Result<(PlayerLookup, Names, Option<UnitKey>)> {
+    let archive = directory_in.join("names.tar.xz");
+    let reads_archive = *platform == PlatformEdition::Bedrock && archive.exists();
+
+    let key = units.key("dictionary", |key| {
+        key.dir(&directory_in.join("dictionary/ids"))?;
+        if reads_archive {
+            key.file(&archive)?;
+        }
+        Ok(())
+    })?;
+    if let Some(key) = &key
+        && let Some((lookup, names)) = units.restore::<(PlayerLookup, Names)>(key, None)
+    {
+        println!("Dictionary unchanged, reusing the cached lookup and names.");
+        return Ok((lookup, names, Some(key.clone())));
+    }
+
+    let (lookup, names) = read_dictionary(directory_in, reads_archive.then_some(&*archive))?;
+    if let Some(key) = &key
+        && let Err(e) = units.store(key, &(&lookup, &names), None)
+    {
+        eprintln!("  Failed to cache the dictionary: {e:#}");
+    }
+    Ok((lookup, names, key))
+}
+
+/// Parse the dictionary, and the names from `archive` when there is one.
+fn read_dictionary(directory_in: &Path, archive: Option<&Path>) -> Result<(PlayerLookup, Names)> {
+    let dict_in = directory_in.join("dictionary/ids");
 
     let walker = WalkDir::new(&dict_in).into_iter();
 
     // Gather all JSONs first
     let mut files = Vec::new();
     for entry in walker.filter_map(|e| e.ok()) {
-        if entry.path().extension().map_or(false, |e| e == "json") {
+        if entry.path().extension().is_some_and(|e| e == "json") {
             files.push(entry.path().to_path_buf());
         }
     }
@@ -44,49 +121,36 @@ pub fn process_dictionary_and_names(
     // Parallel Process Dictionary
     println!("Processing {} dictionary files in parallel...", files.len());
 
-    let (names_map, global_id_map) = files
+    let (names, global_id_map) = files
         .par_iter()
-        .map(
-            |path| -> Result<(
-                HashMap<String, Vec<(String, String)>>,
-                HashMap<String, (String, String)>,
-            )> {
-                // Read Dict
-                let file = File::open(path)?;
-                let map: HashMap<String, (String, Option<String>)> =
-                    serde_json::from_reader(BufReader::new(file))?;
-
-                let mut local_names: HashMap<String, Vec<(String, String)>> = HashMap::new();
-                let mut local_ids = HashMap::new();
-
-                // Collect Names & IDs
-                for (id, (uuid, name_opt)) in map {
-                    if let Some(name) = name_opt {
-                        if name.len() >= raw::MIN_NAME_LENGTH {
-                            let prefix = name[..raw::MIN_NAME_LENGTH].to_lowercase(); // Normalized prefix
-                            local_names
-                                .entry(prefix)
-                                .or_default()
-                                .push((name.clone(), uuid.clone()));
-                        }
-                        local_ids.insert(id, (uuid, name));
-                    } else {
-                        local_ids.insert(id, (uuid.clone(), uuid.to_string()));
-                    }
+        .map(|path| -> Result<(Names, PlayerLookup)> {
+            // Read Dict
+            let file = File::open(path)?;
+            let map: HashMap<String, (String, Option<String>)> =
+                serde_json::from_reader(BufReader::new(file))?;
+
+            let mut local_names = Names::default();
+            let mut local_ids = HashMap::new();
+
+            // Collect Names & IDs
+            for (id, (uuid, name_opt)) in map {
+                if let Some(name) = name_opt {
+                    local_names.insert(name.clone(), uuid.clone());
+                    local_ids.insert(id, (uuid, name));
+                } else {
+                    local_ids.insert(id, (uuid.clone(), uuid.to_string()));
                 }
-                Ok((local_names, local_ids))
-            },
-        )
+            }
+            Ok((local_names, local_ids))
+        })
         .reduce(
-            || Ok((HashMap::new(), HashMap::new())),
+            || Ok((Names::default(), HashMap::new())),
             |acc, item| {
                 let (mut acc_names, mut acc_ids) = acc?;
                 let (item_names, item_ids) = item?;
 
                 // Merge Names
-                for (k, v) in item_names {
-                    acc_names.entry(k).or_default().extend(v);
-                }
+                acc_names.merge(item_names);
                 // Merge IDs
                 acc_ids.extend(item_ids);
 
@@ -94,38 +158,362 @@ pub fn process_dictionary_and_names(
             },
         )?;
 
-    println!("Found {} names.", names_map.len());
+    let names = match archive {
+        Some(archive) => {
+            println!("Reading names from {}...", archive.display());
+            read_names_archive(archive)?
+        }
+        None => names,
+    };
+
+    println!(
+        "Found {} name prefixes and {} short names.",
+        names.by_prefix.len(),
+        names.short.len()
+    );
+
+    Ok((global_id_map, names))
+}
+
+/// Read a `names.tar.xz` archive into the raw material of the names index.
+///
+/// The archive files every name as `names/<PREFIX>/<name>.csv`, a
+/// `player_id,uuid` CSV of the players who carry it. The prefix directory is
+/// not trusted: entries are re-filed with [`name_shard`], so the index matches
+/// what the frontend looks up whatever tool wrote the archive.
+pub fn read_names_archive(path: &Path) -> Result<Names> {
+    let tar = read_lzma_raw(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut archive = tar::Archive::new(std::io::Cursor::new(tar));
+
+    let mut names = Names::default();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let entry_path = entry.path()?.into_owned();
+        let Some(name) = entry_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(".csv"))
+            .map(str::to_owned)
+        else {
+            continue;
+        };
+
+        let mut csv = String::new();
+        entry
+            .read_to_string(&mut csv)
+            .with_context(|| format!("reading {} in {}", entry_path.display(), path.display()))?;
+
+        // The header, then one `player_id,uuid` row per player.
+        for row in csv.lines().skip(1) {
+            if let Some((_, uuid)) = row.split_once(',')
+                && !uuid.is_empty()
+            {
+                names.insert(name.clone(), uuid.to_owned());
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+/// Every name each player has been known by, oldest first.
+///
+/// The names are those of `names`, so a player is found by any of them in the
+/// names index, and `sightings` dates them: a dictionary id in `lookup` stands
+/// for one `(uuid, name)` pair, so the snapshots an id was ranked in are the
+/// ones its player was ranked in under that name. A history is ordered by when
+/// each name was last ranked, then first ranked; names that were never ranked
+/// have nothing to go by and come first, by name.
+pub fn name_histories(
+    names: &Names,
+    lookup: &PlayerLookup,
+    sightings: &Sightings,
+) -> NameHistories {
+    let mut histories = NameHistories::new();
+    for (name, uuid) in names.by_prefix.values().flatten().chain(&names.short) {
+        let history = histories.entry(uuid.clone()).or_default();
+        if !history.iter().any(|record| record.name == *name) {
+            history.push(NameRecord {
+                name: name.into(),
+                first_seen: 0,
+                last_seen: 0,
+            });
+        }
+    }
+
+    for (player_id, &(first, last)) in sightings {
+        // An id the dictionary gives no name stands for the uuid alone.
+        let Some(record) = lookup.get(player_id).and_then(|(uuid, name)| {
+            histories
+                .get_mut(uuid)?
+                .iter_mut()
+                .find(|record| record.name == *name)
+        }) else {
+            continue;
+        };
+        if record.first_seen == 0 || first < record.first_seen {
+            record.first_seen = first;
+        }
+        record.last_seen = record.last_seen.max(last);
+    }
 
-    Ok((global_id_map, names_map))
+    for history in histories.values_mut() {
+        history.sort_by(|a, b| {
+            (a.last_seen, a.first_seen, &a.name).cmp(&(b.last_seen, b.first_seen, &b.name))
+        });
+    }
+    histories
 }
 
 /// Build names archive and index.
 ///
-/// Each index entry maps a player name to `(uuid, has_profile)`, where
-/// `has_profile` is `true` when the player's UUID is present in
-/// `profiled_uuids` (i.e. an actual profile shard was produced for them).
-/// The frontend uses this flag to hide search suggestions that would lead to
-/// an empty "no profile data" page.
+/// Each index entry maps a player name to every [`NameCandidate`] carrying
+/// it, most recently seen first: two players can share a name, or one can
+/// take a name another gave up, and both must stay findable. `has_profile` is
+/// `true` when the player is in `profiled` (i.e. an actual profile shard was
+/// produced for them); the frontend uses it to hide search suggestions that
+/// would lead to an empty "no profile data" page. Names too short for a prefix
+/// all go to one more file of the same layout, [`routes::names_short_bin`]. An
+/// index file that cannot be written is counted in `tally` and the rest are
+/// still written.
 pub fn build_names_archive(
     platform: &PlatformEdition,
     output_dir: &Path,
-    names_map: HashMap<String, Vec<(String, String)>>,
-    profiled_uuids: &HashSet<String>,
+    names: Names,
+    profiled: &ProfiledPlayers,
+    compression: &Compression,
+    tally: &Tally,
 ) -> Result<()> {
-    for (prefix, entries) in names_map {
-        // Write Index Bin (Name -> (UUID, has_profile))
-        let mut index_map: HashMap<String, (String, bool)> = HashMap::with_capacity(entries.len());
+    let files = names
+        .by_prefix
+        .into_iter()
+        .map(|(prefix, entries)| (routes::names_index_bin(platform, &prefix), entries))
+        .chain((!names.short.is_empty()).then(|| (routes::names_short_bin(platform), names.short)));
 
-        for (name, uuid) in &entries {
-            let has_profile = profiled_uuids.contains(uuid);
-            index_map.insert(name.clone(), (uuid.clone(), has_profile));
+    for (relative_path, entries) in files {
+        // Write Index Bin (Name -> [NameCandidate])
+        let mut index_map: HashMap<String, Vec<NameCandidate>> =
+            HashMap::with_capacity(entries.len());
+
+        for (name, uuid) in entries {
+            let candidates = index_map.entry(name).or_default();
+            if candidates.iter().any(|c| c.uuid == uuid) {
+                continue;
+            }
+            let last_seen = profiled.get(&uuid).copied();
+            candidates.push(NameCandidate {
+                uuid: uuid.into(),
+                has_profile: last_seen.is_some(),
+                last_seen: last_seen.unwrap_or(0),
+            });
+        }
+        for candidates in index_map.values_mut() {
+            candidates.sort_unstable_by(|a, b| {
+                b.last_seen
+                    .cmp(&a.last_seen)
+                    .then_with(|| a.uuid.cmp(&b.uuid))
+            });
         }
 
         // Save Index Bin (LZMA)
-        let relative_path = routes::names_index_bin(platform, &prefix);
         let index_path = output_dir.join(relative_path);
-        write_lzma_bin(&index_path, &index_map)?;
+        match write_lzma_bin_with(&index_path, &index_map, compression) {
+            Ok(()) => tally.processed(Stage::NameIndex),
+            Err(e) => tally.failed(
+                Stage::NameIndex,
+                format!("Failed to write names index {:?}: {}", index_path, e),
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the trigram index the name search matches substrings and typos with.
+///
+/// Every profiled player's name is filed under each of its
+/// [`name_trigrams`], so a query finds `xXSteve` through `ste`, `tev` and `eve`
+/// whatever the name starts with. Players without a profile are left out: the
+/// search never suggests them. Each file lists its players sorted by name; one
+/// that cannot be written is counted in `tally` and the rest are still written.
+pub fn build_name_trigrams(
+    platform: &PlatformEdition,
+    output_dir: &Path,
+    names: &Names,
+    profiled: &ProfiledPlayers,
+    compression: &Compression,
+    tally: &Tally,
+) -> Result<()> {
+    let mut players: Vec<(&str, &str)> = names
+        .by_prefix
+        .values()
+        .flatten()
+        .filter(|(_, uuid)| profiled.contains_key(uuid))
+        .map(|(name, uuid)| (name.as_str(), uuid.as_str()))
+        .collect();
+    players.sort_unstable();
+    players.dedup();
+
+    // Positions into `players` rather than copies: a name sits in as many
+    // files as it has trigrams.
+    let mut postings: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, (name, _)) in players.iter().enumerate() {
+        for trigram in name_trigrams(name) {
+            postings.entry(trigram).or_default().push(index);
+        }
     }
 
+    postings.par_iter().for_each(|(trigram, indices)| {
+        let matches: Vec<NameMatch> = indices
+            .iter()
+            .map(|&i| NameMatch {
+                name: players[i].0.into(),
+                uuid: players[i].1.into(),
+            })
+            .collect();
+
+        let path = output_dir.join(routes::name_trigram_bin(platform, trigram));
+        match write_lzma_bin_with(&path, &matches, compression) {
+            Ok(()) => tally.processed(Stage::NameIndex),
+            Err(e) => tally.failed(
+                Stage::NameIndex,
+                format!("Failed to write name trigram file {:?}: {}", path, e),
+            ),
+        }
+    });
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mp_stats_common::compression::read_lzma_bin;
+
+    #[test]
+    fn one_and_two_character_names_get_the_short_index() {
+        let mut names = Names::default();
+        names.insert("Al".into(), "uuid-al".into());
+        names.insert("J".into(), "uuid-j".into());
+        names.insert("Alex".into(), "uuid-alex".into());
+        names.insert(String::new(), "uuid-none".into());
+        assert_eq!(names.short.len(), 2);
+        assert_eq!(
+            names.by_prefix["ale"],
+            [("Alex".into(), "uuid-alex".into())]
+        );
+
+        let dir = temp_dir("short_names");
+        let edition = PlatformEdition::Java;
+        let profiled = ProfiledPlayers::from([("uuid-al".to_string(), 7)]);
+        build_names_archive(
+            &edition,
+            &dir,
+            names,
+            &profiled,
+            &Compression::default(),
+            &Tally::default(),
+        )
+        .unwrap();
+
+        let short: HashMap<String, Vec<NameCandidate>> =
+            read_lzma_bin(&dir.join(routes::names_short_bin(&edition))).unwrap();
+        assert_eq!(short["Al"], [candidate("uuid-al", true, 7)]);
+        assert_eq!(short["J"], [candidate("uuid-j", false, 0)]);
+        assert!(dir.join(routes::names_index_bin(&edition, "ale")).exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn players_sharing_a_name_are_all_kept() {
+        let mut names = Names::default();
+        names.insert("Steve".into(), "uuid-old".into());
+        names.insert("Steve".into(), "uuid-new".into());
+        names.insert("Steve".into(), "uuid-none".into());
+        names.insert("Steve".into(), "uuid-new".into());
+
+        let dir = temp_dir("shared_names");
+        let edition = PlatformEdition::Java;
+        let profiled =
+            ProfiledPlayers::from([("uuid-old".to_string(), 100), ("uuid-new".to_string(), 200)]);
+        build_names_archive(
+            &edition,
+            &dir,
+            names,
+            &profiled,
+            &Compression::default(),
+            &Tally::default(),
+        )
+        .unwrap();
+
+        let index: HashMap<String, Vec<NameCandidate>> =
+            read_lzma_bin(&dir.join(routes::names_index_bin(&edition, "ste"))).unwrap();
+        assert_eq!(
+            index["Steve"],
+            [
+                candidate("uuid-new", true, 200),
+                candidate("uuid-old", true, 100),
+                candidate("uuid-none", false, 0),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn a_name_history_is_ordered_by_when_each_name_was_ranked() {
+        let mut names = Names::default();
+        for name in ["Steve", "Al", "xXSteveXx", "Steve_2"] {
+            names.insert(name.into(), "uuid-steve".into());
+        }
+        names.insert("Alex".into(), "uuid-alex".into());
+        let lookup = PlayerLookup::from([
+            ("1".into(), ("uuid-steve".into(), "Steve".into())),
+            ("2".into(), ("uuid-steve".into(), "xXSteveXx".into())),
+            ("3".into(), ("uuid-steve".into(), "Al".into())),
+            ("4".into(), ("uuid-alex".into(), "uuid-alex".into())),
+        ]);
+        let sightings = Sightings::from([
+            ("1".into(), (300, 400)),
+            ("2".into(), (100, 200)),
+            ("3".into(), (500, 500)),
+            ("4".into(), (100, 100)),
+        ]);
+
+        let histories = name_histories(&names, &lookup, &sightings);
+        let steve: Vec<_> = histories["uuid-steve"]
+            .iter()
+            .map(|r| (r.name.as_str(), r.first_seen, r.last_seen))
+            .collect();
+        assert_eq!(
+            steve,
+            [
+                ("Steve_2", 0, 0),
+                ("xXSteveXx", 100, 200),
+                ("Steve", 300, 400),
+                ("Al", 500, 500),
+            ]
+        );
+        assert_eq!(histories["uuid-alex"].len(), 1);
+        assert_eq!(histories["uuid-alex"][0].last_seen, 0);
+    }
+
+    fn candidate(uuid: &str, has_profile: bool, last_seen: u64) -> NameCandidate {
+        NameCandidate {
+            uuid: uuid.into(),
+            has_profile,
+            last_seen,
+        }
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("mp_stats_{name}_{nanos}"))
+    }
+}
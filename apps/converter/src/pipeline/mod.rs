@@ -4,6 +4,12 @@ pub mod metadata;
 pub mod players;
 
 pub use games::process_game_metadata;
-pub use leaderboards::process_java_leaderboards;
-pub use metadata::{build_names_archive, process_dictionary_and_names};
-pub use players::process_java_players;
+pub use leaderboards::{
+    HistorySnapshots, LeaderboardOutcome, ScoreSummaries, Sightings, process_leaderboards,
+};
+pub use metadata::{
+    build_name_trigrams, build_names_archive, name_histories, process_dictionary_and_names,
+};
+pub use players::{
+    HistorySpool, HistoryWriter, ProfiledPlayers, process_players, write_player_histories,
+};
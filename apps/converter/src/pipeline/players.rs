@@ -1,31 +1,65 @@
+use crate::io::spill::{merge_spilled, read_spilled, spilled_shards};
+use crate::io::{ShardedSpill, SpillDir, UnitCache};
+use crate::pipeline::metadata::NameHistories;
+use crate::report::{Stage, Tally, UnitCounts};
 use anyhow::Result;
-use mp_stats_common::compression::{decompress_file_auto, write_lzma_bin};
+use mp_stats_common::compression::{Compression, decompress_file_auto, write_lzma_bin_with};
+use mp_stats_common::shard::uuid_shard;
 use mp_stats_core::models::{
-    IdMap, PlatformEdition, PlayerProfile, StatRaw, competition_ranks_by_score,
+    HistoryPoint, IdMap, PlatformEdition, PlayerHistory, PlayerProfile, StatHistory, StatRaw,
+    competition_ranks_by_score,
 };
 use mp_stats_core::routes;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
-use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use walkdir::WalkDir;
 
+/// `uuid -> last_seen` for every player that received a profile, where
+/// `last_seen` is the latest `save_time` among the profile's stats.
+pub type ProfiledPlayers = HashMap<String, u64>;
+
+/// `[(player_id, stats)]` as one player file lists them, before the ids are
+/// resolved.
+type FileStats = Vec<(String, Vec<StatRaw>)>;
+
 /// Process the player snapshot files into profile shards.
 ///
-/// Returns the set of UUIDs that actually received a profile shard entry. This
-/// set is later used to stamp a `has_profile` flag onto the names index so the
-/// frontend can hide search suggestions for players without any profile.
-pub fn process_java_players(
+/// Both editions share the player file layout. A Bedrock profile is keyed by
+/// the player's gamertag, which [`uuid_shard`] files the same way as a UUID.
+///
+/// Every profile carries its player's history from `name_histories`, and is
+/// named after the latest name in it the player was ranked under.
+///
+/// Returns the players that actually received a profile shard entry, with when
+/// each was last seen. They are later used to stamp `has_profile` and
+/// `last_seen` onto the names index so the frontend can hide search suggestions
+/// for players without any profile, and tell apart players sharing a name.
+///
+/// Each player file is a unit of `units`, keyed by its content: an unchanged
+/// file is not decompressed and parsed again. What it lists is resolved
+/// against the dictionary and the name histories on every run, since those
+/// change with the rest of the input.
+#[allow(clippy::too_many_arguments)]
+pub fn process_players(
     platform: &PlatformEdition,
-    java_in: &Path,
+    directory_in: &Path,
     output_directory: &Path,
     id_map: &IdMap,
     player_lookup_map: &HashMap<String, (String, String)>,
-) -> Result<HashSet<String>> {
-    let players_in = java_in.join("players");
+    name_histories: &NameHistories,
+    units: &UnitCache,
+    compression: &Compression,
+    tally: &Tally,
+) -> Result<ProfiledPlayers> {
+    let players_in = directory_in.join("players");
 
     if !players_in.exists() {
-        return Ok(HashSet::new());
+        return Ok(ProfiledPlayers::new());
     }
 
     let walker = WalkDir::new(&players_in).into_iter();
@@ -57,12 +91,21 @@ pub fn process_java_players(
     // Sharded storage: Prefix (e.g. "EF4") -> Map<UUID, Profile>
     let mut shards: HashMap<String, HashMap<String, PlayerProfile>> = files
         .par_iter()
-        .map(|path| {
-            process_player_shard(path, all_board_id, player_lookup_map).unwrap_or_else(|e| {
-                eprintln!("Failed to process player shard {:?}: {}", path, e);
-                HashMap::new()
-            })
-        })
+        .map(
+            |path| match read_player_file(path, all_board_id, units, tally) {
+                Ok(stats) => {
+                    tally.processed(Stage::PlayerShard);
+                    player_profiles(stats, player_lookup_map, name_histories, tally)
+                }
+                Err(e) => {
+                    tally.failed(
+                        Stage::PlayerShard,
+                        format!("Failed to process player shard {:?}: {}", path, e),
+                    );
+                    HashMap::new()
+                }
+            },
+        )
         .reduce(HashMap::new, |mut acc, file_shards| {
             for (prefix, mut uuid_map) in file_shards {
                 acc.entry(prefix).or_default().extend(uuid_map.drain());
@@ -78,10 +121,14 @@ pub fn process_java_players(
 
     println!("Writing {} player shards...", shards.len());
 
-    // Collect the set of UUIDs that received a profile.
-    let profiled_uuids: HashSet<String> = shards
+    // Collect the players that received a profile, and when each was last seen.
+    let profiled: ProfiledPlayers = shards
         .values()
-        .flat_map(|profile_map| profile_map.keys().cloned())
+        .flatten()
+        .map(|(uuid, profile)| {
+            let last_seen = profile.stats.iter().map(|s| s.save_time).max();
+            (uuid.clone(), last_seen.unwrap_or(0))
+        })
         .collect();
 
     // Write Shards
@@ -89,10 +136,187 @@ pub fn process_java_players(
         let relative_path = routes::player_shard_bin(platform, prefix);
         let out_path = output_directory.join(relative_path);
 
-        let _ = write_lzma_bin(&out_path, profile_map);
+        match write_lzma_bin_with(&out_path, profile_map, compression) {
+            Ok(()) => tally.processed(Stage::PlayerOutput),
+            Err(e) => tally.failed(
+                Stage::PlayerOutput,
+                format!("Failed to write player shard {:?}: {}", out_path, e),
+            ),
+        }
     });
 
-    Ok(profiled_uuids)
+    Ok(profiled)
+}
+
+/// Every player's points on the global board, spilled by history shard as
+/// the leaderboard pass streams them, so an edition's histories are never
+/// held whole. [`write_player_histories`] reads them back a shard at a time.
+///
+/// Each leaderboard spills to scratch files of its own, merged into the spool
+/// only once it is converted, so a leaderboard that fails partway leaves no
+/// points behind.
+pub struct HistorySpool {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+/// One point as the spool keeps it: whose, and on which leaderboard.
+#[derive(Serialize, Deserialize)]
+struct SpooledPoint {
+    uuid: SmolStr,
+    board: SmolStr,
+    game: SmolStr,
+    stat: SmolStr,
+    point: HistoryPoint,
+}
+
+impl HistorySpool {
+    /// A spool filling `dir`, which should be empty.
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Where one leaderboard's points go, spilled under `spill` until they
+    /// are merged.
+    pub fn writer(
+        &self,
+        spill: &SpillDir,
+        board: &str,
+        game: &str,
+        stat: &str,
+    ) -> Result<HistoryWriter<'_>> {
+        let scratch = spill.scratch("points")?;
+        Ok(HistoryWriter {
+            spool: self,
+            board: SmolStr::new(board),
+            game: SmolStr::new(game),
+            stat: SmolStr::new(stat),
+            spill: ShardedSpill::new(scratch.clone()),
+            scratch,
+        })
+    }
+}
+
+/// Spools the points of one board/game/stat leaderboard; see
+/// [`HistorySpool`]. They reach the spool only once [`finish`](Self::finish)
+/// succeeds; a writer dropped before, as when its leaderboard fails, discards
+/// them.
+pub struct HistoryWriter<'a> {
+    spool: &'a HistorySpool,
+    board: SmolStr,
+    game: SmolStr,
+    stat: SmolStr,
+    spill: ShardedSpill,
+    scratch: PathBuf,
+}
+
+impl HistoryWriter<'_> {
+    /// Add `uuid`'s point at one snapshot. A uuid too short to shard is left
+    /// out, as it would be from the profiles.
+    pub fn push(&mut self, uuid: &str, point: HistoryPoint) {
+        let Ok(shard) = uuid_shard(uuid) else {
+            return;
+        };
+        let point = SpooledPoint {
+            uuid: SmolStr::new(uuid),
+            board: self.board.clone(),
+            game: self.game.clone(),
+            stat: self.stat.clone(),
+            point,
+        };
+        self.spill.push(&shard, &point);
+    }
+
+    /// Merge the points pushed into the spool.
+    pub fn finish(mut self) -> Result<()> {
+        self.spill.flush()?;
+        merge_spilled(&self.scratch, &self.spool.dir, &self.spool.lock)
+    }
+}
+
+impl Drop for HistoryWriter<'_> {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.scratch);
+    }
+}
+
+/// Write the per-player score histories spooled by the leaderboard pass,
+/// sharded by uuid prefix exactly like the profile shards.
+///
+/// Each shard is read back, grouped and written on its own, so only the
+/// shards being written are held at once. Returns the number of shards,
+/// including any that failed to write and were counted in `tally`.
+pub fn write_player_histories(
+    platform: &PlatformEdition,
+    output_directory: &Path,
+    spool: HistorySpool,
+    compression: &Compression,
+    tally: &Tally,
+) -> Result<usize> {
+    let shards = spilled_shards(&spool.dir)?;
+    println!("Writing {} player history shards...", shards.len());
+
+    shards.par_iter().for_each(|(prefix, spilled)| {
+        let out_path = output_directory.join(routes::player_history_bin(platform, prefix));
+        let written = read_spilled::<SpooledPoint>(spilled)
+            .and_then(|points| points.collect::<Result<Vec<_>>>())
+            .map(player_histories)
+            .and_then(|histories| Ok(write_lzma_bin_with(&out_path, &histories, compression)?));
+        match written {
+            Ok(()) => tally.processed(Stage::PlayerOutput),
+            Err(e) => tally.failed(
+                Stage::PlayerOutput,
+                format!("Failed to write player history shard {:?}: {}", out_path, e),
+            ),
+        }
+    });
+
+    Ok(shards.len())
+}
+
+/// `(board, game, stat)` of one leaderboard.
+type Leaderboard = (SmolStr, SmolStr, SmolStr);
+
+/// Fold one shard's spooled points into a time series per player and
+/// leaderboard, oldest snapshot first.
+fn player_histories(points: Vec<SpooledPoint>) -> HashMap<String, PlayerHistory> {
+    let mut series: HashMap<SmolStr, HashMap<Leaderboard, Vec<HistoryPoint>>> = HashMap::new();
+    for spooled in points {
+        series
+            .entry(spooled.uuid)
+            .or_default()
+            .entry((spooled.board, spooled.game, spooled.stat))
+            .or_default()
+            .push(spooled.point);
+    }
+
+    series
+        .into_iter()
+        .map(|(uuid, leaderboards)| {
+            let mut stats: Vec<StatHistory> = leaderboards
+                .into_iter()
+                .map(|((board, game, stat), mut points)| {
+                    // Snapshots taken at the same time keep one order however
+                    // their leaderboard was spooled.
+                    points.sort_by(|a, b| {
+                        (a.timestamp, &a.snapshot_id).cmp(&(b.timestamp, &b.snapshot_id))
+                    });
+                    StatHistory {
+                        board,
+                        game,
+                        stat,
+                        points,
+                    }
+                })
+                .collect();
+            // Stable order, so identical input gives byte-identical shards.
+            stats.sort_by(|a, b| (&a.board, &a.game, &a.stat).cmp(&(&b.board, &b.game, &b.stat)));
+            (uuid.to_string(), PlayerHistory { uuid, stats })
+        })
+        .collect()
 }
 
 /// Recompute every profile's per-stat rank using standard competition ranking
@@ -139,32 +363,44 @@ fn assign_competition_ranks(shards: &mut HashMap<String, HashMap<String, PlayerP
     }
 }
 
-/// Process a single player shard file
-fn process_player_shard(
+/// Read the stats a player file lists, from `units` when the file is
+/// unchanged.
+fn read_player_file(
     path: &Path,
     all_board_id: Option<u32>,
-    player_lookup_map: &HashMap<String, (String, String)>,
-) -> Result<HashMap<String, HashMap<String, PlayerProfile>>> {
+    units: &UnitCache,
+    tally: &Tally,
+) -> Result<FileStats> {
+    let key = units.key("players", |key| {
+        key.bytes(format!("{all_board_id:?}").as_bytes());
+        key.file(path)
+    })?;
+    if let Some(key) = &key
+        && let Some(stats) = units.restore::<FileStats>(key, None)
+    {
+        tally.reused(&UnitCounts::default());
+        return Ok(stats);
+    }
+
+    let stats = parse_player_file(path, all_board_id)?;
+    if let Some(key) = &key
+        && let Err(e) = units.store(key, &stats, None)
+    {
+        eprintln!("  Failed to cache player file {:?}: {e:#}", path);
+    }
+    Ok(stats)
+}
+
+/// Decompress and parse a single player file
+fn parse_player_file(path: &Path, all_board_id: Option<u32>) -> Result<FileStats> {
     // Read & Decompress
     let decompressed = decompress_file_auto(path)?;
 
     // Parse JSON: {"15432": [stride...]}
     let raw_map: HashMap<String, Vec<serde_json::Value>> = serde_json::from_slice(&decompressed)?;
 
-    let mut shards: HashMap<String, HashMap<String, PlayerProfile>> = HashMap::new();
-
+    let mut players = Vec::with_capacity(raw_map.len());
     for (player_id_str, stride_data) in raw_map {
-        // Resolve Identity
-        let (uuid, name) = if let Some(info) = player_lookup_map.get(&player_id_str) {
-            (SmolStr::new(&info.0), Some(SmolStr::new(&info.1)))
-        } else {
-            (SmolStr::new("unknown"), None)
-        };
-
-        if uuid == "unknown" {
-            continue;
-        }
-
         // Parse Stride Data
         let count = stride_data.len() / 7;
         let mut stats = Vec::with_capacity(count);
@@ -199,16 +435,54 @@ fn process_player_shard(
                 save_time,
             });
         }
+        players.push((player_id_str, stats));
+    }
+
+    Ok(players)
+}
+
+/// Resolve the players of one file into profiles, by shard.
+fn player_profiles(
+    players: FileStats,
+    player_lookup_map: &HashMap<String, (String, String)>,
+    name_histories: &NameHistories,
+    tally: &Tally,
+) -> HashMap<String, HashMap<String, PlayerProfile>> {
+    let mut shards: HashMap<String, HashMap<String, PlayerProfile>> = HashMap::new();
+
+    for (player_id_str, stats) in players {
+        // Resolve Identity
+        let (uuid, name) = if let Some(info) = player_lookup_map.get(&player_id_str) {
+            (SmolStr::new(&info.0), Some(SmolStr::new(&info.1)))
+        } else {
+            (SmolStr::new("unknown"), None)
+        };
+
+        if uuid == "unknown" {
+            tally.unresolved(&player_id_str);
+            continue;
+        }
+
+        // The id a player file lists may be one from under an older name; the
+        // name last ranked under is the current one.
+        let name_history = name_histories
+            .get(uuid.as_str())
+            .cloned()
+            .unwrap_or_default();
+        let name = match name_history.last() {
+            Some(latest) if latest.last_seen > 0 => Some(latest.name.clone()),
+            _ => name,
+        };
 
         let profile = PlayerProfile {
             uuid: uuid.clone(),
             name,
             stats,
+            name_history,
         };
 
-        // Determine target shard from UUID
-        if uuid.len() >= 3 {
-            let prefix = uuid[..3].to_uppercase();
+        // Determine target shard from UUID (or Bedrock gamertag)
+        if let Ok(prefix) = uuid_shard(&uuid) {
             shards
                 .entry(prefix)
                 .or_default()
@@ -216,7 +490,7 @@ fn process_player_shard(
         }
     }
 
-    Ok(shards)
+    shards
 }
 
 #[cfg(test)]
@@ -239,6 +513,7 @@ mod tests {
             uuid: SmolStr::new(uuid),
             name: Some(SmolStr::new(uuid)),
             stats,
+            name_history: vec![],
         }
     }
 
@@ -320,4 +595,51 @@ mod tests {
         assert_eq!(rank_of(&shards, "p2", 2, 1), 1);
         assert_eq!(rank_of(&shards, "p1", 2, 1), 2);
     }
+
+    /// Only the points of a writer that finished reach the histories: one
+    /// dropped midway, as for a leaderboard that failed, leaves none behind.
+    #[test]
+    fn only_finished_writers_reach_the_histories() {
+        let dir = std::env::temp_dir().join(format!(
+            "mp_stats_spool_{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let spill = SpillDir::new(dir.join("spill"));
+        let spool = HistorySpool::new(spill.scratch("player_history").unwrap());
+        let point = |rank| HistoryPoint {
+            snapshot_id: SmolStr::new("latest"),
+            timestamp: 100,
+            score: 10,
+            rank,
+        };
+
+        let mut finished = spool.writer(&spill, "All", "game", "kept").unwrap();
+        finished.push("uuid-1", point(1));
+        finished.finish().unwrap();
+        let mut failed = spool.writer(&spill, "All", "game", "lost").unwrap();
+        failed.push("uuid-1", point(2));
+        failed.spill.flush().unwrap();
+        drop(failed);
+
+        let (edition, tally) = (PlatformEdition::Java, Tally::default());
+        let shards =
+            write_player_histories(&edition, &dir, spool, &Compression::default(), &tally).unwrap();
+        assert_eq!(shards, 1);
+        let shard = uuid_shard("uuid-1").unwrap();
+        let histories: HashMap<String, PlayerHistory> =
+            mp_stats_common::compression::read_lzma_bin(
+                &dir.join(routes::player_history_bin(&edition, &shard)),
+            )
+            .unwrap();
+        let stats: Vec<&str> = histories["uuid-1"]
+            .stats
+            .iter()
+            .map(|stat| stat.stat.as_str())
+            .collect();
+        assert_eq!(stats, ["kept"]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -0,0 +1,303 @@
+//! What a conversion run did, collected while it runs and written next to the output.
+//!
+//! The pipeline stages run on rayon and carry on past a chunk, page or shard they cannot
+//! process rather than abandon the whole run. Those failures used to reach stderr only, so a
+//! run that lost half its pages ended in the same "Conversion Complete!" as a clean one. Each
+//! stage now records into a shared [`Tally`], which becomes one [`EditionReport`] per edition;
+//! the [`ConversionReport`] gathering them is written as [`REPORT_FILE`] and is what
+//! `converter.strict` judges the run by.
+
+use anyhow::{Context, Result};
+use mp_stats_core::models::PlatformEdition;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The report's file name, at the root of the output directory.
+pub const REPORT_FILE: &str = "conversion_report.json";
+
+/// The file name of the report of a strict run that failed, at the root of the output directory
+/// beside its generations. Publishing a generation leaves it in place; the next failing run
+/// replaces it.
+pub const FAILED_REPORT_FILE: &str = "failed_conversion_report.json";
+
+/// How many failure messages an edition's report keeps. The counts stay exact; the messages
+/// are there to say what kind of failure it was, and a broken input can produce millions.
+const MAX_MESSAGES: usize = 100;
+
+/// A unit of work a stage counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// A board/game/stat leaderboard, latest snapshot and history together.
+    Leaderboard,
+    /// A raw leaderboard chunk read from the input.
+    Chunk,
+    /// A leaderboard page written to the output.
+    Page,
+    /// A history snapshot of a leaderboard.
+    Snapshot,
+    /// A shard of a leaderboard snapshot's position index.
+    PositionIndex,
+    /// A raw player file read from the input.
+    PlayerShard,
+    /// A player profile or history shard written to the output.
+    PlayerOutput,
+    /// A game's metadata file.
+    Game,
+    /// A names index file.
+    NameIndex,
+}
+
+/// How many units of one [`Stage`] went through, and how many were lost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageCounts {
+    pub processed: u64,
+    pub failed: u64,
+}
+
+/// Where an edition's output came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditionStatus {
+    /// Converted by this run.
+    Converted,
+    /// Restored from the conversion cache; the stages did not run.
+    Cached,
+    /// The edition's input directory does not exist, so there is no output for it.
+    MissingInput,
+}
+
+/// One edition's share of a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditionReport {
+    pub edition: PlatformEdition,
+    pub status: EditionStatus,
+    pub duration_ms: u64,
+    pub stages: BTreeMap<Stage, StageCounts>,
+    /// Distinct player ids that appear in the leaderboards or player files but not in the
+    /// dictionary. Their rows are left out; this is a gap in the input, not a failure.
+    pub unresolved_player_ids: u64,
+    /// Leaderboards and player files restored from the unit cache rather than converted. Their
+    /// stage counts are the ones recorded when they were converted.
+    #[serde(default)]
+    pub reused_units: u64,
+    /// The first failures' messages, at most a hundred.
+    pub errors: Vec<String>,
+}
+
+impl EditionReport {
+    /// Units lost across every stage.
+    pub fn failures(&self) -> u64 {
+        self.stages.values().map(|counts| counts.failed).sum()
+    }
+}
+
+/// Everything a run did, edition by edition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversionReport {
+    pub started_at_unix: u64,
+    pub duration_ms: u64,
+    pub editions: Vec<EditionReport>,
+}
+
+impl ConversionReport {
+    /// Units lost across every edition.
+    pub fn failures(&self) -> u64 {
+        self.editions.iter().map(EditionReport::failures).sum()
+    }
+
+    /// Write the report as [`REPORT_FILE`] under `dir`, creating `dir` if needed.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        self.write_as(dir, REPORT_FILE)
+    }
+
+    /// Write the report as `file_name` under `dir`, creating `dir` if needed.
+    pub fn write_as(&self, dir: &Path, file_name: &str) -> Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(file_name);
+        let json = serde_json::to_vec_pretty(self)?;
+        fs::write(&path, json).with_context(|| format!("writing {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// What one unit of work counted, kept with it in the unit cache so a unit restored from there
+/// counts as it did when it was converted. Only units that lost nothing are cached, so there
+/// are no messages to keep.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnitCounts {
+    pub stages: BTreeMap<Stage, StageCounts>,
+    pub unresolved: Vec<String>,
+}
+
+/// The counters one edition's stages share while they run, from any rayon worker.
+#[derive(Debug, Default)]
+pub struct Tally {
+    inner: Mutex<TallyInner>,
+}
+
+#[derive(Debug, Default)]
+struct TallyInner {
+    stages: BTreeMap<Stage, StageCounts>,
+    unresolved: HashSet<String>,
+    errors: Vec<String>,
+    reused: u64,
+}
+
+impl Tally {
+    fn with<R>(&self, f: impl FnOnce(&mut TallyInner) -> R) -> R {
+        f(&mut self.inner.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Count one unit of `stage` as done.
+    pub fn processed(&self, stage: Stage) {
+        self.with(|inner| inner.stages.entry(stage).or_default().processed += 1);
+    }
+
+    /// Count one unit of `stage` as lost, and say why on stderr and in the report.
+    pub fn failed(&self, stage: Stage, message: impl Display) {
+        let message = message.to_string();
+        eprintln!("{message}");
+        self.with(|inner| {
+            inner.stages.entry(stage).or_default().failed += 1;
+            if inner.errors.len() < MAX_MESSAGES {
+                inner.errors.push(message);
+            }
+        });
+    }
+
+    /// Record a player id the dictionary does not know.
+    pub fn unresolved(&self, player_id: &str) {
+        self.with(|inner| {
+            if !inner.unresolved.contains(player_id) {
+                inner.unresolved.insert(player_id.to_owned());
+            }
+        });
+    }
+
+    /// Count a unit restored from the unit cache, with what it counted when converted.
+    pub fn reused(&self, counts: &UnitCounts) {
+        self.with(|inner| {
+            inner.add(&counts.stages);
+            inner.unresolved.extend(counts.unresolved.iter().cloned());
+            inner.reused += 1;
+        });
+    }
+
+    /// Add everything `unit` counted, its failures and their messages included.
+    pub fn absorb(&self, unit: Tally) {
+        let unit = unit.inner.into_inner().unwrap_or_else(|e| e.into_inner());
+        self.with(|inner| {
+            inner.add(&unit.stages);
+            inner.unresolved.extend(unit.unresolved);
+            let room = MAX_MESSAGES.saturating_sub(inner.errors.len());
+            inner.errors.extend(unit.errors.into_iter().take(room));
+            inner.reused += unit.reused;
+        });
+    }
+
+    /// What this tally has counted so far, messages aside.
+    pub fn counts(&self) -> UnitCounts {
+        self.with(|inner| {
+            let mut unresolved: Vec<String> = inner.unresolved.iter().cloned().collect();
+            unresolved.sort();
+            UnitCounts {
+                stages: inner.stages.clone(),
+                unresolved,
+            }
+        })
+    }
+
+    /// Units lost so far across every stage.
+    pub fn failures(&self) -> u64 {
+        self.with(|inner| inner.stages.values().map(|counts| counts.failed).sum())
+    }
+
+    /// Close the tally into `edition`'s report.
+    pub fn into_report(
+        self,
+        edition: &PlatformEdition,
+        status: EditionStatus,
+        duration: Duration,
+    ) -> EditionReport {
+        let inner = self.inner.into_inner().unwrap_or_else(|e| e.into_inner());
+        EditionReport {
+            edition: edition.clone(),
+            status,
+            duration_ms: duration.as_millis() as u64,
+            stages: inner.stages,
+            unresolved_player_ids: inner.unresolved.len() as u64,
+            reused_units: inner.reused,
+            errors: inner.errors,
+        }
+    }
+}
+
+impl TallyInner {
+    fn add(&mut self, stages: &BTreeMap<Stage, StageCounts>) {
+        for (stage, counts) in stages {
+            let into = self.stages.entry(*stage).or_default();
+            into.processed += counts.processed;
+            into.failed += counts.failed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tally_counts_failures_and_caps_messages() {
+        let tally = Tally::default();
+        tally.processed(Stage::Chunk);
+        for i in 0..MAX_MESSAGES + 5 {
+            tally.failed(Stage::Page, format!("page {i}"));
+        }
+        tally.unresolved("42");
+        tally.unresolved("42");
+        tally.unresolved("43");
+
+        let report = tally.into_report(
+            &PlatformEdition::Java,
+            EditionStatus::Converted,
+            Duration::from_millis(5),
+        );
+
+        assert_eq!(report.stages[&Stage::Chunk].processed, 1);
+        assert_eq!(report.failures(), (MAX_MESSAGES + 5) as u64);
+        assert_eq!(report.errors.len(), MAX_MESSAGES);
+        assert_eq!(report.unresolved_player_ids, 2);
+    }
+
+    #[test]
+    fn a_reused_unit_counts_as_it_did_when_converted() {
+        let unit = Tally::default();
+        unit.processed(Stage::Page);
+        unit.processed(Stage::Page);
+        unit.unresolved("42");
+        let counts = unit.counts();
+
+        let converted = Tally::default();
+        converted.absorb(unit);
+        let reused = Tally::default();
+        reused.reused(&counts);
+
+        let report = |tally: Tally| {
+            tally.into_report(
+                &PlatformEdition::Java,
+                EditionStatus::Converted,
+                Duration::ZERO,
+            )
+        };
+        let (converted, reused) = (report(converted), report(reused));
+        assert_eq!(converted.stages, reused.stages);
+        assert_eq!(reused.unresolved_player_ids, 1);
+        assert_eq!((converted.reused_units, reused.reused_units), (0, 1));
+    }
+}
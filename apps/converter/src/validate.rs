@@ -0,0 +1,506 @@
+//! Input checks that run without converting anything, for `converter validate`.
+//!
+//! Everything here reads the input tree only; nothing is staged or written, so
+//! the check is safe to run against the data a live deployment converts from.
+//!
+//! The pipeline is lenient by design: a record it cannot resolve is skipped
+//! and counted, a stride value that is not a number reads as zero, a snapshot
+//! without metadata has no timestamp. This is the strict counterpart, which
+//! reports each of those as a violation, with the file and the offset in it.
+
+use crate::io::read_json;
+use crate::models::leaderboard::{BINARY_LEADERBOARD_SIZE, binary_leaderboard};
+use crate::pipeline::metadata::read_names_archive;
+use anyhow::Result;
+use mp_stats_common::compression::{decompress_file_auto, lzma_reader};
+use mp_stats_common::formats::FILE_META;
+use mp_stats_core::models::{IdMap, MetaFile, PlatformEdition};
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The values of one stat in a player file's stride, in order.
+const STRIDE_FIELDS: [&str; 7] = [
+    "board id",
+    "game id",
+    "stat id",
+    "save id",
+    "score",
+    "rank",
+    "save time",
+];
+
+/// The stride values the pipeline narrows to `u32`.
+const U32_FIELDS: [usize; 4] = [0, 1, 2, 5];
+
+/// One thing wrong with the input, and where.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Violation {
+    /// The file, or for an entry of a history archive, the archive joined
+    /// with the entry's path in it.
+    pub path: PathBuf,
+    /// Where in the file, when the problem is at one place in it: the byte
+    /// offset into a decompressed leaderboard chunk, or the index into a
+    /// player's stride.
+    pub offset: Option<u64>,
+    pub message: String,
+}
+
+impl Violation {
+    fn new(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            offset: None,
+            message: message.into(),
+        }
+    }
+
+    fn at(path: impl Into<PathBuf>, offset: u64, message: impl Into<String>) -> Self {
+        Self {
+            offset: Some(offset),
+            ..Self::new(path, message)
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(
+                f,
+                "{} at offset {offset}: {}",
+                self.path.display(),
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Check that `edition`'s input under `input_dir` has the shape the pipeline
+/// expects: a readable id map, parseable dictionary files, a readable Bedrock
+/// names archive where there is one, a metadata file for every leaderboard
+/// snapshot, chunks of whole records whose player ids the dictionary knows,
+/// and player files of whole, numeric strides.
+///
+/// Returns every violation found rather than stopping at the first one,
+/// sorted by file and offset. A missing edition directory is a single
+/// violation. Player ids are only checked against a dictionary that was read
+/// in full, so a broken dictionary is not also reported once per record.
+pub fn validate_edition(input_dir: &Path, edition: &PlatformEdition) -> Result<Vec<Violation>> {
+    let edition_dir = input_dir.join(edition.directory_name());
+    let mut violations = Vec::new();
+
+    if !edition_dir.is_dir() {
+        violations.push(Violation::new(
+            edition_dir,
+            "edition directory does not exist",
+        ));
+        return Ok(violations);
+    }
+
+    let map_path = edition_dir.join("meta/map.json");
+    if let Err(e) = read_json::<IdMap>(&map_path) {
+        violations.push(Violation::new(map_path, format!("{e:#}")));
+    }
+
+    let dictionary = edition_dir.join("dictionary/ids");
+    let mut ids = HashSet::new();
+    let mut dictionary_whole = dictionary.is_dir();
+    if !dictionary_whole {
+        violations.push(Violation::new(
+            dictionary.clone(),
+            "dictionary directory does not exist",
+        ));
+    }
+    for entry in WalkDir::new(&dictionary).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        match read_json::<HashMap<String, (String, Option<String>)>>(path) {
+            Ok(file) => ids.extend(file.into_keys()),
+            Err(e) => {
+                dictionary_whole = false;
+                violations.push(Violation::new(path, format!("{e:#}")));
+            }
+        }
+    }
+    let ids = dictionary_whole.then_some(&ids);
+
+    let names_archive = edition_dir.join("names.tar.xz");
+    if *edition == PlatformEdition::Bedrock
+        && names_archive.exists()
+        && let Err(e) = read_names_archive(&names_archive)
+    {
+        violations.push(Violation::new(names_archive, format!("{e:#}")));
+    }
+
+    let stat_dirs: Vec<PathBuf> = WalkDir::new(edition_dir.join("leaderboards"))
+        .min_depth(3)
+        .max_depth(3)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir())
+        .map(|e| e.into_path())
+        .collect();
+    violations.par_extend(
+        stat_dirs
+            .par_iter()
+            .flat_map_iter(|stat_dir| validate_leaderboard(stat_dir, ids)),
+    );
+
+    let player_files: Vec<PathBuf> = WalkDir::new(edition_dir.join("players"))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_type().is_file() && e.file_name().to_string_lossy().ends_with(".json.xz")
+        })
+        .map(|e| e.into_path())
+        .collect();
+    violations.par_extend(
+        player_files
+            .par_iter()
+            .flat_map_iter(|path| validate_player_file(path, ids)),
+    );
+
+    violations.sort();
+    Ok(violations)
+}
+
+/// Check one board/game/stat directory: its latest snapshot and its history.
+fn validate_leaderboard(stat_dir: &Path, ids: Option<&HashSet<String>>) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    let latest = stat_dir.join("latest");
+    if latest.is_dir() {
+        let meta_path = latest.join(FILE_META);
+        if !meta_path.exists() {
+            violations.push(Violation::new(
+                latest.clone(),
+                format!("missing {FILE_META}"),
+            ));
+        } else if let Err(e) = read_json::<MetaFile>(&meta_path) {
+            violations.push(Violation::new(meta_path, format!("{e:#}")));
+        }
+
+        let mut chunks: Vec<PathBuf> = WalkDir::new(&latest)
+            .min_depth(1)
+            .max_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| e.into_path())
+            .filter(|path| path.extension().is_some_and(|e| e == "xz"))
+            .collect();
+        chunks.sort();
+        for path in chunks {
+            match lzma_reader(&path) {
+                Ok(reader) => validate_chunk(&path, reader, ids, &mut violations),
+                Err(e) => violations.push(Violation::new(&path, format!("{e:#}"))),
+            }
+        }
+    }
+
+    let history = stat_dir.join("history.tar.xz");
+    if history.exists()
+        && let Err(e) = validate_history(&history, ids, &mut violations)
+    {
+        violations.push(Violation::new(
+            history,
+            format!("reading the archive: {e:#}"),
+        ));
+    }
+
+    violations
+}
+
+/// Check every snapshot of a history archive: a parseable metadata file, and
+/// chunks of whole records whose player ids the dictionary knows.
+fn validate_history(
+    history: &Path,
+    ids: Option<&HashSet<String>>,
+    violations: &mut Vec<Violation>,
+) -> Result<()> {
+    let mut snapshots = BTreeSet::new();
+    let mut with_meta = BTreeSet::new();
+
+    let mut archive = tar::Archive::new(lzma_reader(history)?);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let entry_path = entry.path()?.to_string_lossy().into_owned();
+        let Some((snapshot, file_name)) = entry_path.split_once('/') else {
+            continue;
+        };
+        if file_name.is_empty() {
+            continue;
+        }
+        snapshots.insert(snapshot.to_string());
+
+        let path = history.join(&entry_path);
+        if file_name == FILE_META {
+            with_meta.insert(snapshot.to_string());
+            if let Err(e) = serde_json::from_reader::<_, MetaFile>(BufReader::new(entry)) {
+                violations.push(Violation::new(path, e.to_string()));
+            }
+        } else if file_name.starts_with("chunk_") && file_name.ends_with(".bin") {
+            validate_chunk(&path, entry, ids, violations);
+        }
+    }
+
+    for snapshot in snapshots.difference(&with_meta) {
+        violations.push(Violation::new(
+            history.join(snapshot),
+            format!("missing {FILE_META}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Check one leaderboard chunk, decompressed, as read from `reader`: whole
+/// records, none for player id 0, and every player id in the dictionary.
+fn validate_chunk(
+    path: &Path,
+    reader: impl Read,
+    ids: Option<&HashSet<String>>,
+    violations: &mut Vec<Violation>,
+) {
+    let mut reader = BufReader::new(reader);
+    let mut record = [0u8; BINARY_LEADERBOARD_SIZE];
+    let mut offset = 0u64;
+    loop {
+        let filled = match read_full(&mut reader, &mut record) {
+            Ok(filled) => filled,
+            Err(e) => {
+                violations.push(Violation::at(path, offset, format!("unreadable: {e}")));
+                return;
+            }
+        };
+        if filled < record.len() {
+            if filled > 0 {
+                violations.push(Violation::at(
+                    path,
+                    offset,
+                    format!(
+                        "ends with {filled} bytes, not a whole {BINARY_LEADERBOARD_SIZE}-byte record"
+                    ),
+                ));
+            }
+            return;
+        }
+
+        let player_id = binary_leaderboard::View::new(&record[..])
+            .player_id()
+            .read();
+        if player_id == 0 {
+            violations.push(Violation::at(path, offset, "player id 0"));
+        } else if let Some(ids) = ids
+            && !ids.contains(&player_id.to_string())
+        {
+            violations.push(Violation::at(
+                path,
+                offset,
+                format!("player id {player_id} is not in the dictionary"),
+            ));
+        }
+        offset += BINARY_LEADERBOARD_SIZE as u64;
+    }
+}
+
+/// Fill as much of `buf` as `reader` has left, returning how much that was.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Check one player file: numeric player ids the dictionary knows, each with
+/// a stride of whole stats whose values are unsigned integers that fit.
+fn validate_player_file(path: &Path, ids: Option<&HashSet<String>>) -> Vec<Violation> {
+    let parsed = decompress_file_auto(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| {
+            Ok(serde_json::from_slice::<
+                HashMap<String, Vec<serde_json::Value>>,
+            >(&bytes)?)
+        });
+    let players = match parsed {
+        Ok(players) => players,
+        Err(e) => return vec![Violation::new(path, format!("{e:#}"))],
+    };
+
+    let mut violations = Vec::new();
+    for (player_id, stride) in players {
+        if player_id.parse::<u64>().is_err() {
+            violations.push(Violation::new(
+                path,
+                format!("player id '{player_id}' is not a number"),
+            ));
+        } else if let Some(ids) = ids
+            && !ids.contains(&player_id)
+        {
+            violations.push(Violation::new(
+                path,
+                format!("player id {player_id} is not in the dictionary"),
+            ));
+        }
+
+        let whole = stride.len() - stride.len() % STRIDE_FIELDS.len();
+        if whole < stride.len() {
+            violations.push(Violation::at(
+                path,
+                whole as u64,
+                format!(
+                    "player {player_id}: stride of {} values is not a whole number of \
+                     {}-value stats",
+                    stride.len(),
+                    STRIDE_FIELDS.len()
+                ),
+            ));
+        }
+
+        for (index, value) in stride[..whole].iter().enumerate() {
+            let field = index % STRIDE_FIELDS.len();
+            let problem = match value.as_u64() {
+                None => "is not an unsigned integer",
+                Some(n) if U32_FIELDS.contains(&field) && u32::try_from(n).is_err() => {
+                    "does not fit in 32 bits"
+                }
+                Some(_) => continue,
+            };
+            violations.push(Violation::at(
+                path,
+                index as u64,
+                format!(
+                    "player {player_id}: {} {value} {problem}",
+                    STRIDE_FIELDS[field]
+                ),
+            ));
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mp_stats_common::compression::write_lzma_raw;
+    use std::fs;
+
+    fn record(player_id: u64, score: u64) -> Vec<u8> {
+        [player_id.to_be_bytes(), score.to_be_bytes()].concat()
+    }
+
+    /// Each kind of malformed input is reported once, at the file and offset
+    /// it is found.
+    #[test]
+    fn every_violation_is_reported_with_its_file_and_offset() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let input = std::env::temp_dir().join(format!("mp_stats_validate_test_{nanos}"));
+        let java = input.join("java");
+        fs::create_dir_all(java.join("dictionary/ids")).unwrap();
+        fs::write(
+            java.join("dictionary/ids/0.json"),
+            r#"{"7": ["uuid-7", "Seven"]}"#,
+        )
+        .unwrap();
+
+        // A latest snapshot without metadata: a known player, player id 0, a
+        // dangling id, and three bytes of a record that never ends.
+        let stat = java.join("leaderboards/All/Game/Wins");
+        fs::create_dir_all(stat.join("latest")).unwrap();
+        let chunk = [record(7, 100), record(0, 90), record(8, 80), vec![1, 2, 3]].concat();
+        write_lzma_raw(&stat.join("latest/chunk_0000.bin.xz"), &chunk).unwrap();
+
+        // A history snapshot without metadata, ranking another dangling id.
+        let mut tar = tar::Builder::new(Vec::new());
+        let history_chunk = record(9, 5);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(history_chunk.len() as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, "100/chunk_0000.bin", &history_chunk[..])
+            .unwrap();
+        write_lzma_raw(&stat.join("history.tar.xz"), &tar.into_inner().unwrap()).unwrap();
+
+        // One stride cut short, one with a value that is not a number.
+        fs::create_dir_all(java.join("players")).unwrap();
+        let players = r#"{"7": [1, 2, 3, 4, 5, 6, 7, 1, 2], "x": [1, "a", 3, 4, 5, 6, 7]}"#;
+        write_lzma_raw(&java.join("players/0.json.xz"), players.as_bytes()).unwrap();
+
+        let violations = validate_edition(&input, &PlatformEdition::Java).unwrap();
+        let found: Vec<(String, Option<u64>, &str)> = violations
+            .iter()
+            .filter(|v| !v.path.ends_with("meta/map.json"))
+            .map(|v| {
+                let path = v.path.strip_prefix(&java).unwrap();
+                (
+                    path.to_string_lossy().replace('\\', "/"),
+                    v.offset,
+                    v.message.as_str(),
+                )
+            })
+            .collect();
+
+        let chunk = "leaderboards/All/Game/Wins/latest/chunk_0000.bin.xz";
+        assert_eq!(
+            found,
+            [
+                (
+                    "leaderboards/All/Game/Wins/history.tar.xz/100".into(),
+                    None,
+                    "missing _meta.json"
+                ),
+                (
+                    "leaderboards/All/Game/Wins/history.tar.xz/100/chunk_0000.bin".into(),
+                    Some(0),
+                    "player id 9 is not in the dictionary"
+                ),
+                (
+                    "leaderboards/All/Game/Wins/latest".into(),
+                    None,
+                    "missing _meta.json"
+                ),
+                (chunk.into(), Some(16), "player id 0"),
+                (
+                    chunk.into(),
+                    Some(32),
+                    "player id 8 is not in the dictionary"
+                ),
+                (
+                    chunk.into(),
+                    Some(48),
+                    "ends with 3 bytes, not a whole 16-byte record"
+                ),
+                (
+                    "players/0.json.xz".into(),
+                    None,
+                    "player id 'x' is not a number"
+                ),
+                (
+                    "players/0.json.xz".into(),
+                    Some(1),
+                    "player x: game id \"a\" is not an unsigned integer"
+                ),
+                (
+                    "players/0.json.xz".into(),
+                    Some(7),
+                    "player 7: stride of 9 values is not a whole number of 7-value stats"
+                ),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&input);
+    }
+}
@@ -1,8 +1,18 @@
-use mp_stats_common::compression::read_lzma_bin;
-use mp_stats_converter::{ConversionCache, Converter};
-use mp_stats_core::models::{GameLeaderboardData, PlatformEdition};
+use mp_stats_common::compression::{Compression, read_lzma_bin};
+use mp_stats_common::{generation, manifest};
+use mp_stats_config::StrictConfig;
+use mp_stats_converter::report::{FAILED_REPORT_FILE, REPORT_FILE};
+use mp_stats_converter::{
+    ConversionCache, ConversionReport, Converter, EditionStatus, Stage, copy_dir_all,
+    default_staging_root, list_generations,
+};
+use mp_stats_core::models::{
+    GameLeaderboardData, LeaderboardPosition, NameCandidate, NameMatch, PlatformEdition,
+    PlayerHistory, PlayerProfile, ScorePlacement, place_score,
+};
 use mp_stats_core::routes;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 /// Both integration tests drive `Converter::convert`. Each converter now uses
@@ -16,16 +26,13 @@ static CONVERT_GUARD: Mutex<()> = Mutex::new(());
 /// directory the test binary is executed from.
 fn data_test_dir() -> Option<PathBuf> {
     let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    for candidate in [
+    [
         manifest.join("../../data-test"),
         manifest.join("../../../data-test"),
         PathBuf::from("data-test"),
-    ] {
-        if candidate.join("java").is_dir() {
-            return Some(candidate);
-        }
-    }
-    None
+    ]
+    .into_iter()
+    .find(|candidate| candidate.join("java").is_dir())
 }
 
 #[test]
@@ -49,9 +56,22 @@ fn conversion_populates_per_category_top_and_total_entries() {
     // Disable the on-disk cache so the test always exercises a full conversion.
     let converter = Converter::with_cache(input, output.clone(), ConversionCache::disabled())
         .expect("converter setup");
-    converter.convert().expect("conversion succeeds");
+    let report = converter.convert().expect("conversion succeeds");
+    assert_eq!(report.failures(), 0, "the fixture converts without loss");
+    let served = current_output(&output);
+
+    // The manifest covers exactly what was written.
+    let java_dir = served.join(PlatformEdition::Java.directory_name());
+    let manifest = manifest::verify(&java_dir).expect("output matches its manifest");
+    assert!(
+        manifest
+            .files
+            .iter()
+            .any(|f| f.path == "meta/map.bin.xz" && f.size > 0),
+        "the manifest lists the metadata map"
+    );
 
-    let game_path = output.join(routes::game_bin(&PlatformEdition::Java, "ABarbariansLife"));
+    let game_path = served.join(routes::game_bin(&PlatformEdition::Java, "ABarbariansLife"));
     assert!(
         game_path.exists(),
         "expected game metadata file at {:?}",
@@ -67,18 +87,111 @@ fn conversion_populates_per_category_top_and_total_entries() {
     );
 
     // At least one category/board should expose its `#1 holder`.
-    let top = game
+    let (top_stat, top_board, top) = game
         .stats
-        .values()
-        .flat_map(|boards| boards.values())
-        .find_map(|meta| meta.top.as_ref())
+        .iter()
+        .flat_map(|(stat, boards)| boards.iter().map(move |(board, meta)| (stat, board, meta)))
+        .find_map(|(stat, board, meta)| meta.top.as_ref().map(|top| (stat, board, top)))
         .expect("at least one category should have a top holder");
     assert!(!top.uuid.is_empty(), "top holder must have a uuid");
     assert!(top.score > 0, "top holder score should be positive");
     assert!(!top.name.is_empty(), "top holder name should be set");
 
+    // The latest snapshot's page score ranges start at their score, so a
+    // higher one would have placed first.
+    let latest = game.stats[top_stat][top_board]
+        .snapshots
+        .iter()
+        .find(|s| s.snapshot_id == "latest")
+        .expect("the board has a latest snapshot");
+    assert_eq!(latest.page_scores.first().map(|r| r.max), Some(top.score));
+    assert_eq!(
+        place_score(&latest.page_scores, top.score + 1),
+        ScorePlacement::Rank(1)
+    );
+
+    // Its distribution covers every written row, up to the top score.
+    let spread = latest
+        .distribution
+        .as_ref()
+        .expect("the latest snapshot has a distribution");
+    let written: u32 = latest.page_scores.iter().map(|r| r.entries).sum();
+    let counted: u32 = spread.buckets.iter().map(|b| b.count).sum();
+    assert_eq!(counted, written);
+    assert_eq!(spread.buckets.last().map(|b| b.max), Some(top.score));
+    assert!(spread.p50 <= spread.p90 && spread.p90 <= spread.p99);
+
+    // The latest snapshot's position index files them on its first page.
+    let positions_path = served.join(routes::leaderboard_positions_bin(
+        &PlatformEdition::Java,
+        top_board,
+        &game.game_id,
+        top_stat,
+        &mp_stats_common::shard::position_shard(&top.uuid).expect("position shard"),
+    ));
+    let positions: HashMap<String, LeaderboardPosition> =
+        read_lzma_bin(&positions_path).expect("read position index shard");
+    let position = positions[top.uuid.as_str()];
+    assert_eq!((position.page, position.rank), (0, 1));
+
+    // The top holder has a profile, so the name search finds them through any
+    // trigram of their name.
+    let trigram = mp_stats_common::shard::name_trigrams(&top.name)
+        .pop()
+        .expect("top holder name has a trigram");
+    let matches: Vec<NameMatch> =
+        read_lzma_bin(&served.join(routes::name_trigram_bin(&PlatformEdition::Java, &trigram)))
+            .expect("read name trigram file");
+    assert!(
+        matches.iter().any(|m| m.uuid == top.uuid),
+        "top holder should be listed under {trigram}"
+    );
+
+    // The top holder appears on the global board, so they have a history shard
+    // whose series run oldest snapshot first.
+    let shard = mp_stats_common::shard::uuid_shard(&top.uuid).expect("shard key");
+    let history_path = served.join(routes::player_history_bin(&PlatformEdition::Java, &shard));
+    let histories: HashMap<String, PlayerHistory> =
+        read_lzma_bin(&history_path).expect("read player history shard");
+    let history = histories
+        .get(top.uuid.as_str())
+        .expect("top holder should have a history");
+    assert!(!history.stats.is_empty(), "history should cover some stat");
+    for stat in &history.stats {
+        assert!(
+            stat.points
+                .windows(2)
+                .all(|w| w[0].timestamp <= w[1].timestamp),
+            "points should be in snapshot order"
+        );
+    }
+
+    // Their profile's name history ends with the name they were last ranked
+    // under, dated by the snapshots they were ranked in.
+    let profile_path = served.join(routes::player_shard_bin(&PlatformEdition::Java, &shard));
+    let profiles: HashMap<String, PlayerProfile> =
+        read_lzma_bin(&profile_path).expect("read player shard");
+    let profile = &profiles[top.uuid.as_str()];
+    let latest = profile
+        .name_history
+        .last()
+        .expect("top holder has a name history");
+    assert_eq!(profile.name.as_ref(), Some(&latest.name));
+    assert!(latest.first_seen > 0 && latest.first_seen <= latest.last_seen);
+
     // Cleanup best-effort.
-    let _ = std::fs::remove_dir_all(&output);
+    remove_output(&output);
+}
+
+/// The directory a converted output is served from: its current generation.
+fn current_output(output: &Path) -> PathBuf {
+    generation::resolve(output).expect("the output has a current generation")
+}
+
+/// Remove a test's output, and the directory its runs staged in beside it.
+fn remove_output(output: &Path) {
+    let _ = std::fs::remove_dir_all(output);
+    let _ = std::fs::remove_dir_all(default_staging_root(output));
 }
 
 /// Recursively collect every file under `root` as a map of its path relative to
@@ -143,9 +256,11 @@ fn cached_run_reuses_output_and_matches_uncached_run() {
 
     // The cache must now hold a stored output + matching fingerprint for the
     // Java edition, proving `store` ran and the next run will hit the cache.
-    let fingerprint =
-        ConversionCache::fingerprint_dir(&input.join(PlatformEdition::Java.directory_name()))
-            .expect("fingerprint java input");
+    let fingerprint = ConversionCache::fingerprint(
+        &input.join(PlatformEdition::Java.directory_name()),
+        &Compression::default(),
+    )
+    .expect("fingerprint java input");
     let probe = tmp.join(format!("mp_stats_probe_{unique}"));
     let restored = ConversionCache::new(cache_root.clone())
         .restore(PlatformEdition::Java.directory_name(), fingerprint, &probe)
@@ -165,8 +280,11 @@ fn cached_run_reuses_output_and_matches_uncached_run() {
     warm.convert().expect("warm conversion succeeds");
 
     // The cached (warm) output must be byte-for-byte identical to the cold one.
-    let cold_tree = collect_tree(&output_cold);
-    let warm_tree = collect_tree(&output_warm);
+    // The reports differ by design: timings, and `cached` rather than `converted`.
+    let mut cold_tree = collect_tree(&current_output(&output_cold));
+    let mut warm_tree = collect_tree(&current_output(&output_warm));
+    cold_tree.remove(REPORT_FILE);
+    warm_tree.remove(REPORT_FILE);
     assert!(
         !cold_tree.is_empty(),
         "cold run produced no output files at {output_cold:?}"
@@ -183,7 +301,238 @@ fn cached_run_reuses_output_and_matches_uncached_run() {
 
     // Cleanup best-effort.
     let _ = std::fs::remove_dir_all(&cache_root);
-    let _ = std::fs::remove_dir_all(&output_cold);
-    let _ = std::fs::remove_dir_all(&output_warm);
+    remove_output(&output_cold);
+    remove_output(&output_warm);
     let _ = std::fs::remove_dir_all(&probe);
 }
+
+/// A run whose input changed in one leaderboard converts that leaderboard and
+/// restores every other unit, and its output matches what a full conversion
+/// of the same input would have written.
+#[test]
+fn changed_leaderboard_is_converted_and_the_other_units_reused() {
+    let Some(fixture) = data_test_dir() else {
+        eprintln!("data-test fixture not found; skipping integration test");
+        return;
+    };
+
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let tmp = std::env::temp_dir();
+    let input = tmp.join(format!("mp_stats_units_in_{unique}"));
+    let cache_root = tmp.join(format!("mp_stats_units_cache_{unique}"));
+    let output_cold = tmp.join(format!("mp_stats_units_cold_{unique}"));
+    let output_warm = tmp.join(format!("mp_stats_units_warm_{unique}"));
+    copy_dir_all(&fixture, &input).expect("copy fixture");
+
+    let _guard = CONVERT_GUARD.lock().unwrap_or_else(|e| e.into_inner());
+    let convert = |output: &PathBuf| {
+        let report = Converter::with_cache(
+            input.clone(),
+            output.clone(),
+            ConversionCache::new(cache_root.clone()),
+        )
+        .expect("converter setup")
+        .with_editions(vec![PlatformEdition::Java])
+        .convert()
+        .expect("conversion succeeds");
+        report
+            .editions
+            .into_iter()
+            .find(|e| e.edition == PlatformEdition::Java)
+            .expect("java in the report")
+    };
+    let cold = convert(&output_cold);
+    assert_eq!(cold.reused_units, 0);
+
+    // The same metadata, reformatted: the leaderboard's input is no longer
+    // byte-for-byte what was cached, but converts to the same pages.
+    let latest = walkdir::WalkDir::new(input.join("java/leaderboards"))
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_type().is_dir() && e.file_name() == "latest")
+        .expect("fixture has a latest leaderboard");
+    let meta_path = latest.path().join("_meta.json");
+    let meta: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&meta_path).unwrap()).unwrap();
+    std::fs::write(&meta_path, serde_json::to_vec_pretty(&meta).unwrap()).unwrap();
+
+    let warm = convert(&output_warm);
+    assert_eq!(warm.status, EditionStatus::Converted);
+    let units =
+        cold.stages[&Stage::Leaderboard].processed + cold.stages[&Stage::PlayerShard].processed;
+    assert_eq!(warm.reused_units, units - 1);
+    assert_eq!(warm.stages, cold.stages);
+
+    // Both runs wrote the same files. The other leaderboards' were restored
+    // byte for byte; the rest was written afresh, and maps serialize in no
+    // particular order, so the game metadata and the player histories, which
+    // hold the restored leaderboards' points too, are compared decoded.
+    let changed_dir = latest
+        .path()
+        .parent()
+        .unwrap()
+        .strip_prefix(&input)
+        .unwrap()
+        .to_string_lossy()
+        .replace('\\', "/");
+    let (cold_out, warm_out) = (current_output(&output_cold), current_output(&output_warm));
+    let (cold_tree, warm_tree) = (collect_tree(&cold_out), collect_tree(&warm_out));
+    assert_eq!(
+        cold_tree.keys().collect::<Vec<_>>(),
+        warm_tree.keys().collect::<Vec<_>>(),
+        "a partly reused run wrote a different set of files"
+    );
+    for (path, bytes) in &cold_tree {
+        if path.contains("/leaderboards/") && !path.starts_with(&changed_dir) {
+            assert_eq!(bytes, &warm_tree[path], "{path} was not reused");
+        }
+    }
+    for entry in std::fs::read_dir(cold_out.join("java/games")).expect("games written") {
+        let path = entry.unwrap().path();
+        let warm_path = warm_out.join("java/games").join(path.file_name().unwrap());
+        let cold_game: GameLeaderboardData = read_lzma_bin(&path).unwrap();
+        let warm_game: GameLeaderboardData = read_lzma_bin(&warm_path).unwrap();
+        assert_eq!(cold_game, warm_game, "{path:?} differs");
+    }
+    let histories = cold_out.join("java/player_history");
+    for entry in std::fs::read_dir(&histories).expect("player histories written") {
+        let path = entry.unwrap().path();
+        let warm_path = warm_out
+            .join("java/player_history")
+            .join(path.file_name().unwrap());
+        let cold_shard: HashMap<String, PlayerHistory> = read_lzma_bin(&path).unwrap();
+        let warm_shard: HashMap<String, PlayerHistory> = read_lzma_bin(&warm_path).unwrap();
+        assert_eq!(cold_shard, warm_shard, "{path:?} differs");
+    }
+
+    for dir in [&input, &cache_root] {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    remove_output(&output_cold);
+    remove_output(&output_warm);
+}
+
+/// A strict run that loses work must fail without replacing the output, and
+/// still leave a report saying what was lost.
+#[test]
+fn strict_run_fails_on_lost_work_and_keeps_the_output() {
+    let Some(fixture) = data_test_dir() else {
+        eprintln!("data-test fixture not found; skipping integration test");
+        return;
+    };
+
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let tmp = std::env::temp_dir();
+    let input = tmp.join(format!("mp_stats_strict_in_{unique}"));
+    let output = tmp.join(format!("mp_stats_strict_out_{unique}"));
+
+    // A copy of the fixture with one leaderboard chunk that is not XZ at all.
+    copy_dir_all(&fixture, &input).expect("copy fixture");
+    let latest = walkdir::WalkDir::new(input.join("java/leaderboards"))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_type().is_dir() && e.file_name() == "latest")
+        .expect("fixture has a latest leaderboard");
+    std::fs::write(latest.path().join("chunk_9999.bin.xz"), b"not xz").unwrap();
+
+    let _guard = CONVERT_GUARD.lock().unwrap_or_else(|e| e.into_inner());
+
+    let current = generation::read_current(&output).unwrap();
+    let generations = list_generations(&output).unwrap();
+    let converter =
+        Converter::with_cache(input.clone(), output.clone(), ConversionCache::disabled())
+            .expect("converter setup")
+            .with_strict(StrictConfig {
+                enabled: true,
+                max_errors: 0,
+            });
+    assert!(converter.convert().is_err(), "strict run should fail");
+
+    assert_eq!(current, None);
+    assert_eq!(
+        generation::read_current(&output).unwrap(),
+        current,
+        "a failed strict run must not publish a generation"
+    );
+    assert_eq!(
+        list_generations(&output).unwrap(),
+        generations,
+        "a failed strict run must not write a generation"
+    );
+    let report: ConversionReport = serde_json::from_slice(
+        &std::fs::read(output.join(FAILED_REPORT_FILE)).expect("report written"),
+    )
+    .expect("report parses");
+    let java = report
+        .editions
+        .iter()
+        .find(|e| e.edition == PlatformEdition::Java)
+        .expect("java in the report");
+    assert_eq!(java.stages[&Stage::Chunk].failed, 1);
+    assert!(!java.errors.is_empty());
+
+    let _ = std::fs::remove_dir_all(&input);
+    remove_output(&output);
+}
+
+/// Bedrock players are keyed by gamertag. Gamertags with spaces or punctuation
+/// must land in URL-safe shards, and the names index comes from `names.tar.xz`.
+#[test]
+fn bedrock_output_is_keyed_by_gamertag() {
+    let Some(input) = data_test_dir() else {
+        eprintln!("data-test fixture not found; skipping integration test");
+        return;
+    };
+
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let output = std::env::temp_dir().join(format!("mp_stats_bedrock_{unique}"));
+
+    let _guard = CONVERT_GUARD.lock().unwrap_or_else(|e| e.into_inner());
+
+    let converter = Converter::with_cache(input, output.clone(), ConversionCache::disabled())
+        .expect("converter setup")
+        .with_editions(vec![PlatformEdition::Bedrock]);
+    let report = converter.convert().expect("conversion succeeds");
+    assert_eq!(report.failures(), 0);
+    let served = current_output(&output);
+
+    let edition = PlatformEdition::Bedrock;
+    for dir in ["players", "names_index"] {
+        for entry in std::fs::read_dir(served.join(edition.directory_name()).join(dir)).unwrap() {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            let key = name.strip_suffix(".bin.xz").expect("shard file");
+            assert!(
+                key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "shard {dir}/{name} is not URL-safe"
+            );
+        }
+    }
+
+    let shard = mp_stats_common::shard::uuid_shard("C Titan").unwrap();
+    let profiles: HashMap<String, PlayerProfile> =
+        read_lzma_bin(&served.join(routes::player_shard_bin(&edition, &shard)))
+            .expect("read player shard");
+    assert!(profiles.contains_key("C Titan"));
+
+    let names: HashMap<String, Vec<NameCandidate>> =
+        read_lzma_bin(&served.join(routes::names_index_bin(&edition, "ct_")))
+            .expect("read names index");
+    let [titan] = names["C Titan"].as_slice() else {
+        panic!("one player is named C Titan: {:?}", names["C Titan"]);
+    };
+    assert_eq!(titan.uuid, "C Titan");
+    assert!(titan.has_profile);
+    assert!(titan.last_seen > 0, "a profiled player has been seen");
+
+    remove_output(&output);
+}
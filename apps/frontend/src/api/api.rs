@@ -1,8 +1,11 @@
 use gloo_net::http::Request;
 use mp_stats_common::compression::uncompress_lzma;
+use mp_stats_common::formats::raw;
+use mp_stats_common::shard::{name_shard, name_trigrams, position_shard, uuid_shard};
 use mp_stats_core::models::{
-    GameLeaderboardData, IdMap, LeaderboardEntry, LeaderboardPage, PlatformEdition, PlatformMeta,
-    PlayerProfile,
+    GameLeaderboardData, HistoricalSnapshot, IdMap, LeaderboardEntry, LeaderboardPage,
+    LeaderboardPosition, NameCandidate, NameMatch, PlatformEdition, PlatformMeta, PlayerHistory,
+    PlayerProfile, ScorePlacement, place_score, rank_within_page,
 };
 use mp_stats_core::routes;
 use smol_str::SmolStr;
@@ -62,6 +65,11 @@ impl Api {
 
     const TTL_ERROR_MS: f64 = 10.0 * 1000.0; // 10 Seconds
 
+    /// Trigram files fetched per edition for one query; a long query is
+    /// matched on an evenly spread sample of its trigrams.
+    const MAX_QUERY_TRIGRAMS: usize = 6;
+    const MAX_SEARCH_RESULTS: usize = 10;
+
     const SWEEP_INTERVAL_MS: u64 = 30_000; // every 30s at most
 
     fn maybe_sweep_expired(&self) {
@@ -166,23 +174,29 @@ impl Api {
 
     /// Fetch the names index for a prefix.
     ///
-    /// Each entry maps a player name to `(uuid, has_profile)`, where
-    /// `has_profile` indicates whether an actual profile exists for that player.
+    /// Each entry maps a player name to every [`NameCandidate`] carrying it,
+    /// most recently seen first; `has_profile` indicates whether an actual
+    /// profile exists for that player.
     ///
-    /// To stay compatible with data generated before the `has_profile` flag was
-    /// introduced, this falls back to the legacy `name -> uuid` layout when the
-    /// new tuple layout cannot be decoded. Legacy entries are treated as having
-    /// a profile so search keeps returning results against older data instead of
-    /// silently showing nothing.
+    /// Data generated by older converters is read too, see
+    /// [`decode_name_index`], so search keeps returning results against it
+    /// instead of silently showing nothing.
     async fn get_name_index(
         &self,
         edition: &PlatformEdition,
         prefix: &str,
-    ) -> Option<HashMap<String, (String, bool)>> {
-        let url = format!("/data/{}", routes::names_index_bin(edition, prefix));
+    ) -> Option<HashMap<String, Vec<NameCandidate>>> {
+        self.get_name_index_file(&format!(
+            "/data/{}",
+            routes::names_index_bin(edition, prefix)
+        ))
+        .await
+    }
 
+    /// Fetch a names index file by URL; see [`Api::get_name_index`].
+    async fn get_name_index_file(&self, url: &str) -> Option<HashMap<String, Vec<NameCandidate>>> {
         let bytes = self
-            .get_decompressed_cached(&url, Self::TTL_NAME_INDEX_MS)
+            .get_decompressed_cached(url, Self::TTL_NAME_INDEX_MS)
             .await
             .ok()?;
 
@@ -205,21 +219,7 @@ impl Api {
     pub async fn fetch_meta(&self, edition: &PlatformEdition) -> ApiResult<PlatformMeta> {
         let id_map = self.fetch_id_map(edition).await?;
 
-        let mut games: Vec<mp_stats_core::models::Game> = id_map
-            .games
-            .into_values()
-            .map(|value| mp_stats_core::models::Game {
-                id: value.name.clone(),
-                name: value.name.clone(),
-                description: value.description.clone(),
-                icon: None,
-                total_snapshots: value.total_snapshots,
-            })
-            .collect();
-
-        games.sort_by(|a, b| a.name.cmp(&b.name));
-
-        Ok(PlatformMeta { games })
+        Ok(PlatformMeta::from_id_map(id_map))
     }
 
     pub async fn fetch_id_map(&self, edition: &PlatformEdition) -> ApiResult<IdMap> {
@@ -249,21 +249,124 @@ impl Api {
             .await
             .map_err(|_| gloo_net::Error::GlooError("Failed to fetch leaderboard".to_string()))?;
 
-        let entries = page
-            .ranks
+        Ok(page.into_entries())
+    }
+
+    /// Where `uuid` sits on a snapshot of a leaderboard, from the snapshot's
+    /// position index; `snapshot_id` is `latest` for the latest snapshot.
+    pub async fn fetch_leaderboard_position(
+        &self,
+        edition: &PlatformEdition,
+        board: &str,
+        game: &str,
+        stat: &str,
+        snapshot_id: &str,
+        uuid: &str,
+    ) -> ApiResult<LeaderboardPosition> {
+        let Ok(shard) = position_shard(uuid) else {
+            return Err(gloo_net::Error::GlooError("Invalid UUID format".into()));
+        };
+
+        let route = if snapshot_id == "latest" {
+            routes::leaderboard_positions_bin(edition, board, game, stat, &shard)
+        } else {
+            routes::history_leaderboard_positions_bin(
+                edition,
+                board,
+                game,
+                stat,
+                snapshot_id,
+                &shard,
+            )
+        };
+        let positions = self
+            .fetch_bin_cached::<HashMap<String, LeaderboardPosition>>(
+                &format!("/data/{route}"),
+                Self::TTL_LEADERBOARD_CHUNK_MS,
+            )
+            .await
+            .map_err(|_| {
+                gloo_net::Error::GlooError("Failed to fetch position index".to_string())
+            })?;
+
+        positions
+            .get(uuid)
+            .copied()
+            .ok_or_else(|| gloo_net::Error::GlooError("Player not on this leaderboard".into()))
+    }
+
+    /// Find a player on a snapshot of a leaderboard by UUID, gamertag or name.
+    ///
+    /// `query` is tried as an id first, then every player of the edition
+    /// carrying it as a name, most recently seen first; the first one on the
+    /// board is answered with their uuid.
+    pub async fn find_on_leaderboard(
+        &self,
+        edition: &PlatformEdition,
+        board: &str,
+        game: &str,
+        stat: &str,
+        snapshot_id: &str,
+        query: &str,
+    ) -> Option<(String, LeaderboardPosition)> {
+        let query = query.trim();
+        if query.is_empty() {
+            return None;
+        }
+        if let Ok(position) = self
+            .fetch_leaderboard_position(edition, board, game, stat, snapshot_id, query)
+            .await
+        {
+            return Some((query.to_string(), position));
+        }
+
+        let named = self.search_players_by_name(query).await.unwrap_or_default();
+        for (_, _, uuid) in named
             .into_iter()
-            .zip(page.uuids)
-            .zip(page.names)
-            .zip(page.scores)
-            .map(|(((rank, uuid), name), score)| LeaderboardEntry {
-                rank,
-                uuid,
-                name,
-                score,
-            })
-            .collect();
+            .filter(|(e, name, _)| e == edition && name.eq_ignore_ascii_case(query))
+        {
+            if let Ok(position) = self
+                .fetch_leaderboard_position(edition, board, game, stat, snapshot_id, &uuid)
+                .await
+            {
+                return Some((uuid, position));
+            }
+        }
+        None
+    }
 
-        Ok(entries)
+    /// The competition rank `score` would have had on `snapshot`, placed
+    /// through its page score ranges; at most one page is fetched.
+    pub async fn rank_for_score(
+        &self,
+        edition: &PlatformEdition,
+        board: &str,
+        game: &str,
+        stat: &str,
+        snapshot: &HistoricalSnapshot,
+        score: u64,
+    ) -> ApiResult<u32> {
+        match place_score(&snapshot.page_scores, score) {
+            ScorePlacement::Rank(rank) => Ok(rank),
+            ScorePlacement::InPage { page, ahead } => {
+                let entries = if snapshot.snapshot_id == "latest" {
+                    self.fetch_leaderboard(edition, board, game, stat, page)
+                        .await?
+                } else {
+                    self.fetch_history_leaderboard(
+                        edition,
+                        board,
+                        game,
+                        stat,
+                        &snapshot.snapshot_id,
+                        page,
+                    )
+                    .await?
+                };
+                let scores: Vec<u64> = entries.iter().map(|e| e.score).collect();
+                Ok(rank_within_page(&scores, ahead, score))
+            }
+        }
     }
 
     pub async fn resolve_names(
@@ -297,9 +400,11 @@ impl Api {
             return Err(gloo_net::Error::GlooError("Invalid UUID format".into()));
         }
 
-        let shard = &uuid[..3].to_uppercase();
+        let Ok(shard) = uuid_shard(uuid) else {
+            return Err(gloo_net::Error::GlooError("Invalid UUID format".into()));
+        };
 
-        let bin_path = format!("/data/{}", routes::player_shard_bin(edition, shard));
+        let bin_path = format!("/data/{}", routes::player_shard_bin(edition, &shard));
         let mut shard_map = self
             .fetch_bin_cached::<HashMap<String, PlayerProfile>>(
                 &bin_path,
@@ -322,69 +427,142 @@ impl Api {
         }
     }
 
+    /// A player's score history, from the history shard next to their profile shard.
+    pub async fn fetch_player_history(
+        &self,
+        edition: &PlatformEdition,
+        uuid: &str,
+    ) -> ApiResult<PlayerHistory> {
+        let Ok(shard) = uuid_shard(uuid) else {
+            return Err(gloo_net::Error::GlooError("Invalid UUID format".into()));
+        };
+
+        let bin_path = format!("/data/{}", routes::player_history_bin(edition, &shard));
+        let mut shard_map = self
+            .fetch_bin_cached::<HashMap<String, PlayerHistory>>(
+                &bin_path,
+                Self::TTL_PLAYER_SHARD_MS,
+            )
+            .await
+            .map_err(|_| gloo_net::Error::GlooError("Failed to fetch player history".into()))?;
+
+        shard_map
+            .remove(uuid)
+            .ok_or_else(|| gloo_net::Error::GlooError("Player history not found in shard".into()))
+    }
+
+    /// Players whose names contain `query`, or come close to it.
+    ///
+    /// Matches through the trigram index: a name is a candidate when it shares
+    /// a trigram with the query, and ranked by [`rank_name_matches`]. Data
+    /// converted before the trigram index existed has none of its files, in
+    /// which case the edition is searched through the prefix index instead.
+    ///
+    /// A query of one or two characters has no trigram; it is matched against
+    /// the short-name index, which holds every name that short.
     pub async fn search_players_by_name(
         &self,
         query: &str,
     ) -> ApiResult<Vec<(PlatformEdition, String, String)>> {
         let name_lower = query.to_lowercase();
-        if name_lower.len() < 3 {
+        if name_lower.is_empty() {
             return Ok(Vec::new());
         }
 
-        let prefix = name_lower.chars().take(3).collect::<String>();
-
-        // Concurrent fetch mapping from cache
-        let java_future = self.get_name_index(&PlatformEdition::Java, &prefix);
-        let bedrock_future = self.get_name_index(&PlatformEdition::Bedrock, &prefix);
+        let (candidates, trigram_count) = if name_lower.chars().count() < raw::MIN_NAME_LENGTH {
+            let (java, bedrock) = futures::future::join(
+                self.short_name_candidates(&PlatformEdition::Java, &name_lower),
+                self.short_name_candidates(&PlatformEdition::Bedrock, &name_lower),
+            )
+            .await;
+            (java.into_iter().chain(bedrock).collect::<Vec<_>>(), 0)
+        } else {
+            let trigrams = spread(name_trigrams(&name_lower), Self::MAX_QUERY_TRIGRAMS);
+            let (java, bedrock) = futures::future::join(
+                self.name_candidates(&PlatformEdition::Java, &name_lower, &trigrams),
+                self.name_candidates(&PlatformEdition::Bedrock, &name_lower, &trigrams),
+            )
+            .await;
+            (java.into_iter().chain(bedrock).collect(), trigrams.len())
+        };
 
-        let (java_res, bedrock_res) = futures::future::join(java_future, bedrock_future).await;
+        let mut results = rank_name_matches(&name_lower, trigram_count, candidates);
+        results.truncate(Self::MAX_SEARCH_RESULTS);
 
-        let mut results = Vec::new();
+        Ok(results
+            .into_iter()
+            .map(|(edition, m)| (edition, m.name.to_string(), m.uuid.to_string()))
+            .collect())
+    }
 
-        if let Some(map) = java_res {
-            for (name, (uuid, has_profile)) in map {
-                // Skip players without a profile so suggestions never lead to an
-                // empty "no profile data" page.
-                if has_profile && name.to_lowercase().contains(&name_lower) {
-                    results.push((PlatformEdition::Java, name, uuid));
-                }
+    /// Every player of `edition` sharing a trigram with the query, with how
+    /// many of `trigrams` their name holds.
+    async fn name_candidates(
+        &self,
+        edition: &PlatformEdition,
+        name_lower: &str,
+        trigrams: &[String],
+    ) -> Vec<(PlatformEdition, NameMatch, usize)> {
+        let files = futures::future::join_all(trigrams.iter().map(|trigram| {
+            let url = format!("/data/{}", routes::name_trigram_bin(edition, trigram));
+            async move {
+                self.fetch_bin_cached::<Vec<NameMatch>>(&url, Self::TTL_NAME_INDEX_MS)
+                    .await
             }
+        }))
+        .await;
+
+        // A trigram no name holds has no file, so one missing file is normal;
+        // all of them missing means the data predates the trigram index.
+        if files.iter().all(Result::is_err) {
+            return self
+                .legacy_name_candidates(edition, name_lower, trigrams.len())
+                .await;
         }
 
-        if let Some(map) = bedrock_res {
-            for (name, (uuid, has_profile)) in map {
-                if has_profile && name.to_lowercase().contains(&name_lower) {
-                    results.push((PlatformEdition::Bedrock, name, uuid));
-                }
-            }
+        let mut hits: HashMap<NameMatch, usize> = HashMap::new();
+        for name_match in files.into_iter().flatten().flatten() {
+            *hits.entry(name_match).or_default() += 1;
         }
 
-        // Sort results: exact match first, then starts with, then contains
-        results.sort_by(|a, b| {
-            let a_name = a.1.to_lowercase();
-            let b_name = b.1.to_lowercase();
-
-            let a_exact = a_name == name_lower;
-            let b_exact = b_name == name_lower;
-
-            if a_exact != b_exact {
-                return b_exact.cmp(&a_exact);
-            }
-
-            let a_starts = a_name.starts_with(&name_lower);
-            let b_starts = b_name.starts_with(&name_lower);
-
-            if a_starts != b_starts {
-                return b_starts.cmp(&a_starts);
-            }
-
-            a_name.cmp(&b_name)
-        });
+        hits.into_iter()
+            .map(|(name_match, count)| (edition.clone(), name_match, count))
+            .collect()
+    }
 
-        // Limit results to top 10
-        results.truncate(10);
+    /// The profiled players of `edition` with a name of one or two characters
+    /// containing the query.
+    async fn short_name_candidates(
+        &self,
+        edition: &PlatformEdition,
+        name_lower: &str,
+    ) -> Vec<(PlatformEdition, NameMatch, usize)> {
+        let url = format!("/data/{}", routes::names_short_bin(edition));
+        let Some(map) = self.get_name_index_file(&url).await else {
+            return Vec::new();
+        };
+
+        profiled_matches(edition, name_lower, map, 0)
+    }
 
-        Ok(results)
+    /// The search as it was before the trigram index: the names sharing the
+    /// query's first three characters that contain it.
+    async fn legacy_name_candidates(
+        &self,
+        edition: &PlatformEdition,
+        name_lower: &str,
+        trigram_count: usize,
+    ) -> Vec<(PlatformEdition, NameMatch, usize)> {
+        // Filed the way the converter files names, which also keeps Bedrock
+        // gamertags with spaces or punctuation in a fetchable file.
+        let Ok(prefix) = name_shard(name_lower) else {
+            return Vec::new();
+        };
+        let Some(map) = self.get_name_index(edition, &prefix).await else {
+            return Vec::new();
+        };
+
+        profiled_matches(edition, name_lower, map, trigram_count)
     }
 
     pub async fn fetch_history_leaderboard(
@@ -408,47 +586,146 @@ impl Api {
                 gloo_net::Error::GlooError("Failed to fetch history leaderboard".to_string())
             })?;
 
-        let entries = page
-            .ranks
-            .into_iter()
-            .zip(page.uuids)
-            .zip(page.names)
-            .zip(page.scores)
-            .map(|(((rank, uuid), name), score)| LeaderboardEntry {
-                rank,
-                uuid,
-                name,
-                score,
-            })
-            .collect();
+        Ok(page.into_entries())
+    }
+}
 
-        Ok(entries)
+/// The players in a names index file whose name contains the query, as
+/// search candidates holding `hits` trigrams. Every player carrying a name is
+/// a candidate of its own, most recently seen first.
+fn profiled_matches(
+    edition: &PlatformEdition,
+    name_lower: &str,
+    index: HashMap<String, Vec<NameCandidate>>,
+    hits: usize,
+) -> Vec<(PlatformEdition, NameMatch, usize)> {
+    index
+        .into_iter()
+        .filter(|(name, _)| name.to_lowercase().contains(name_lower))
+        .flat_map(|(name, candidates)| {
+            candidates
+                .into_iter()
+                // Skip players without a profile so suggestions never lead to
+                // an empty "no profile data" page.
+                .filter(|candidate| candidate.has_profile)
+                .map(move |candidate| NameMatch {
+                    name: name.as_str().into(),
+                    uuid: candidate.uuid,
+                })
+        })
+        .map(|name_match| (edition.clone(), name_match, hits))
+        .collect()
+}
+
+/// At most `max` of `items`, evenly spread from the first to the last.
+fn spread(items: Vec<String>, max: usize) -> Vec<String> {
+    if items.len() <= max || max < 2 {
+        return items.into_iter().take(max.max(1)).collect();
     }
+
+    let last = items.len() - 1;
+    (0..max)
+        .map(|i| items[i * last / (max - 1)].clone())
+        .collect()
+}
+
+/// Order search candidates best first.
+///
+/// Names equal to the query come first, then names starting with it, then
+/// names containing it; each group alphabetically. What is left is a typo
+/// match when the name holds at least half of the query's `trigram_count`
+/// trigrams, and is ranked by how many it holds, then by how close its length
+/// is to the query's. Anything else is dropped.
+fn rank_name_matches(
+    name_lower: &str,
+    trigram_count: usize,
+    candidates: impl IntoIterator<Item = (PlatformEdition, NameMatch, usize)>,
+) -> Vec<(PlatformEdition, NameMatch)> {
+    let query_len = name_lower.chars().count();
+
+    let mut ranked: Vec<_> = candidates
+        .into_iter()
+        .filter_map(|(edition, name_match, hits)| {
+            let name = name_match.name.to_lowercase();
+            let tier = if name == name_lower {
+                0
+            } else if name.starts_with(name_lower) {
+                1
+            } else if name.contains(name_lower) {
+                2
+            } else if hits * 2 >= trigram_count && trigram_count > 1 {
+                3
+            } else {
+                return None;
+            };
+            let len_gap = name.chars().count().abs_diff(query_len);
+            Some((tier, hits, len_gap, name, edition, name_match))
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        a.0.cmp(&b.0).then_with(|| {
+            if a.0 == 3 {
+                b.1.cmp(&a.1)
+                    .then(a.2.cmp(&b.2))
+                    .then_with(|| a.3.cmp(&b.3))
+            } else {
+                a.3.cmp(&b.3)
+            }
+        })
+    });
+
+    ranked
+        .into_iter()
+        .map(|(_, _, _, _, edition, name_match)| (edition, name_match))
+        .collect()
 }
 
-/// Decode a (decompressed) names-index payload into `name -> (uuid, has_profile)`.
+/// Decode a (decompressed) names-index payload into `name -> [NameCandidate]`.
+///
+/// The current data layout stores every player carrying a name. Data produced
+/// by older converters kept one player per name, as `name -> (uuid,
+/// has_profile)` or, before the `has_profile` flag was introduced, as
+/// `name -> uuid`. We try the current layout first and fall back to the older
+/// ones (treating `name -> uuid` entries as having a profile) so player search
+/// keeps returning results even when the served data has not yet been
+/// regenerated. Older entries carry no `last_seen` and get 0.
 ///
-/// The current data layout stores `name -> (uuid, has_profile)`, but data
-/// produced before the `has_profile` flag was introduced stored `name -> uuid`.
-/// We first try the current layout and fall back to the legacy one (treating
-/// legacy entries as having a profile) so player search keeps returning results
-/// even when the served data has not yet been regenerated.
-fn decode_name_index(bytes: &[u8]) -> Option<HashMap<String, (String, bool)>> {
+/// postcard is not self-describing, so a payload only counts as one layout
+/// when decoding it as that layout uses every byte.
+fn decode_name_index(bytes: &[u8]) -> Option<HashMap<String, Vec<NameCandidate>>> {
     if bytes.is_empty() {
         return None;
     }
 
-    // Current layout: name -> (uuid, has_profile).
-    if let Ok(map) = postcard::from_bytes::<HashMap<String, (String, bool)>>(bytes) {
+    // Current layout: name -> [NameCandidate].
+    if let Some(map) = decode_whole::<HashMap<String, Vec<NameCandidate>>>(bytes) {
         return Some(map);
     }
 
+    let single = |uuid: String, has_profile: bool| {
+        vec![NameCandidate {
+            uuid: uuid.into(),
+            has_profile,
+            last_seen: 0,
+        }]
+    };
+
+    // Previous layout: name -> (uuid, has_profile).
+    if let Some(map) = decode_whole::<HashMap<String, (String, bool)>>(bytes) {
+        return Some(
+            map.into_iter()
+                .map(|(name, (uuid, has_profile))| (name, single(uuid, has_profile)))
+                .collect(),
+        );
+    }
+
     // Legacy layout: name -> uuid (no has_profile flag).
-    if let Ok(legacy) = postcard::from_bytes::<HashMap<String, String>>(bytes) {
+    if let Some(legacy) = decode_whole::<HashMap<String, String>>(bytes) {
         return Some(
             legacy
                 .into_iter()
-                .map(|(name, uuid)| (name, (uuid, true)))
+                .map(|(name, uuid)| (name, single(uuid, true)))
                 .collect(),
         );
     }
@@ -456,20 +733,54 @@ fn decode_name_index(bytes: &[u8]) -> Option<HashMap<String, (String, bool)>> {
     None
 }
 
+/// `bytes` decoded as a `T`, if that takes all of them.
+fn decode_whole<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+    match postcard::take_from_bytes(bytes) {
+        Ok((value, [])) => Some(value),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn name_candidate(uuid: &str, has_profile: bool, last_seen: u64) -> NameCandidate {
+        NameCandidate {
+            uuid: uuid.into(),
+            has_profile,
+            last_seen,
+        }
+    }
+
     #[test]
-    fn decodes_current_layout_preserving_has_profile() {
+    fn decodes_current_layout_keeping_every_player() {
+        let mut index: HashMap<String, Vec<NameCandidate>> = HashMap::new();
+        index.insert(
+            "relyh".into(),
+            vec![
+                name_candidate("68b61e3c", true, 200),
+                name_candidate("0a1b2c3d", true, 100),
+            ],
+        );
+        index.insert("ghost".into(), vec![name_candidate("deadbeef", false, 0)]);
+        let bytes = postcard::to_stdvec(&index).unwrap();
+
+        let decoded = decode_name_index(&bytes).expect("current layout should decode");
+        assert_eq!(decoded, index);
+    }
+
+    #[test]
+    fn decodes_previous_layout_preserving_has_profile() {
+        // Previous data: name -> (uuid, has_profile), one player per name.
         let mut index: HashMap<String, (String, bool)> = HashMap::new();
         index.insert("relyh".into(), ("68b61e3c".into(), true));
         index.insert("ghost".into(), ("deadbeef".into(), false));
         let bytes = postcard::to_stdvec(&index).unwrap();
 
-        let decoded = decode_name_index(&bytes).expect("current layout should decode");
-        assert_eq!(decoded.get("relyh"), Some(&("68b61e3c".into(), true)));
-        assert_eq!(decoded.get("ghost"), Some(&("deadbeef".into(), false)));
+        let decoded = decode_name_index(&bytes).expect("previous layout should decode");
+        assert_eq!(decoded["relyh"], [name_candidate("68b61e3c", true, 0)]);
+        assert_eq!(decoded["ghost"], [name_candidate("deadbeef", false, 0)]);
     }
 
     #[test]
@@ -487,15 +798,19 @@ mod tests {
         assert_eq!(decoded.len(), 2);
         // Legacy entries must be treated as having a profile so they stay
         // searchable.
-        for (_name, (_uuid, has_profile)) in &decoded {
+        for candidates in decoded.values() {
             assert!(
-                has_profile,
+                candidates.iter().all(|c| c.has_profile),
                 "legacy entries must be marked has_profile=true"
             );
         }
         assert_eq!(
-            decoded.get("relyh").map(|(u, _)| u.as_str()),
-            Some("68b61e3c-4be0-4c0c-8897-6a8d3703fe9a")
+            decoded["relyh"],
+            [name_candidate(
+                "68b61e3c-4be0-4c0c-8897-6a8d3703fe9a",
+                true,
+                0
+            )]
         );
     }
 
@@ -503,4 +818,86 @@ mod tests {
     fn empty_payload_returns_none() {
         assert!(decode_name_index(&[]).is_none());
     }
+
+    fn candidate(name: &str, hits: usize) -> (PlatformEdition, NameMatch, usize) {
+        let name_match = NameMatch {
+            name: name.into(),
+            uuid: format!("uuid-{name}").into(),
+        };
+        (PlatformEdition::Java, name_match, hits)
+    }
+
+    #[test]
+    fn substring_matches_rank_above_typos() {
+        // "steve" has the trigrams ste, tev, eve.
+        let ranked = rank_name_matches(
+            "steve",
+            3,
+            [
+                candidate("xXSteve", 3),
+                candidate("Steven", 3),
+                candidate("steve", 3),
+                candidate("Steev", 1),
+                candidate("steeve", 2),
+                candidate("Stan", 1),
+            ],
+        );
+        let names: Vec<_> = ranked.iter().map(|(_, m)| m.name.as_str()).collect();
+
+        assert_eq!(names, ["steve", "Steven", "xXSteve", "steeve"]);
+    }
+
+    #[test]
+    fn one_and_two_character_names_are_found() {
+        let mut index: HashMap<String, Vec<NameCandidate>> = HashMap::new();
+        index.insert("Al".into(), vec![name_candidate("uuid-al", true, 0)]);
+        index.insert("Jo".into(), vec![name_candidate("uuid-jo", true, 0)]);
+        index.insert("J".into(), vec![name_candidate("uuid-j", true, 0)]);
+        index.insert("Lo".into(), vec![name_candidate("uuid-lo", false, 0)]);
+
+        let edition = PlatformEdition::Java;
+        let ranked = rank_name_matches("j", 0, profiled_matches(&edition, "j", index.clone(), 0));
+        let names: Vec<_> = ranked.iter().map(|(_, m)| m.name.as_str()).collect();
+        assert_eq!(names, ["J", "Jo"]);
+
+        // "Lo" has no profile, so only "Al" holds an "l" worth suggesting.
+        let ranked = rank_name_matches("l", 0, profiled_matches(&edition, "l", index, 0));
+        let names: Vec<_> = ranked.iter().map(|(_, m)| m.name.as_str()).collect();
+        assert_eq!(names, ["Al"]);
+    }
+
+    #[test]
+    fn players_sharing_a_name_are_all_suggested() {
+        let mut index: HashMap<String, Vec<NameCandidate>> = HashMap::new();
+        index.insert(
+            "Steve".into(),
+            vec![
+                name_candidate("uuid-new", true, 200),
+                name_candidate("uuid-gone", false, 150),
+                name_candidate("uuid-old", true, 100),
+            ],
+        );
+        index.insert(
+            "Stevie".into(),
+            vec![name_candidate("uuid-stevie", true, 0)],
+        );
+
+        let edition = PlatformEdition::Java;
+        let ranked = rank_name_matches("steve", 3, profiled_matches(&edition, "steve", index, 3));
+        let uuids: Vec<_> = ranked.iter().map(|(_, m)| m.uuid.as_str()).collect();
+
+        // The most recently seen Steve first; the one without a profile is left out.
+        assert_eq!(uuids, ["uuid-new", "uuid-old"]);
+    }
+
+    #[test]
+    fn a_long_query_is_sampled_end_to_end() {
+        let trigrams = name_trigrams("abcdefghij");
+        let sampled = spread(trigrams.clone(), 3);
+
+        assert_eq!(sampled.len(), 3);
+        assert_eq!(sampled.first(), trigrams.first());
+        assert_eq!(sampled.last(), trigrams.last());
+        assert_eq!(spread(trigrams[..2].to_vec(), 3).len(), 2);
+    }
 }
@@ -33,9 +33,22 @@ fn switch(routes: Route) -> Html {
         } => html! {
             <pages::java::LeaderboardView edition={edition} game={game} board={board} stat={stat} page={page}  />
         },
+        Route::LeaderboardDiff {
+            edition,
+            game,
+            board,
+            stat,
+            from,
+            to,
+        } => html! {
+            <pages::java::LeaderboardDiffView edition={edition} game={game} board={board} stat={stat} from={from} to={to} />
+        },
         Route::Player { edition, uuid } => html! {
             <pages::java::PlayerView edition={edition} uuid={uuid} />
         },
+        Route::Compare { edition, uuids } => html! {
+            <pages::java::CompareView edition={edition} uuids={uuids} />
+        },
         Route::NotFound => html! { <NotFound /> },
     }
 }
@@ -31,9 +31,15 @@ pub fn header() -> Html {
                         ) || matches!(
                             &route,
                             Route::Leaderboard { edition: current, .. } if current == edition
+                        ) || matches!(
+                            &route,
+                            Route::LeaderboardDiff { edition: current, .. } if current == edition
                         ) || matches!(
                             &route,
                             Route::Player { edition: current, .. } if current == edition
+                        ) || matches!(
+                            &route,
+                            Route::Compare { edition: current, .. } if current == edition
                         );
                         html! {
                             <Link<Route>
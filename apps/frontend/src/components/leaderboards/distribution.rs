@@ -0,0 +1,86 @@
+use crate::hooks::use_theme;
+use crate::util::score_formatter::create_score_formatter;
+use mp_stats_core::models::ScoreDistribution;
+use yew::prelude::*;
+
+/// Chart area in SVG user units; the SVG itself stretches to its box.
+const WIDTH: f64 = 480.0;
+const HEIGHT: f64 = 96.0;
+const GAP: f64 = 2.0;
+
+#[derive(Properties, PartialEq)]
+pub struct DistributionChartProps {
+    pub game: String,
+    pub stat: String,
+    pub distribution: ScoreDistribution,
+}
+
+/// How the scores of the snapshot shown are spread: a bar per score band,
+/// lowest scores on the left, with the percentiles underneath.
+///
+/// Bar heights follow the square root of the row count, so the few rows in the
+/// high bands stay visible next to the crowded low end.
+#[function_component(DistributionChart)]
+pub fn distribution_chart(props: &DistributionChartProps) -> Html {
+    let theme_color = use_theme();
+    let spread = &props.distribution;
+    let score_formatter = create_score_formatter(&props.game, &props.stat);
+
+    let tallest = spread
+        .buckets
+        .iter()
+        .map(|b| b.count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let width = WIDTH / spread.buckets.len().max(1) as f64;
+
+    let bars = spread.buckets.iter().enumerate().map(|(i, bucket)| {
+        let height = (f64::from(bucket.count) / f64::from(tallest)).sqrt() * HEIGHT;
+        let label = if bucket.min == bucket.max {
+            score_formatter.format_score(bucket.min)
+        } else {
+            format!(
+                "{}–{}",
+                score_formatter.format_score(bucket.min),
+                score_formatter.format_score(bucket.max)
+            )
+        };
+        html! {
+            <rect
+                x={format!("{:.1}", i as f64 * width + GAP / 2.0)}
+                y={format!("{:.1}", HEIGHT - height)}
+                width={format!("{:.1}", (width - GAP).max(1.0))}
+                height={format!("{height:.1}")}
+                fill="var(--color-theme-500)"
+            >
+                <title>{ format!("{label}: {} players", bucket.count) }</title>
+            </rect>
+        }
+    });
+
+    let figure = |name: &'static str, value: String| {
+        html! {
+            <div class="flex flex-col">
+                <span class="eyebrow">{ name }</span>
+                <span class="font-mono tnum text-xs text-paper-1">{ value }</span>
+            </div>
+        }
+    };
+
+    html! {
+        <div class={classes!(theme_color, "card", "p-4", "w-full", "md:max-w-md")}>
+            <div class="eyebrow mb-2">{ "Score distribution" }</div>
+            <svg viewBox={format!("0 0 {WIDTH} {HEIGHT}")} preserveAspectRatio="none" class="block w-full h-24">
+                { for bars }
+            </svg>
+            <div class="flex flex-wrap justify-between gap-3 mt-3">
+                { figure("Mean", score_formatter.format_score(spread.mean.round() as u64)) }
+                { figure("Median", score_formatter.format_score(spread.p50)) }
+                { figure("Top 10%", score_formatter.format_score(spread.p90)) }
+                { figure("Top 1%", score_formatter.format_score(spread.p99)) }
+                { figure("Tied", spread.ties.to_string()) }
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,100 @@
+use crate::Api;
+use crate::hooks::use_theme;
+use mp_stats_core::models::{LeaderboardPosition, PlatformEdition};
+use web_sys::HtmlInputElement;
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct FindPlayerProps {
+    pub edition: PlatformEdition,
+    pub game: String,
+    pub board: String,
+    pub stat: String,
+    /// The snapshot shown, or `latest`.
+    pub snapshot: String,
+    /// Called with the uuid and position of the player found.
+    pub on_found: Callback<(String, LeaderboardPosition)>,
+}
+
+#[derive(Clone, PartialEq)]
+enum Lookup {
+    Idle,
+    Searching,
+    NotFound(String),
+}
+
+/// Look a player up by UUID, gamertag or name and jump to their row.
+#[function_component(FindPlayer)]
+pub fn find_player(props: &FindPlayerProps) -> Html {
+    let theme_color = use_theme();
+    let input_ref = use_node_ref();
+    let lookup = use_state(|| Lookup::Idle);
+    let context = use_context::<Api>().expect("no api context found");
+
+    let onsubmit = {
+        let input_ref = input_ref.clone();
+        let lookup = lookup.clone();
+        let on_found = props.on_found.clone();
+        let (edition, game, board, stat, snapshot) = (
+            props.edition.clone(),
+            props.game.clone(),
+            props.board.clone(),
+            props.stat.clone(),
+            props.snapshot.clone(),
+        );
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let Some(input) = input_ref.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let query = input.value().trim().to_string();
+            if query.is_empty() {
+                return;
+            }
+
+            lookup.set(Lookup::Searching);
+            let provider = context.clone();
+            let lookup = lookup.clone();
+            let on_found = on_found.clone();
+            let (edition, game, board, stat, snapshot) = (
+                edition.clone(),
+                game.clone(),
+                board.clone(),
+                stat.clone(),
+                snapshot.clone(),
+            );
+            spawn_local(async move {
+                match provider
+                    .find_on_leaderboard(&edition, &board, &game, &stat, &snapshot, &query)
+                    .await
+                {
+                    Some(found) => {
+                        lookup.set(Lookup::Idle);
+                        on_found.emit(found);
+                    }
+                    None => lookup.set(Lookup::NotFound(query)),
+                }
+            });
+        })
+    };
+
+    html! {
+        <form {onsubmit} class={classes!(theme_color, "flex", "items-center", "gap-2")}>
+            <input
+                ref={input_ref}
+                type="text"
+                placeholder="Find a player by UUID or name"
+                class="input-text font-mono py-1.5 px-3 text-xs rounded-md w-56"
+            />
+            <button type="submit" class="btn-ghost text-xs font-mono" disabled={*lookup == Lookup::Searching}>
+                { if *lookup == Lookup::Searching { "Finding…" } else { "Find" } }
+            </button>
+            if let Lookup::NotFound(query) = &*lookup {
+                <span class="font-mono text-[11px] text-paper-3">
+                    { format!("{query} is not on this leaderboard") }
+                </span>
+            }
+        </form>
+    }
+}
@@ -1,6 +1,7 @@
 use crate::Route;
+use crate::components::leaderboards::distribution::DistributionChart;
 use crate::hooks::use_theme;
-use mp_stats_core::models::PlatformEdition;
+use mp_stats_core::models::{PlatformEdition, ScoreDistribution};
 use yew::prelude::*;
 use yew_router::prelude::*;
 
@@ -9,6 +10,9 @@ pub struct HeaderProps {
     pub edition: PlatformEdition,
     pub game: String,
     pub stat: String,
+    /// The score distribution of the snapshot shown, when it has one.
+    #[prop_or_default]
+    pub distribution: Option<ScoreDistribution>,
 }
 
 #[function_component(LeaderboardHeader)]
@@ -38,6 +42,13 @@ pub fn leaderboard_header(props: &HeaderProps) -> Html {
                         { stat_display }
                     </h1>
                 </div>
+                if let Some(distribution) = &props.distribution {
+                    <DistributionChart
+                        game={props.game.clone()}
+                        stat={props.stat.clone()}
+                        distribution={distribution.clone()}
+                    />
+                }
             </div>
         </div>
     }
@@ -13,6 +13,9 @@ pub struct LeaderboardTableProps {
     pub stat: String,
     pub entries: Vec<LeaderboardEntry>,
     pub edition: PlatformEdition,
+    /// The uuid of a row to scroll to and highlight.
+    #[prop_or_default]
+    pub highlight: Option<String>,
 }
 
 #[function_component(LeaderboardTable)]
@@ -23,6 +26,18 @@ pub fn leaderboard_table(props: &LeaderboardTableProps) -> Html {
     // Top score for "vs. #1" bar
     let top_score = props.entries.first().map(|e| e.score).unwrap_or(0);
 
+    // Bring the highlighted row into view once it is rendered.
+    let highlighted_row = use_node_ref();
+    {
+        let highlighted_row = highlighted_row.clone();
+        use_effect_with((props.highlight.clone(), props.entries.len()), move |_| {
+            if let Some(row) = highlighted_row.cast::<web_sys::Element>() {
+                row.scroll_into_view_with_bool(false);
+            }
+            || ()
+        });
+    }
+
     html! {
         <div class={classes!(theme_color, "overflow-x-auto")}>
             <table class="w-full text-left border-collapse">
@@ -51,8 +66,16 @@ pub fn leaderboard_table(props: &LeaderboardTableProps) -> Html {
                             "font-mono text-sm text-paper-3"
                         };
 
+                        let is_highlighted = props.highlight.as_deref() == Some(row.uuid.as_str());
+                        let row_ref = if is_highlighted { highlighted_row.clone() } else { NodeRef::default() };
+                        let row_class = if is_highlighted {
+                            classes!("table-row", "group", "bg-theme-500/10", "outline", "outline-1", "outline-theme-500/60")
+                        } else {
+                            classes!("table-row", "group")
+                        };
+
                         html! {
-                        <tr class="table-row group">
+                        <tr ref={row_ref} class={row_class}>
                             <td class="table-cell">
                                 <span class={rank_class}>
                                     { format!("#{}", row.rank) }
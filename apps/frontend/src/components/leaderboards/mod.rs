@@ -1,5 +1,8 @@
 pub mod board_type_selector;
+pub mod distribution;
+pub mod find_player;
 pub mod header;
 pub mod leaderboard_table;
 pub mod pagination_controls;
+pub mod score_rank;
 pub mod snapshot_selector;
@@ -0,0 +1,125 @@
+use crate::Api;
+use crate::hooks::use_theme;
+use mp_stats_core::HistoricalSnapshot;
+use mp_stats_core::models::PlatformEdition;
+use web_sys::HtmlInputElement;
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct ScoreRankProps {
+    pub edition: PlatformEdition,
+    pub game: String,
+    pub board: String,
+    pub stat: String,
+    /// The snapshot shown; the widget is hidden until it has loaded.
+    pub snapshot: Option<HistoricalSnapshot>,
+}
+
+#[derive(Clone, PartialEq)]
+enum Placement {
+    Idle,
+    Placing,
+    Placed { score: u64, rank: u32 },
+    Failed(String),
+}
+
+/// Where an arbitrary score would have ranked on the snapshot shown.
+#[function_component(ScoreRank)]
+pub fn score_rank(props: &ScoreRankProps) -> Html {
+    let theme_color = use_theme();
+    let input_ref = use_node_ref();
+    let placement = use_state(|| Placement::Idle);
+    let context = use_context::<Api>().expect("no api context found");
+
+    {
+        // A result for another snapshot or board would be misleading.
+        let placement = placement.clone();
+        use_effect_with(
+            (
+                props.board.clone(),
+                props.stat.clone(),
+                props.snapshot.clone(),
+            ),
+            move |_| {
+                placement.set(Placement::Idle);
+                || ()
+            },
+        );
+    }
+
+    let Some(snapshot) = props.snapshot.clone() else {
+        return html! {};
+    };
+    // Snapshots converted before score ranges were recorded cannot be placed.
+    if snapshot.page_scores.is_empty() && snapshot.total_entries > 0 {
+        return html! {};
+    }
+
+    let onsubmit = {
+        let input_ref = input_ref.clone();
+        let placement = placement.clone();
+        let (edition, game, board, stat) = (
+            props.edition.clone(),
+            props.game.clone(),
+            props.board.clone(),
+            props.stat.clone(),
+        );
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let Some(input) = input_ref.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let Ok(score) = input.value().trim().parse::<u64>() else {
+                placement.set(Placement::Failed(
+                    "Enter a whole, non-negative score".into(),
+                ));
+                return;
+            };
+
+            placement.set(Placement::Placing);
+            let provider = context.clone();
+            let placement = placement.clone();
+            let snapshot = snapshot.clone();
+            let (edition, game, board, stat) =
+                (edition.clone(), game.clone(), board.clone(), stat.clone());
+            spawn_local(async move {
+                match provider
+                    .rank_for_score(&edition, &board, &game, &stat, &snapshot, score)
+                    .await
+                {
+                    Ok(rank) => placement.set(Placement::Placed { score, rank }),
+                    Err(e) => placement.set(Placement::Failed(e.to_string())),
+                }
+            });
+        })
+    };
+
+    html! {
+        <form {onsubmit} class={classes!(theme_color, "flex", "items-center", "gap-2")}>
+            <input
+                ref={input_ref}
+                type="number"
+                min="0"
+                placeholder="Rank a score"
+                class="input-text font-mono py-1.5 px-3 text-xs rounded-md w-32"
+            />
+            <button type="submit" class="btn-ghost text-xs font-mono" disabled={*placement == Placement::Placing}>
+                { if *placement == Placement::Placing { "Ranking…" } else { "Rank" } }
+            </button>
+            {
+                match &*placement {
+                    Placement::Placed { score, rank } => html! {
+                        <span class="font-mono text-[11px] text-paper-2">
+                            { format!("{score} would rank #{rank}") }
+                        </span>
+                    },
+                    Placement::Failed(message) => html! {
+                        <span class="font-mono text-[11px] text-paper-3">{ message.clone() }</span>
+                    },
+                    Placement::Idle | Placement::Placing => html! {},
+                }
+            }
+        </form>
+    }
+}
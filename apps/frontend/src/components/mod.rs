@@ -4,4 +4,5 @@ pub mod header;
 pub mod leaderboard;
 pub mod leaderboards;
 pub mod player_card;
+pub mod player_history;
 pub mod search_bar;
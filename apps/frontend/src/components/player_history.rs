@@ -0,0 +1,173 @@
+use crate::hooks::use_theme;
+use crate::util::score_formatter::create_score_formatter;
+use mp_stats_core::models::{HistoryPoint, PlayerHistory};
+use web_sys::js_sys::Date;
+use web_sys::js_sys::Intl::DateTimeFormatOptions;
+use web_sys::wasm_bindgen::JsValue;
+use yew::prelude::*;
+
+/// Chart area in SVG user units; the SVG itself stretches to the card width.
+const WIDTH: f64 = 1180.0;
+const HEIGHT: f64 = 160.0;
+const PAD: f64 = 8.0;
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct PlayerHistoryChartProps {
+    pub history: PlayerHistory,
+}
+
+/// How a player's score and position on one stat moved across snapshots.
+///
+/// One stat at a time, picked from a dropdown; the score and the rank get a
+/// chart each, the rank one drawn upside down so that climbing the leaderboard
+/// reads as going up.
+#[function_component(PlayerHistoryChart)]
+pub fn player_history_chart(props: &PlayerHistoryChartProps) -> Html {
+    let theme_color = use_theme();
+
+    // Only stats with at least two points have a trend to show.
+    let mut series: Vec<_> = props
+        .history
+        .stats
+        .iter()
+        .filter(|s| s.points.len() > 1)
+        .collect();
+    series.sort_by(|a, b| (&a.game, &a.stat).cmp(&(&b.game, &b.stat)));
+
+    let selected = use_state(|| 0usize);
+
+    if series.is_empty() {
+        return html! {};
+    }
+
+    let index = (*selected).min(series.len() - 1);
+    let stat = series[index];
+
+    let onchange = {
+        let selected = selected.clone();
+        Callback::from(move |e: Event| {
+            let target: web_sys::HtmlSelectElement = e.target_unchecked_into();
+            selected.set(target.value().parse().unwrap_or(0));
+        })
+    };
+
+    let locale = web_sys::window()
+        .map(|w| w.navigator())
+        .and_then(|n| n.language())
+        .unwrap_or_else(|| "en-US".to_string());
+    let date_formats = DateTimeFormatOptions::new();
+    let fmt_date = |ts: u64| -> String {
+        let ms = (ts * 1000) as f64;
+        let d = Date::new(&JsValue::from_f64(ms));
+        d.to_locale_date_string(&locale, &date_formats).into()
+    };
+
+    let score_formatter = create_score_formatter(&stat.game.to_string(), &stat.stat.to_string());
+
+    let first = stat.points.first().map(|p| p.timestamp).unwrap_or(0);
+    let last = stat.points.last().map(|p| p.timestamp).unwrap_or(0);
+
+    let scores: Vec<f64> = stat.points.iter().map(|p| p.score as f64).collect();
+    // Negated so the best (lowest) rank ends up at the top of the chart.
+    let ranks: Vec<f64> = stat.points.iter().map(|p| -(p.rank as f64)).collect();
+
+    let best_rank = stat.points.iter().map(|p| p.rank).min().unwrap_or(0);
+    let latest = stat.points.last();
+
+    html! {
+        <div class={classes!(theme_color, "card", "p-5", "mt-7")}>
+            <div class="flex items-baseline justify-between mb-4 gap-3 flex-wrap">
+                <div class="eyebrow">
+                    { format!("History · {} snapshots", stat.points.len()) }
+                </div>
+                <div class="relative max-w-xs w-full">
+                    <select
+                        {onchange}
+                        class={classes!(theme_color, "appearance-none", "w-full", "px-3", "py-2", "pr-9", "bg-ink-2", "border", "border-rule", "rounded-md", "text-sm", "font-mono", "text-paper-1", "cursor-pointer", "focus:outline-none", "focus:border-theme-500/60", "transition-colors")}
+                    >
+                        { for series.iter().enumerate().map(|(i, s)| html! {
+                            <option value={i.to_string()} selected={i == index}>
+                                { format!("{} · {}", s.game, s.stat) }
+                            </option>
+                        }) }
+                    </select>
+                    <div class="pointer-events-none absolute inset-y-0 right-0 flex items-center px-3 text-paper-4">
+                        <svg class="h-3.5 w-3.5" fill="none" stroke="currentColor" viewBox="0 0 24 24" stroke-width="2">
+                            <path stroke-linecap="round" stroke-linejoin="round" d="M19 9l-7 7-7-7"/>
+                        </svg>
+                    </div>
+                </div>
+            </div>
+
+            <div class="grid grid-cols-1 lg:grid-cols-2 gap-6">
+                <div>
+                    <div class="flex items-baseline justify-between mb-2">
+                        <span class="text-xs text-paper-3">{ "Score" }</span>
+                        <span class="font-mono tnum text-xs text-paper-1">
+                            { latest.map(|p| score_formatter.format_score(p.score)).unwrap_or_default() }
+                        </span>
+                    </div>
+                    { line_chart(&stat.points, &scores) }
+                </div>
+                <div>
+                    <div class="flex items-baseline justify-between mb-2">
+                        <span class="text-xs text-paper-3">{ "Position" }</span>
+                        <span class="font-mono tnum text-xs text-paper-1">
+                            { latest.map(|p| format!("#{}", p.rank)).unwrap_or_default() }
+                            <span class="text-paper-3">{ format!(" · best #{best_rank}") }</span>
+                        </span>
+                    </div>
+                    { line_chart(&stat.points, &ranks) }
+                </div>
+            </div>
+
+            <div class="flex justify-between mt-2 font-mono text-[11px] text-paper-3">
+                <span>{ fmt_date(first) }</span>
+                <span class="text-theme-500">{ fmt_date(last) }</span>
+            </div>
+        </div>
+    }
+}
+
+/// A polyline of `values` placed by each point's timestamp, scaled to fill the
+/// chart vertically. Larger values are drawn higher.
+fn line_chart(points: &[HistoryPoint], values: &[f64]) -> Html {
+    let min_ts = points.first().map(|p| p.timestamp).unwrap_or(0);
+    let max_ts = points.last().map(|p| p.timestamp).unwrap_or(0);
+    let ts_range = (max_ts - min_ts).max(1) as f64;
+
+    let min_v = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max_v = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let v_range = (max_v - min_v).max(1.0);
+
+    let coords: Vec<(f64, f64)> = points
+        .iter()
+        .zip(values)
+        .map(|(p, v)| {
+            let x = PAD + (p.timestamp - min_ts) as f64 / ts_range * (WIDTH - 2.0 * PAD);
+            let y = PAD + (1.0 - (v - min_v) / v_range) * (HEIGHT - 2.0 * PAD);
+            (x, y)
+        })
+        .collect();
+
+    let line = coords
+        .iter()
+        .map(|(x, y)| format!("{x:.1},{y:.1}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    html! {
+        <svg viewBox={format!("0 0 {WIDTH} {HEIGHT}")} preserveAspectRatio="none" class="block w-full h-40 rounded-md bg-ink-2 border border-rule">
+            <polyline
+                points={line}
+                fill="none"
+                stroke="var(--color-theme-500)"
+                stroke-width="2"
+                vector-effect="non-scaling-stroke"
+            />
+            if let Some((x, y)) = coords.last() {
+                <circle cx={format!("{x:.1}")} cy={format!("{y:.1}")} r="4" fill="var(--color-theme-500)"/>
+            }
+        </svg>
+    }
+}
@@ -2,6 +2,7 @@ use crate::hooks::use_theme;
 use crate::{Api, Route};
 use mp_stats_core::models::PlatformEdition;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use web_sys::{HtmlInputElement, KeyboardEvent, MouseEvent};
 use yew::prelude::*;
@@ -55,6 +56,17 @@ struct DropdownProps {
 
 #[function_component(SearchDropdown)]
 fn search_dropdown(props: &DropdownProps) -> Html {
+    // Players sharing a name within an edition are told apart by UUID, so
+    // theirs is shown whole rather than cut short.
+    let mut name_counts: HashMap<(&PlatformEdition, String), usize> = HashMap::new();
+    for suggestion in &props.suggestions {
+        if let Suggestion::Player(edition, name, _) = suggestion {
+            *name_counts
+                .entry((edition, name.to_lowercase()))
+                .or_default() += 1;
+        }
+    }
+
     html! {
         <div class="absolute mt-2 w-full card overflow-hidden z-50 shadow-2xl">
             { for props.suggestions.iter().enumerate().map(|(index, suggestion)| {
@@ -76,12 +88,17 @@ fn search_dropdown(props: &DropdownProps) -> Html {
                             PlatformEdition::Java => "chip chip-mint",
                             PlatformEdition::Bedrock => "chip chip-azure",
                         };
-                        let short_uuid = if uuid.len() > 8 { &uuid[..8] } else { uuid.as_str() };
+                        let shared = name_counts.get(&(edition, name.to_lowercase())).is_some_and(|&n| n > 1);
+                        // Bedrock ids are gamertags, so cut on characters, not bytes.
+                        let shown_uuid = match uuid.char_indices().nth(8) {
+                            Some((i, _)) if !shared => format!("{}…", &uuid[..i]),
+                            _ => uuid.clone(),
+                        };
                         html! {
                             <div {onmousedown} class={classes!("px-4", "py-2.5", "cursor-pointer", "flex", "items-center", "justify-between", "gap-3", "transition-colors", bg_class)}>
                                 <div class="flex items-center gap-3 min-w-0">
                                     <span class="text-paper-1 text-sm font-medium truncate">{name}</span>
-                                    <span class="font-mono text-xs text-paper-3">{short_uuid}{"…"}</span>
+                                    <span class="font-mono text-xs text-paper-3 truncate">{shown_uuid}</span>
                                 </div>
                                 <span class={badge_class}>{ edition.display_name() }</span>
                             </div>
@@ -148,7 +165,7 @@ pub fn search_bar(props: &SearchBarProps) -> Html {
                 return;
             }
 
-            if val_len >= 3 {
+            if !val.trim().is_empty() {
                 let q = val.clone();
                 let suggestions = suggestions.clone();
                 let show_dropdown = show_dropdown.clone();
@@ -1,12 +1,24 @@
+pub mod use_board_sizes;
+pub use use_board_sizes::use_board_sizes;
+
 pub mod use_game_leaderboards;
 pub use use_game_leaderboards::use_game_leaderboards;
 
 pub mod use_leaderboard_entries;
 pub use use_leaderboard_entries::use_leaderboard_entries;
 
+pub mod use_player_history;
+pub use use_player_history::use_player_history;
+
 pub mod use_player_profile;
 pub use use_player_profile::use_player_profile;
 
+pub mod use_player_profiles;
+pub use use_player_profiles::use_player_profiles;
+
+pub mod use_snapshot_entries;
+pub use use_snapshot_entries::use_snapshot_entries;
+
 pub mod use_theme;
 
 pub use use_theme::use_theme;
@@ -0,0 +1,59 @@
+use crate::Api;
+use mp_stats_core::models::PlatformEdition;
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+/// `(game, board, stat) -> entries` on each leaderboard's latest snapshot.
+pub type BoardSizes = HashMap<(SmolStr, SmolStr, SmolStr), u32>;
+
+/// Load how many players are ranked on every leaderboard of `games`, so a
+/// rank can be told as a share of its board. Games that fail to load are
+/// left out.
+#[hook]
+pub fn use_board_sizes(edition: PlatformEdition, games: Vec<String>) -> BoardSizes {
+    let sizes = use_state(BoardSizes::new);
+
+    let context = use_context::<Api>().expect("no api context found");
+
+    {
+        let sizes = sizes.clone();
+
+        use_effect_with((edition, games, context), move |(edition, games, ctx)| {
+            let edition = edition.clone();
+            let games = games.clone();
+            let provider = ctx.clone();
+
+            spawn_local(async move {
+                let fetched = futures::future::join_all(
+                    games
+                        .iter()
+                        .map(|game| provider.fetch_game_leaderboards(&edition, game)),
+                )
+                .await;
+
+                let mut found = BoardSizes::new();
+                for (game, data) in games.iter().zip(fetched) {
+                    let Ok(data) = data else { continue };
+                    for (stat, boards) in &data.stats {
+                        for (board, meta) in boards {
+                            if let Some(latest) =
+                                meta.snapshots.iter().find(|s| s.snapshot_id == "latest")
+                            {
+                                found.insert(
+                                    (SmolStr::new(game), board.clone(), stat.clone()),
+                                    latest.total_entries,
+                                );
+                            }
+                        }
+                    }
+                }
+                sizes.set(found);
+            });
+            || ()
+        });
+    }
+
+    (*sizes).clone()
+}
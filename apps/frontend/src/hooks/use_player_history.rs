@@ -0,0 +1,48 @@
+use crate::Api;
+use mp_stats_core::models::{PlatformEdition, PlayerHistory};
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct UsePlayerHistoryResult {
+    /// `None` while loading, and also when the player has no history: older
+    /// output has no history shards, and a player only ranked on other boards
+    /// has no entry in them. Either way there is simply nothing to chart.
+    pub history: Option<PlayerHistory>,
+    pub loading: bool,
+}
+
+#[hook]
+pub fn use_player_history(edition: PlatformEdition, uuid: String) -> UsePlayerHistoryResult {
+    let history = use_state(|| None::<PlayerHistory>);
+    let loading = use_state(|| true);
+
+    let context = use_context::<Api>().expect("no api context found");
+
+    {
+        let history = history.clone();
+        let loading = loading.clone();
+
+        use_effect_with((edition, uuid, context), move |(edition, id, ctx)| {
+            history.set(None);
+            loading.set(true);
+
+            let edition = edition.clone();
+            let id = id.clone();
+            let provider = ctx.clone();
+
+            spawn_local(async move {
+                if let Ok(h) = provider.fetch_player_history(&edition, &id).await {
+                    history.set(Some(h));
+                }
+                loading.set(false);
+            });
+            || ()
+        });
+    }
+
+    UsePlayerHistoryResult {
+        history: (*history).clone(),
+        loading: *loading,
+    }
+}
@@ -0,0 +1,65 @@
+use crate::Api;
+use mp_stats_core::models::{IdMap, PlatformEdition, PlayerProfile};
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct UsePlayerProfilesResult {
+    /// One entry per player asked for, in order: their profile, or `None` when
+    /// they have none or it failed to load.
+    pub profiles: Vec<Option<PlayerProfile>>,
+    pub id_map: Option<IdMap>,
+    pub loading: bool,
+}
+
+/// Load several players' profiles at once, for comparing them.
+#[hook]
+pub fn use_player_profiles(
+    edition: PlatformEdition,
+    uuids: Vec<String>,
+) -> UsePlayerProfilesResult {
+    let profiles = use_state(Vec::<Option<PlayerProfile>>::new);
+    let id_map = use_state(|| None::<IdMap>);
+    let loading = use_state(|| true);
+
+    let context = use_context::<Api>().expect("no api context found");
+
+    {
+        let profiles = profiles.clone();
+        let id_map = id_map.clone();
+        let loading = loading.clone();
+
+        use_effect_with((edition, uuids, context), move |(edition, uuids, ctx)| {
+            profiles.set(Vec::new());
+            id_map.set(None);
+            loading.set(true);
+
+            let edition = edition.clone();
+            let uuids = uuids.clone();
+            let provider = ctx.clone();
+
+            spawn_local(async move {
+                let (fetched, map) = futures::future::join(
+                    futures::future::join_all(
+                        uuids
+                            .iter()
+                            .map(|uuid| provider.fetch_player(&edition, uuid)),
+                    ),
+                    provider.fetch_id_map(&edition),
+                )
+                .await;
+
+                profiles.set(fetched.into_iter().map(Result::ok).collect());
+                id_map.set(map.ok());
+                loading.set(false);
+            });
+            || ()
+        });
+    }
+
+    UsePlayerProfilesResult {
+        profiles: (*profiles).clone(),
+        id_map: (*id_map).clone(),
+        loading: *loading,
+    }
+}
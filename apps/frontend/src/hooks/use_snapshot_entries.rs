@@ -0,0 +1,104 @@
+use crate::Api;
+use crate::models::LeaderboardEntry;
+use mp_stats_core::HistoricalSnapshot;
+use mp_stats_core::models::PlatformEdition;
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+#[derive(Clone, PartialEq)]
+pub struct UseSnapshotEntriesResult {
+    /// The entries of the first `max_pages` pages, in rank order.
+    pub entries: Vec<LeaderboardEntry>,
+    /// Whether `entries` holds every page of the snapshot.
+    pub complete: bool,
+    pub loading: bool,
+    pub error: Option<String>,
+}
+
+/// Load the first `max_pages` pages of one snapshot of a leaderboard at once,
+/// for the snapshot diff.
+#[hook]
+pub fn use_snapshot_entries(
+    edition: PlatformEdition,
+    game: String,
+    board: String,
+    stat: String,
+    snapshot: Option<HistoricalSnapshot>,
+    max_pages: u32,
+) -> UseSnapshotEntriesResult {
+    let entries = use_state(Vec::<LeaderboardEntry>::new);
+    let complete = use_state(|| false);
+    let loading = use_state(|| true);
+    let error = use_state(|| None::<String>);
+
+    let context = use_context::<Api>().expect("no api context found");
+
+    {
+        let entries = entries.clone();
+        let complete = complete.clone();
+        let loading = loading.clone();
+        let error = error.clone();
+
+        use_effect_with(
+            (edition, game, board, stat, snapshot),
+            move |(edition, game, board, stat, snapshot)| {
+                error.set(None);
+
+                if let Some(snapshot) = snapshot.clone() {
+                    let provider = context.clone();
+                    let (edition, game, board, stat) =
+                        (edition.clone(), game.clone(), board.clone(), stat.clone());
+                    let pages = snapshot.total_pages.min(max_pages);
+
+                    loading.set(true);
+                    spawn_local(async move {
+                        let is_latest = snapshot.snapshot_id == "latest";
+                        let fetched = futures::future::join_all((0..pages).map(|chunk| {
+                            let (provider, snapshot_id) = (&provider, &snapshot.snapshot_id);
+                            let (edition, game, board, stat) = (&edition, &game, &board, &stat);
+                            async move {
+                                if is_latest {
+                                    provider
+                                        .fetch_leaderboard(edition, board, game, stat, chunk)
+                                        .await
+                                } else {
+                                    provider
+                                        .fetch_history_leaderboard(
+                                            edition,
+                                            board,
+                                            game,
+                                            stat,
+                                            snapshot_id,
+                                            chunk,
+                                        )
+                                        .await
+                                }
+                            }
+                        }))
+                        .await;
+
+                        match fetched.into_iter().collect::<Result<Vec<_>, _>>() {
+                            Ok(pages) => {
+                                entries.set(pages.into_iter().flatten().collect());
+                                complete.set(snapshot.total_pages <= max_pages);
+                            }
+                            Err(e) => {
+                                entries.set(vec![]);
+                                error.set(Some(format!("Failed to fetch snapshot: {}", e)));
+                            }
+                        }
+                        loading.set(false);
+                    });
+                }
+                || ()
+            },
+        );
+    }
+
+    UseSnapshotEntriesResult {
+        entries: (*entries).clone(),
+        complete: *complete,
+        loading: *loading,
+        error: (*error).clone(),
+    }
+}
@@ -12,7 +12,9 @@ pub fn use_theme() -> &'static str {
             Route::Landing { edition }
             | Route::Game { edition, .. }
             | Route::Leaderboard { edition, .. }
-            | Route::Player { edition, .. },
+            | Route::LeaderboardDiff { edition, .. }
+            | Route::Player { edition, .. }
+            | Route::Compare { edition, .. },
         ) = route_ctx
         {
             get_theme_color(edition)
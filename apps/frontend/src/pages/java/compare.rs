@@ -0,0 +1,261 @@
+use crate::Route;
+use crate::components::error_message::ErrorMessage;
+use crate::hooks::{use_player_profiles, use_theme};
+use crate::util::compare::{
+    ComparedStat, align_stats, join_uuids, rank_delta, score_delta, split_uuids,
+};
+use crate::util::score_formatter::create_score_formatter;
+use mp_stats_core::models::{IdMap, PlatformEdition, PlayerProfile};
+use std::collections::BTreeMap;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+/// More columns than this no longer fit side by side.
+const MAX_COMPARED: usize = 6;
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct CompareProps {
+    pub edition: PlatformEdition,
+    /// The compared players, as [`join_uuids`] writes them.
+    pub uuids: String,
+}
+
+#[function_component(CompareView)]
+pub fn compare_view(props: &CompareProps) -> Html {
+    let uuids: Vec<String> = split_uuids(&props.uuids)
+        .into_iter()
+        .take(MAX_COMPARED)
+        .collect();
+    let req = use_player_profiles(props.edition.clone(), uuids.clone());
+    let profiles: Vec<PlayerProfile> = req.profiles.iter().flatten().cloned().collect();
+    let missing: Vec<&str> = uuids
+        .iter()
+        .zip(&req.profiles)
+        .filter(|(_, profile)| profile.is_none())
+        .map(|(uuid, _)| uuid.as_str())
+        .collect();
+    let theme_color = use_theme();
+    let navigator = use_navigator().unwrap();
+    let input_ref = use_node_ref();
+
+    let compare_with = |uuids: Vec<String>| Route::Compare {
+        edition: props.edition.clone(),
+        uuids: join_uuids(&uuids),
+    };
+
+    let onsubmit = {
+        let input_ref = input_ref.clone();
+        let uuids = uuids.clone();
+        let edition = props.edition.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let Some(input) = input_ref.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let added = split_uuids(&input.value());
+            if added.is_empty() {
+                return;
+            }
+            input.set_value("");
+            // Repeats are dropped when the page reads the route back.
+            let uuids: Vec<String> = uuids.iter().cloned().chain(added).collect();
+            navigator.push(&Route::Compare {
+                edition: edition.clone(),
+                uuids: join_uuids(&uuids),
+            });
+        })
+    };
+
+    html! {
+        <div class={classes!(theme_color, "container", "mx-auto", "px-6", "py-8", "max-w-6xl", "xl:max-w-7xl", "2xl:max-w-[1600px]")}>
+            // Crumbs
+            <div class="crumbs mb-5">
+                <Link<Route> to={Route::Home}>{"Home"}</Link<Route>>
+                <span class="sep">{"/"}</span>
+                <Link<Route> to={Route::Landing { edition: props.edition.clone() }}>{ props.edition.display_name() }</Link<Route>>
+                <span class="sep">{"/"}</span>
+                <span class="here">{"Compare"}</span>
+            </div>
+
+            // ---- Header ----
+            <div class="pb-7 border-b border-rule">
+                <div class="eyebrow mb-2">
+                    { format!("Player comparison · {} edition", props.edition.display_name()) }
+                </div>
+                <h1 class="serif page-title text-5xl md:text-6xl text-paper-1">{ "Compare players" }</h1>
+                <div class="flex flex-wrap gap-2 mt-4">
+                    { for uuids.iter().enumerate().map(|(i, uuid)| {
+                        let others: Vec<String> = uuids.iter().filter(|u| *u != uuid).cloned().collect();
+                        let name = match req.profiles.get(i) {
+                            Some(Some(p)) => display_name(p),
+                            _ => uuid.clone(),
+                        };
+                        html! {
+                            <span class="chip flex items-center gap-2">
+                                <Link<Route> to={Route::Player { edition: props.edition.clone(), uuid: uuid.clone() }} classes="hover:underline">
+                                    { name }
+                                </Link<Route>>
+                                <Link<Route> to={compare_with(others)} classes="text-paper-3 hover:text-paper-1">{ "×" }</Link<Route>>
+                            </span>
+                        }
+                    }) }
+                </div>
+                if uuids.len() < MAX_COMPARED {
+                    <form {onsubmit} class="flex gap-2 mt-4 max-w-md">
+                        <input
+                            ref={input_ref}
+                            type="text"
+                            placeholder="Add a player by UUID or gamertag"
+                            class="input-text font-mono flex-1 py-2 px-3 text-sm rounded-md"
+                        />
+                        <button type="submit" class="btn">{ "Add" }</button>
+                    </form>
+                }
+            </div>
+
+            if !req.loading && !missing.is_empty() {
+                <div class="mt-6">
+                    <ErrorMessage
+                        title="Some players could not be loaded"
+                        message={format!("No profile data for {}.", missing.join(", "))}
+                        is_banner={true}
+                    />
+                </div>
+            }
+
+            if req.loading {
+                <div class="card p-16 flex flex-col items-center justify-center gap-3 mt-6">
+                    <div class={classes!("animate-spin", "h-5", "w-5", "border-2", "border-theme-500", "border-t-transparent", "rounded-full")}></div>
+                    <p class="text-sm text-paper-3">{ "Loading profiles…" }</p>
+                </div>
+            } else if profiles.len() < 2 {
+                <div class="card p-12 text-center mt-6">
+                    <p class="serif text-2xl text-paper-1 mb-2">{ "Add a player to compare with" }</p>
+                    <p class="text-sm text-paper-3">{ "Scores, ranks and the difference to the first player are shown side by side, game by game." }</p>
+                </div>
+            } else if let Some(map) = &req.id_map {
+                <ComparisonGrid edition={props.edition.clone()} profiles={profiles.clone()} id_map={map.clone()} />
+            }
+        </div>
+    }
+}
+
+fn display_name(profile: &PlayerProfile) -> String {
+    profile.name.as_ref().unwrap_or(&profile.uuid).to_string()
+}
+
+#[derive(Properties, PartialEq, Clone)]
+struct ComparisonGridProps {
+    edition: PlatformEdition,
+    profiles: Vec<PlayerProfile>,
+    id_map: IdMap,
+}
+
+/// One card per game, with a row per stat and a column per player.
+#[function_component(ComparisonGrid)]
+fn comparison_grid(props: &ComparisonGridProps) -> Html {
+    let map = &props.id_map;
+    let mut games: BTreeMap<String, Vec<ComparedStat>> = BTreeMap::new();
+    for stat in align_stats(&props.profiles) {
+        let game_name = map
+            .games
+            .get(&stat.game_id)
+            .map(|g| g.name.as_str())
+            .unwrap_or("Unknown Game");
+        games.entry(game_name.to_string()).or_default().push(stat);
+    }
+
+    let columns = format!(
+        "grid-template-columns: minmax(120px,1fr) repeat({}, minmax(110px,1fr));",
+        props.profiles.len()
+    );
+
+    html! {
+        <div class="grid grid-cols-1 gap-4 mt-7">
+            { for games.into_iter().map(|(game_name, stats)| html! {
+                <div class="card p-5 overflow-x-auto">
+                    <div class="pb-3 mb-3 border-b border-rule">
+                        <Link<Route>
+                            to={Route::Game { edition: props.edition.clone(), game: game_name.clone() }}
+                            classes="serif text-xl text-theme-500 hover:underline"
+                        >
+                            { game_name.clone() }
+                        </Link<Route>>
+                    </div>
+
+                    <div class="grid gap-x-4 gap-y-1 items-baseline" style={columns.clone()}>
+                        <span></span>
+                        { for props.profiles.iter().map(|p| html! {
+                            <span class="text-xs font-medium text-paper-2 text-right truncate">{ display_name(p) }</span>
+                        }) }
+
+                        { for stats.iter().map(|stat| {
+                            let board_name = map.boards.get(&stat.board_id).map(|b| b.name.as_str()).unwrap_or("Board");
+                            let stat_name = map.stats.get(&stat.stat_id).map(|s| s.name.to_string()).unwrap_or(String::from("Stat"));
+                            let formatter = create_score_formatter(&game_name, &stat_name);
+                            let label = if board_name == "All" {
+                                stat_name.clone()
+                            } else {
+                                format!("{stat_name} ({board_name})")
+                            };
+                            let leader = stat.leader();
+                            let base = stat.entries[0];
+
+                            html! {
+                                <>
+                                    <Link<Route>
+                                        to={Route::Leaderboard { edition: props.edition.clone(), game: game_name.clone(), board: board_name.to_string(), stat: stat_name.clone(), page: 1 }}
+                                        classes="text-xs text-paper-2 truncate hover:underline py-1.5"
+                                    >
+                                        { label }
+                                    </Link<Route>>
+                                    { for stat.entries.iter().enumerate().map(|(i, entry)| {
+                                        let Some(entry) = entry else {
+                                            return html! { <span class="font-mono text-xs text-paper-4 text-right">{ "—" }</span> };
+                                        };
+                                        let score_class = if leader == Some(i) {
+                                            "font-mono tnum text-xs font-semibold text-theme-500"
+                                        } else {
+                                            "font-mono tnum text-xs text-paper-1"
+                                        };
+                                        let rank = if entry.rank > 0 { format!("#{}", entry.rank) } else { "—".to_string() };
+
+                                        // Every column after the first is measured against it.
+                                        let delta = base.filter(|_| i > 0).map(|base| {
+                                            let score = score_delta(base.score, entry.score);
+                                            let magnitude = u64::try_from(score.unsigned_abs()).unwrap_or(u64::MAX);
+                                            let score = match score.signum() {
+                                                1 => format!("+{}", formatter.format_score(magnitude)),
+                                                -1 => format!("−{}", formatter.format_score(magnitude)),
+                                                _ => "±0".to_string(),
+                                            };
+                                            let places = match rank_delta(base.rank, entry.rank) {
+                                                Some(places) if places > 0 => format!(" ▲{places}"),
+                                                Some(places) if places < 0 => format!(" ▼{}", -places),
+                                                _ => String::new(),
+                                            };
+                                            format!("{score}{places}")
+                                        });
+
+                                        html! {
+                                            <span class="flex flex-col items-end">
+                                                <span class={score_class}>{ formatter.format_score(entry.score) }</span>
+                                                <span class="font-mono tnum text-[11px] text-paper-3 whitespace-nowrap">
+                                                    { rank }
+                                                    if let Some(delta) = delta {
+                                                        { " · " }{ delta }
+                                                    }
+                                                </span>
+                                            </span>
+                                        }
+                                    }) }
+                                </>
+                            }
+                        }) }
+                    </div>
+                </div>
+            }) }
+        </div>
+    }
+}
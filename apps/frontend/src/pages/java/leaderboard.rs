@@ -5,12 +5,15 @@ use yew_router::prelude::*;
 use crate::Route;
 use crate::components::error_message::ErrorMessage;
 use crate::components::leaderboards::board_type_selector::BoardTypeSelector;
+use crate::components::leaderboards::find_player::FindPlayer;
 use crate::components::leaderboards::header::LeaderboardHeader;
 use crate::components::leaderboards::leaderboard_table::LeaderboardTable;
 use crate::components::leaderboards::pagination_controls::PaginationControls;
+use crate::components::leaderboards::score_rank::ScoreRank;
 use crate::components::leaderboards::snapshot_selector::SnapshotSelector;
 use crate::hooks::{use_game_leaderboards, use_leaderboard_entries, use_theme};
-use mp_stats_core::models::PlatformEdition;
+use crate::util::export::{ExportFormat, export_url};
+use mp_stats_core::models::{LeaderboardPosition, PlatformEdition};
 
 #[derive(Properties, PartialEq, Clone)]
 pub struct LeaderboardProps {
@@ -24,6 +27,9 @@ pub struct LeaderboardProps {
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct SnapshotQuery {
     pub snapshot: String,
+    /// The uuid of a row to scroll to and highlight, set by the player finder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
 }
 
 #[function_component(LeaderboardView)]
@@ -31,6 +37,7 @@ pub fn leaderboard_view(props: &LeaderboardProps) -> Html {
     let location = use_location().unwrap();
     let query: SnapshotQuery = location.query::<SnapshotQuery>().unwrap_or(SnapshotQuery {
         snapshot: "latest".to_string(),
+        highlight: None,
     });
 
     let navigator = use_navigator().unwrap();
@@ -108,6 +115,7 @@ pub fn leaderboard_view(props: &LeaderboardProps) -> Html {
                         &route,
                         &SnapshotQuery {
                             snapshot: query.snapshot.clone(),
+                            highlight: None,
                         },
                     )
                     .expect("Failed to navigate to new page");
@@ -137,6 +145,7 @@ pub fn leaderboard_view(props: &LeaderboardProps) -> Html {
                         &route,
                         &SnapshotQuery {
                             snapshot: new_snapshot,
+                            highlight: None,
                         },
                     )
                     .expect("Failed to navigate");
@@ -144,6 +153,50 @@ pub fn leaderboard_view(props: &LeaderboardProps) -> Html {
         })
     };
 
+    // The page a found player is on, with their row highlighted.
+    let on_found = {
+        let navigator = navigator.clone();
+        let props = props.clone();
+        let snapshot = query.snapshot.clone();
+        Callback::from(move |(uuid, position): (String, LeaderboardPosition)| {
+            let route = Route::Leaderboard {
+                edition: props.edition.clone(),
+                game: props.game.clone(),
+                board: props.board.clone(),
+                stat: props.stat.clone(),
+                page: position.page + 1,
+            };
+            navigator
+                .push_with_query(
+                    &route,
+                    &SnapshotQuery {
+                        snapshot: snapshot.clone(),
+                        highlight: Some(uuid),
+                    },
+                )
+                .expect("Failed to navigate");
+        })
+    };
+
+    // The snapshot shown against the one before it, or the one after when it is the first.
+    let diff_route = current_meta.as_ref().as_ref().and_then(|meta| {
+        let mut chronological = meta.snapshots.clone();
+        chronological.sort_by_key(|s| s.timestamp);
+        let at = chronological
+            .iter()
+            .position(|s| s.snapshot_id == query.snapshot)?;
+        let (from, to) = if at > 0 { (at - 1, at) } else { (at, at + 1) };
+        let (from, to) = (chronological.get(from)?, chronological.get(to)?);
+        Some(Route::LeaderboardDiff {
+            edition: props.edition.clone(),
+            game: props.game.clone(),
+            board: props.board.clone(),
+            stat: props.stat.clone(),
+            from: from.snapshot_id.to_string(),
+            to: to.snapshot_id.to_string(),
+        })
+    });
+
     let scroll_to_bottom = Callback::from(|_| {
         if let Some(window) = web_sys::window() {
             window.scroll_to_with_x_and_y(0.0, 100000.0);
@@ -161,7 +214,12 @@ pub fn leaderboard_view(props: &LeaderboardProps) -> Html {
 
     html! {
         <div class={classes!(theme_color, "container", "mx-auto", "px-6", "py-8", "max-w-6xl", "xl:max-w-7xl", "2xl:max-w-[1600px]")}>
-            <LeaderboardHeader edition={props.edition.clone()} game={props.game.clone()} stat={props.stat.clone()} />
+            <LeaderboardHeader
+                edition={props.edition.clone()}
+                game={props.game.clone()}
+                stat={props.stat.clone()}
+                distribution={current_snapshot_meta.as_ref().as_ref().and_then(|meta| meta.distribution.clone())}
+            />
 
             // Snapshot timeline card
             <div class="mt-6">
@@ -173,7 +231,7 @@ pub fn leaderboard_view(props: &LeaderboardProps) -> Html {
                 />
             </div>
 
-            // Controls row: board selector + go-to-bottom
+            // Controls row: board selector, player finder, score ranking, diff, export and go-to-bottom
             <div class="flex flex-col md:flex-row md:items-center justify-between gap-3 mt-6 mb-4">
                 <div>
                     if !boards.is_empty() {
@@ -187,12 +245,53 @@ pub fn leaderboard_view(props: &LeaderboardProps) -> Html {
                     }
                 </div>
 
-                <button onclick={scroll_to_bottom} class="btn-ghost text-xs font-mono">
-                    <svg xmlns="http://www.w3.org/2000/svg" class="h-3.5 w-3.5" fill="none" viewBox="0 0 24 24" stroke="currentColor" stroke-width="2">
-                        <path stroke-linecap="round" stroke-linejoin="round" d="M19 14l-7 7m0 0l-7-7m7 7V3" />
-                    </svg>
-                    { "Go to bottom" }
-                </button>
+                <div class="flex flex-wrap items-center gap-2">
+                    <FindPlayer
+                        edition={props.edition.clone()}
+                        game={props.game.clone()}
+                        board={props.board.clone()}
+                        stat={props.stat.clone()}
+                        snapshot={query.snapshot.clone()}
+                        {on_found}
+                    />
+                    <ScoreRank
+                        edition={props.edition.clone()}
+                        game={props.game.clone()}
+                        board={props.board.clone()}
+                        stat={props.stat.clone()}
+                        snapshot={(*current_snapshot_meta).clone()}
+                    />
+                    if let Some(route) = diff_route {
+                        <Link<Route> to={route} classes="btn-ghost text-xs font-mono">
+                            { "Compare snapshots" }
+                        </Link<Route>>
+                    }
+                    // Every page of the snapshot shown, in one file.
+                    { for ExportFormat::ALL.into_iter().map(|format| {
+                        let href = export_url(
+                            &props.edition,
+                            &props.board,
+                            &props.game,
+                            &props.stat,
+                            (!is_latest).then_some(query.snapshot.as_str()),
+                            format,
+                        );
+                        html! {
+                            <a {href} download="" class="btn-ghost text-xs font-mono" title={format!("Download every page as {}", format.label())}>
+                                <svg xmlns="http://www.w3.org/2000/svg" class="h-3.5 w-3.5" fill="none" viewBox="0 0 24 24" stroke="currentColor" stroke-width="2">
+                                    <path stroke-linecap="round" stroke-linejoin="round" d="M4 16v2a2 2 0 002 2h12a2 2 0 002-2v-2M12 4v12m0 0l-4-4m4 4l4-4" />
+                                </svg>
+                                { format.label() }
+                            </a>
+                        }
+                    }) }
+                    <button onclick={scroll_to_bottom} class="btn-ghost text-xs font-mono">
+                        <svg xmlns="http://www.w3.org/2000/svg" class="h-3.5 w-3.5" fill="none" viewBox="0 0 24 24" stroke="currentColor" stroke-width="2">
+                            <path stroke-linecap="round" stroke-linejoin="round" d="M19 14l-7 7m0 0l-7-7m7 7V3" />
+                        </svg>
+                        { "Go to bottom" }
+                    </button>
+                </div>
             </div>
 
             if let Some(err) = &error {
@@ -206,6 +305,7 @@ pub fn leaderboard_view(props: &LeaderboardProps) -> Html {
                         stat={props.stat.clone()}
                         entries={entries_req.entries.clone()}
                         edition={props.edition.clone()}
+                        highlight={query.highlight.clone()}
                     />
                     <PaginationControls
                         edition={props.edition.clone()}
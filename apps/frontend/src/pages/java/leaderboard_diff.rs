@@ -0,0 +1,356 @@
+use web_sys::js_sys::Date;
+use web_sys::js_sys::Intl::DateTimeFormatOptions;
+use web_sys::wasm_bindgen::JsValue;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::Route;
+use crate::components::error_message::ErrorMessage;
+use crate::components::leaderboards::header::LeaderboardHeader;
+use crate::hooks::{use_game_leaderboards, use_snapshot_entries, use_theme};
+use crate::util::score_formatter::create_score_formatter;
+use crate::util::snapshot_diff::{DiffRow, Standing, compared_depth, diff_snapshots, summarize};
+use mp_stats_core::HistoricalSnapshot;
+use mp_stats_core::models::PlatformEdition;
+
+/// How many pages of each snapshot are loaded. Boards longer than this are
+/// compared over their top only.
+const MAX_DIFF_PAGES: u32 = 10;
+
+#[derive(Properties, PartialEq, Clone)]
+pub struct LeaderboardDiffProps {
+    pub edition: PlatformEdition,
+    pub game: String,
+    pub board: String,
+    pub stat: String,
+    /// The earlier snapshot id, or `latest`.
+    pub from: String,
+    /// The later snapshot id, or `latest`.
+    pub to: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DiffFilter {
+    All,
+    Movers,
+    Entered,
+    Dropped,
+}
+
+impl DiffFilter {
+    const ALL: [Self; 4] = [Self::All, Self::Movers, Self::Entered, Self::Dropped];
+
+    fn label(self) -> &'static str {
+        match self {
+            DiffFilter::All => "All",
+            DiffFilter::Movers => "Movers",
+            DiffFilter::Entered => "New",
+            DiffFilter::Dropped => "Dropped",
+        }
+    }
+
+    fn keeps(self, row: &DiffRow) -> bool {
+        match self {
+            DiffFilter::All => true,
+            DiffFilter::Movers => row.rank_change().is_some_and(|places| places != 0),
+            DiffFilter::Entered => row.is_new(),
+            DiffFilter::Dropped => row.has_dropped(),
+        }
+    }
+}
+
+#[function_component(LeaderboardDiffView)]
+pub fn leaderboard_diff_view(props: &LeaderboardDiffProps) -> Html {
+    let theme_color = use_theme();
+    let navigator = use_navigator().unwrap();
+    let filter = use_state(|| DiffFilter::All);
+
+    let game_req = use_game_leaderboards(props.edition.clone(), props.game.clone());
+    let snapshots = use_memo(
+        (
+            game_req.data.clone(),
+            props.board.clone(),
+            props.stat.clone(),
+        ),
+        |(data, board, stat)| {
+            let mut snapshots = data
+                .as_ref()
+                .and_then(|d| d.stats.get(stat.as_str()))
+                .and_then(|stat_map| stat_map.get(board.as_str()))
+                .map(|meta| meta.snapshots.clone())
+                .unwrap_or_default();
+            snapshots.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+            snapshots
+        },
+    );
+    let find = |id: &str| -> Option<HistoricalSnapshot> {
+        snapshots
+            .iter()
+            .find(|s| s.snapshot_id.as_str() == id)
+            .cloned()
+    };
+    let (from_snapshot, to_snapshot) = (find(&props.from), find(&props.to));
+
+    let before = use_snapshot_entries(
+        props.edition.clone(),
+        props.game.clone(),
+        props.board.clone(),
+        props.stat.clone(),
+        from_snapshot.clone(),
+        MAX_DIFF_PAGES,
+    );
+    let after = use_snapshot_entries(
+        props.edition.clone(),
+        props.game.clone(),
+        props.board.clone(),
+        props.stat.clone(),
+        to_snapshot.clone(),
+        MAX_DIFF_PAGES,
+    );
+
+    let depth = compared_depth(
+        &before.entries,
+        before.complete,
+        &after.entries,
+        after.complete,
+    );
+    let rows = use_memo(
+        (before.entries.clone(), after.entries.clone(), depth),
+        |(before, after, depth)| diff_snapshots(before, after, *depth),
+    );
+    let summary = summarize(&rows);
+
+    let loading = game_req.loading || before.loading || after.loading;
+    let error = game_req
+        .error
+        .clone()
+        .or(before.error.clone())
+        .or(after.error.clone());
+    let unknown_snapshot = !game_req.loading && (from_snapshot.is_none() || to_snapshot.is_none());
+
+    let locale = web_sys::window()
+        .map(|w| w.navigator())
+        .and_then(|n| n.language())
+        .unwrap_or_else(|| "en-US".to_string());
+    let date_formats = DateTimeFormatOptions::new();
+    let snapshot_label = |snapshot: &HistoricalSnapshot| -> String {
+        let ms = (snapshot.timestamp * 1000) as f64;
+        let formatted: String = Date::new(&JsValue::from_f64(ms))
+            .to_locale_date_string(&locale, &date_formats)
+            .into();
+        if snapshot.snapshot_id == "latest" {
+            format!("Latest ({formatted})")
+        } else {
+            formatted
+        }
+    };
+
+    let diff_route = {
+        let props = props.clone();
+        move |from: String, to: String| Route::LeaderboardDiff {
+            edition: props.edition.clone(),
+            game: props.game.clone(),
+            board: props.board.clone(),
+            stat: props.stat.clone(),
+            from,
+            to,
+        }
+    };
+
+    let on_from = {
+        let navigator = navigator.clone();
+        let diff_route = diff_route.clone();
+        let to = props.to.clone();
+        Callback::from(move |e: Event| {
+            let target: web_sys::HtmlSelectElement = e.target_unchecked_into();
+            navigator.push(&diff_route(target.value(), to.clone()));
+        })
+    };
+    let on_to = {
+        let navigator = navigator.clone();
+        let diff_route = diff_route.clone();
+        let from = props.from.clone();
+        Callback::from(move |e: Event| {
+            let target: web_sys::HtmlSelectElement = e.target_unchecked_into();
+            navigator.push(&diff_route(from.clone(), target.value()));
+        })
+    };
+
+    let snapshot_select = |current: &str, onchange: Callback<Event>| {
+        html! {
+            <select
+                {onchange}
+                class={classes!(theme_color, "appearance-none", "w-full", "px-3", "py-2", "bg-ink-2", "border", "border-rule", "rounded-md", "text-sm", "font-mono", "text-paper-1", "cursor-pointer", "focus:outline-none", "focus:border-theme-500/60", "transition-colors")}
+            >
+                { for snapshots.iter().map(|snap| html! {
+                    <option value={snap.snapshot_id.to_string()} selected={snap.snapshot_id.as_str() == current}>
+                        { snapshot_label(snap) }
+                    </option>
+                }) }
+            </select>
+        }
+    };
+
+    let formatter = create_score_formatter(&props.game, &props.stat);
+    let standing = |standing: Option<Standing>| match standing {
+        Some(s) => html! {
+            <span class="flex flex-col items-end">
+                <span class="font-mono tnum text-sm text-paper-1">{ formatter.format_score(s.score) }</span>
+                <span class="font-mono tnum text-[11px] text-paper-3">{ format!("#{}", s.rank) }</span>
+            </span>
+        },
+        None => html! { <span class="font-mono text-xs text-paper-4">{ "—" }</span> },
+    };
+
+    html! {
+        <div class={classes!(theme_color, "container", "mx-auto", "px-6", "py-8", "max-w-6xl", "xl:max-w-7xl", "2xl:max-w-[1600px]")}>
+            <LeaderboardHeader edition={props.edition.clone()} game={props.game.clone()} stat={props.stat.clone()} />
+
+            // Snapshot pickers
+            <div class="card p-4 mt-6">
+                <div class="flex items-baseline justify-between mb-3 gap-3 flex-wrap">
+                    <div class="eyebrow">{ format!("Snapshot diff · {} board", props.board) }</div>
+                    <Link<Route>
+                        to={Route::Leaderboard { edition: props.edition.clone(), game: props.game.clone(), board: props.board.clone(), stat: props.stat.clone(), page: 1 }}
+                        classes="font-mono text-[11px] text-paper-3 hover:text-paper-1"
+                    >
+                        { "Back to leaderboard" }
+                    </Link<Route>>
+                </div>
+                <div class="flex flex-col md:flex-row md:items-end gap-3">
+                    <label class="flex-1">
+                        <span class="eyebrow block mb-1">{ "From" }</span>
+                        { snapshot_select(&props.from, on_from) }
+                    </label>
+                    <Link<Route> to={diff_route(props.to.clone(), props.from.clone())} classes="btn-ghost text-xs font-mono">
+                        { "⇄ Swap" }
+                    </Link<Route>>
+                    <label class="flex-1">
+                        <span class="eyebrow block mb-1">{ "To" }</span>
+                        { snapshot_select(&props.to, on_to) }
+                    </label>
+                </div>
+            </div>
+
+            if let Some(err) = &error {
+                <div class="mt-6">
+                    <ErrorMessage title="Error loading data" message={err.clone()} />
+                </div>
+            } else if unknown_snapshot {
+                <div class="mt-6">
+                    <ErrorMessage title="Unknown snapshot" message="This board has no snapshot with that id; pick one above." />
+                </div>
+            } else if loading {
+                <div class="card p-12 text-center mt-6">
+                    <div class={classes!("animate-spin", "h-5", "w-5", "border-2", "border-theme-500", "border-t-transparent", "rounded-full", "mx-auto", "mb-3")}></div>
+                    <p class="text-sm text-paper-3">{ "Loading both snapshots…" }</p>
+                </div>
+            } else if props.from == props.to {
+                <div class="card p-12 text-center mt-6">
+                    <p class="serif text-2xl text-paper-1 mb-2">{ "Pick two different snapshots" }</p>
+                    <p class="text-sm text-paper-3">{ "Rank movement, score changes, new entrants and players who dropped off are shown between them." }</p>
+                </div>
+            } else {
+                // Summary and filters
+                <div class="flex flex-col md:flex-row md:items-center justify-between gap-3 mt-6 mb-4">
+                    <div class="inline-flex items-center gap-1 p-1 bg-ink-2 border border-rule rounded-md">
+                        { for DiffFilter::ALL.into_iter().map(|option| {
+                            let count = match option {
+                                DiffFilter::All => rows.len(),
+                                DiffFilter::Movers => summary.climbed + summary.fell,
+                                DiffFilter::Entered => summary.entered,
+                                DiffFilter::Dropped => summary.dropped,
+                            };
+                            let class = if *filter == option {
+                                classes!("px-3", "py-1.5", "rounded", "text-xs", "font-mono", "bg-ink-3", "text-theme-400", "border", "border-theme-500/40")
+                            } else {
+                                classes!("px-3", "py-1.5", "rounded", "text-xs", "font-mono", "text-paper-3", "hover:text-paper-1", "hover:bg-ink-3", "border", "border-transparent", "cursor-pointer")
+                            };
+                            let onclick = {
+                                let filter = filter.clone();
+                                Callback::from(move |_: MouseEvent| filter.set(option))
+                            };
+                            html! {
+                                <button type="button" {class} {onclick}>
+                                    { format!("{} · {count}", option.label()) }
+                                </button>
+                            }
+                        }) }
+                    </div>
+                    <div class="font-mono text-[11px] text-paper-3">
+                        { format!("▲ {} climbed · ▼ {} fell", summary.climbed, summary.fell) }
+                    </div>
+                </div>
+
+                if let Some(depth) = depth {
+                    <p class="text-xs text-paper-3 mb-3">
+                        { format!("Only the first {MAX_DIFF_PAGES} pages of each snapshot are loaded, so the top {depth} are compared; \"new\" and \"dropped\" are relative to that range.") }
+                    </p>
+                }
+
+                <div class="card overflow-x-auto">
+                    <table class="w-full text-left border-collapse">
+                        <thead>
+                            <tr>
+                                <th class="table-header">{ "Player" }</th>
+                                <th class="table-header text-right">{ "Before" }</th>
+                                <th class="table-header text-right">{ "After" }</th>
+                                <th class="table-header text-right">{ "Rank" }</th>
+                                <th class="table-header text-right">{ "Score" }</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            { for rows.iter().filter(|row| filter.keeps(row)).map(|row| {
+                                let rank = match (row.is_new(), row.has_dropped(), row.rank_change()) {
+                                    (true, _, _) => html! { <span class="chip text-theme-400">{ "New" }</span> },
+                                    (_, true, _) => html! { <span class="chip text-paper-3">{ "Dropped" }</span> },
+                                    (_, _, Some(places)) if places > 0 => html! { <span class="font-mono tnum text-xs text-theme-400">{ format!("▲{places}") }</span> },
+                                    (_, _, Some(places)) if places < 0 => html! { <span class="font-mono tnum text-xs text-paper-3">{ format!("▼{}", -places) }</span> },
+                                    _ => html! { <span class="font-mono text-xs text-paper-4">{ "=" }</span> },
+                                };
+                                let score = match row.score_change() {
+                                    Some(change) => {
+                                        let magnitude = u64::try_from(change.unsigned_abs()).unwrap_or(u64::MAX);
+                                        match change.signum() {
+                                            1 => format!("+{}", formatter.format_score(magnitude)),
+                                            -1 => format!("−{}", formatter.format_score(magnitude)),
+                                            _ => "±0".to_string(),
+                                        }
+                                    }
+                                    None => "—".to_string(),
+                                };
+
+                                html! {
+                                    <tr class="table-row">
+                                        <td class="table-cell">
+                                            <Link<Route> to={Route::Player { edition: props.edition.clone(), uuid: row.uuid.to_string() }} classes="flex items-center gap-3 w-fit group/link">
+                                                <img
+                                                    src={format!("https://mc-heads.net/avatar/{}/32", row.uuid)}
+                                                    class="w-6 h-6 rounded bg-ink-3 border border-rule"
+                                                    alt="Avatar"
+                                                    loading="lazy"
+                                                />
+                                                <span class="font-mono text-sm font-medium text-paper-1 group-hover/link:text-theme-400 transition-colors">
+                                                    { row.name.as_str() }
+                                                </span>
+                                            </Link<Route>>
+                                        </td>
+                                        <td class="table-cell text-right">{ standing(row.before) }</td>
+                                        <td class="table-cell text-right">{ standing(row.after) }</td>
+                                        <td class="table-cell text-right">{ rank }</td>
+                                        <td class="table-cell text-right font-mono tnum text-xs text-paper-2">{ score }</td>
+                                    </tr>
+                                }
+                            }) }
+                        </tbody>
+                    </table>
+                    if rows.is_empty() {
+                        <div class="p-12 text-center">
+                            <p class="text-sm text-paper-3">{ "Neither snapshot has entries." }</p>
+                        </div>
+                    }
+                </div>
+            }
+        </div>
+    }
+}
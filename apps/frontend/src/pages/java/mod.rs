@@ -1,12 +1,18 @@
+pub use self::compare::CompareView;
 pub use self::game::GameView;
 pub use self::landing::JavaLanding;
 pub use self::player::PlayerView;
 pub use leaderboard::LeaderboardView;
+pub use leaderboard_diff::LeaderboardDiffView;
 
 pub mod leaderboard;
 
+pub mod leaderboard_diff;
+
 pub mod game;
 
 pub mod player;
 
+pub mod compare;
+
 pub mod landing;
@@ -1,8 +1,11 @@
 use crate::Route;
 use crate::components::error_message::ErrorMessage;
-use crate::hooks::{use_player_profile, use_theme};
+use crate::components::player_history::PlayerHistoryChart;
+use crate::hooks::{use_board_sizes, use_player_history, use_player_profile, use_theme};
+use crate::util::percent::format_top_percent;
 use crate::util::score_formatter::create_score_formatter;
 use mp_stats_core::models::PlatformEdition;
+use smol_str::SmolStr;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
@@ -15,8 +18,25 @@ pub struct PlayerProps {
 #[function_component(PlayerView)]
 pub fn player_view(props: &PlayerProps) -> Html {
     let profile_req = use_player_profile(props.edition.clone(), props.uuid.clone());
+    let history_req = use_player_history(props.edition.clone(), props.uuid.clone());
     let theme_color = use_theme();
 
+    // Every game the player is ranked in, for the size of each of its boards.
+    let ranked_games: Vec<String> = match (&profile_req.profile, &profile_req.id_map) {
+        (Some(profile), Some(map)) => {
+            let mut names: Vec<String> = profile
+                .stats
+                .iter()
+                .filter_map(|s| map.games.get(&s.game_id).map(|g| g.name.to_string()))
+                .collect();
+            names.sort();
+            names.dedup();
+            names
+        }
+        _ => Vec::new(),
+    };
+    let board_sizes = use_board_sizes(props.edition.clone(), ranked_games);
+
     html! {
         <div class={classes!(theme_color, "container", "mx-auto", "px-6", "py-8", "max-w-6xl", "xl:max-w-7xl", "2xl:max-w-[1600px]")}>
             // Crumbs
@@ -50,7 +70,32 @@ pub fn player_view(props: &PlayerProps) -> Html {
                             </h1>
                             <div class="flex flex-wrap gap-2 mt-4">
                                 <span class="chip select-all">{ p.uuid.as_str() }</span>
+                                <Link<Route>
+                                    to={Route::Compare { edition: props.edition.clone(), uuids: p.uuid.to_string() }}
+                                    classes="chip hover:text-paper-1"
+                                >
+                                    { "Compare with…" }
+                                </Link<Route>>
                             </div>
+                            {
+                                {
+                                    // Former names, newest first; the history ends with the current one.
+                                    let former: Vec<&str> = p.name_history.iter().rev()
+                                        .map(|record| record.name.as_str())
+                                        .filter(|name| Some(*name) != p.name.as_deref())
+                                        .collect();
+                                    if former.is_empty() {
+                                        html! {}
+                                    } else {
+                                        html! {
+                                            <div class="flex flex-wrap items-center gap-2 mt-3">
+                                                <span class="eyebrow">{"Also known as"}</span>
+                                                { for former.into_iter().map(|name| html! { <span class="chip">{ name }</span> }) }
+                                            </div>
+                                        }
+                                    }
+                                }
+                            }
                         </div>
                     </div>
                 </div>
@@ -115,6 +160,17 @@ pub fn player_view(props: &PlayerProps) -> Html {
                                                     let bar_color = if is_top10 { "var(--color-theme-500)" } else { "var(--color-paper-3)" };
                                                     let bar_style = format!("width:{:.1}%; background:{};", fill * 100.0, bar_color);
 
+                                                    // Past the top ten, the share of the board says more than the place.
+                                                    let board_size = board_sizes
+                                                        .get(&(SmolStr::new(&game_name), SmolStr::new(board_name), SmolStr::new(&stat_name)))
+                                                        .copied();
+                                                    let top_share = board_size
+                                                        .filter(|_| !is_top10)
+                                                        .and_then(|total| format_top_percent(rank, total));
+                                                    let rank_title = board_size
+                                                        .filter(|_| rank > 0)
+                                                        .map(|total| format!("#{rank} of {total}"));
+
                                                     let rank_class = if is_top10 {
                                                         "font-mono tnum text-xs font-semibold text-theme-500 text-right whitespace-nowrap"
                                                     } else if rank > 0 {
@@ -135,8 +191,14 @@ pub fn player_view(props: &PlayerProps) -> Html {
                                                             <span class="font-mono tnum text-xs text-paper-1 text-right whitespace-nowrap">
                                                                 { formatted_score }
                                                             </span>
-                                                            <span class={rank_class}>
-                                                                { if rank > 0 { format!("#{rank}") } else { "—".to_string() } }
+                                                            <span class={rank_class} title={rank_title}>
+                                                                {
+                                                                    match top_share {
+                                                                        Some(share) => share,
+                                                                        None if rank > 0 => format!("#{rank}"),
+                                                                        None => "—".to_string(),
+                                                                    }
+                                                                }
                                                             </span>
                                                         </Link<Route>>
                                                     }
@@ -155,6 +217,11 @@ pub fn player_view(props: &PlayerProps) -> Html {
                         }) }
                     </div>
                 }
+
+                // ---- Score & position over time ----
+                if let Some(history) = &history_req.history {
+                    <PlayerHistoryChart history={history.clone()} />
+                }
             } else if profile_req.loading {
                 <div class="card p-16 flex flex-col items-center justify-center gap-3 mt-6">
                     <div class={classes!("animate-spin", "h-5", "w-5", "border-2", "border-theme-500", "border-t-transparent", "rounded-full")}></div>
@@ -20,11 +20,27 @@ pub enum Route {
         stat: String,
         page: u32,
     },
+    /// What changed on a leaderboard between snapshots `from` and `to`.
+    #[at("/:edition/leaderboard/:game/:board/:stat/diff/:from/:to")]
+    LeaderboardDiff {
+        edition: PlatformEdition,
+        game: String,
+        board: String,
+        stat: String,
+        from: String,
+        to: String,
+    },
     #[at("/:edition/player/:uuid")]
     Player {
         edition: PlatformEdition,
         uuid: String,
     },
+    /// Several players side by side; `uuids` is comma separated.
+    #[at("/:edition/compare/:uuids")]
+    Compare {
+        edition: PlatformEdition,
+        uuids: String,
+    },
     #[not_found]
     #[at("/404")]
     NotFound,
@@ -0,0 +1,168 @@
+use mp_stats_core::models::{PlayerProfile, StatRaw};
+use std::collections::BTreeMap;
+
+/// Separates the players in a [`Route::Compare`](crate::Route::Compare) path
+/// segment. Neither UUIDs nor gamertags contain it.
+const SEPARATOR: char = ',';
+
+/// The players a compare path segment names, in order, without blanks or
+/// repeats.
+pub fn split_uuids(uuids: &str) -> Vec<String> {
+    let mut split: Vec<String> = Vec::new();
+    for uuid in uuids.split(SEPARATOR).map(str::trim) {
+        if !uuid.is_empty() && !split.iter().any(|u| u == uuid) {
+            split.push(uuid.to_string());
+        }
+    }
+    split
+}
+
+/// The compare path segment naming `uuids`.
+pub fn join_uuids<S: AsRef<str>>(uuids: &[S]) -> String {
+    let uuids: Vec<&str> = uuids.iter().map(AsRef::as_ref).collect();
+    uuids.join(&SEPARATOR.to_string())
+}
+
+/// One (board, game, stat) across the compared players.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparedStat<'a> {
+    pub board_id: u32,
+    pub game_id: u32,
+    pub stat_id: u32,
+    /// One entry per compared profile, in the order given; `None` when that
+    /// player has no score on the stat.
+    pub entries: Vec<Option<&'a StatRaw>>,
+}
+
+impl ComparedStat<'_> {
+    /// The position in `entries` of the best ranked player, or `None` when no
+    /// one is ranked or the best rank is shared.
+    pub fn leader(&self) -> Option<usize> {
+        let mut ranked = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| entry.filter(|s| s.rank > 0).map(|s| (s.rank, i)));
+
+        let mut best = ranked.next()?;
+        let mut shared = false;
+        for (rank, i) in ranked {
+            if rank < best.0 {
+                best = (rank, i);
+                shared = false;
+            } else if rank == best.0 {
+                shared = true;
+            }
+        }
+        (!shared).then_some(best.1)
+    }
+}
+
+/// Line up the stats of `profiles` by (board, game, stat), sorted by game,
+/// then stat, then board id.
+///
+/// Every stat any of the players has a score on is listed, so a gap shows
+/// where one player has played and another has not.
+pub fn align_stats(profiles: &[PlayerProfile]) -> Vec<ComparedStat<'_>> {
+    let mut aligned: BTreeMap<(u32, u32, u32), Vec<Option<&StatRaw>>> = BTreeMap::new();
+    for (i, profile) in profiles.iter().enumerate() {
+        for stat in &profile.stats {
+            aligned
+                .entry((stat.game_id, stat.stat_id, stat.board_id))
+                .or_insert_with(|| vec![None; profiles.len()])[i] = Some(stat);
+        }
+    }
+
+    aligned
+        .into_iter()
+        .map(|((game_id, stat_id, board_id), entries)| ComparedStat {
+            board_id,
+            game_id,
+            stat_id,
+            entries,
+        })
+        .collect()
+}
+
+/// How far `other`'s score is above (positive) or below `base`'s.
+pub fn score_delta(base: u64, other: u64) -> i128 {
+    i128::from(other) - i128::from(base)
+}
+
+/// How many places `other` ranks above (positive) or below `base`, when both
+/// are ranked.
+pub fn rank_delta(base: u32, other: u32) -> Option<i64> {
+    (base > 0 && other > 0).then(|| i64::from(base) - i64::from(other))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(game_id: u32, stat_id: u32, score: u64, rank: u32) -> StatRaw {
+        StatRaw {
+            board_id: 1,
+            game_id,
+            stat_id,
+            score,
+            rank,
+            save_time: 0,
+        }
+    }
+
+    fn profile(uuid: &str, stats: Vec<StatRaw>) -> PlayerProfile {
+        PlayerProfile {
+            uuid: uuid.into(),
+            name: None,
+            stats,
+            name_history: vec![],
+        }
+    }
+
+    #[test]
+    fn uuids_round_trip_through_the_path() {
+        let uuids = split_uuids(" a-1 ,b-2,,a-1,C Titan");
+        assert_eq!(uuids, ["a-1", "b-2", "C Titan"]);
+        assert_eq!(split_uuids(&join_uuids(&uuids)), uuids);
+        assert!(split_uuids("").is_empty());
+    }
+
+    #[test]
+    fn stats_are_aligned_with_gaps_for_missing_players() {
+        let profiles = [
+            profile("a", vec![stat(2, 1, 50, 3), stat(1, 1, 10, 9)]),
+            profile("b", vec![stat(1, 1, 30, 4)]),
+        ];
+        let aligned = align_stats(&profiles);
+
+        let keys: Vec<_> = aligned.iter().map(|s| (s.game_id, s.stat_id)).collect();
+        assert_eq!(keys, [(1, 1), (2, 1)]);
+        assert_eq!(aligned[0].entries[0].map(|s| s.score), Some(10));
+        assert_eq!(aligned[0].entries[1].map(|s| s.score), Some(30));
+        assert_eq!(aligned[0].leader(), Some(1));
+        assert_eq!(aligned[1].entries[1], None);
+        assert_eq!(aligned[1].leader(), Some(0));
+    }
+
+    #[test]
+    fn a_shared_or_missing_rank_has_no_leader() {
+        let profiles = [
+            profile("a", vec![stat(1, 1, 10, 2), stat(1, 2, 5, 0)]),
+            profile("b", vec![stat(1, 1, 10, 2)]),
+        ];
+        let aligned = align_stats(&profiles);
+
+        assert_eq!(aligned[0].leader(), None);
+        assert_eq!(aligned[1].leader(), None);
+    }
+
+    #[test]
+    fn deltas_are_signed_from_the_first_player() {
+        assert_eq!(score_delta(10, 30), 20);
+        assert_eq!(score_delta(30, 10), -20);
+        assert_eq!(score_delta(0, u64::MAX), i128::from(u64::MAX));
+        assert_eq!(rank_delta(9, 4), Some(5));
+        assert_eq!(rank_delta(4, 9), Some(-5));
+        assert_eq!(rank_delta(0, 4), None);
+    }
+}
@@ -0,0 +1,105 @@
+use mp_stats_core::models::PlatformEdition;
+
+/// A format the server exports a whole leaderboard snapshot in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub const ALL: [Self; 2] = [Self::Csv, Self::Ndjson];
+
+    /// The `format` query value the server expects.
+    pub fn query_value(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Ndjson => "NDJSON",
+        }
+    }
+}
+
+/// The download URL of every page of a leaderboard snapshot; the latest
+/// snapshot when `snapshot` is `None`.
+pub fn export_url(
+    edition: &PlatformEdition,
+    board: &str,
+    game: &str,
+    stat: &str,
+    snapshot: Option<&str>,
+    format: ExportFormat,
+) -> String {
+    let mut url = format!(
+        "/api/v1/{}/leaderboards/{}/{}/{}/export?format={}",
+        edition.directory_name(),
+        encode_component(board),
+        encode_component(game),
+        encode_component(stat),
+        format.query_value()
+    );
+    if let Some(snapshot) = snapshot {
+        url.push_str("&snapshot=");
+        url.push_str(&encode_component(snapshot));
+    }
+    url
+}
+
+/// Percent-encode everything but the RFC 3986 unreserved characters, so
+/// names with spaces, slashes or `&` survive as one path segment or value.
+fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_latest_snapshot_has_no_snapshot_parameter() {
+        let url = export_url(
+            &PlatformEdition::Java,
+            "All",
+            "Skywars",
+            "Wins",
+            None,
+            ExportFormat::Csv,
+        );
+        assert_eq!(
+            url,
+            "/api/v1/java/leaderboards/All/Skywars/Wins/export?format=csv"
+        );
+    }
+
+    #[test]
+    fn names_are_encoded_as_single_components() {
+        let url = export_url(
+            &PlatformEdition::Bedrock,
+            "All",
+            "Battle Box",
+            "Kills/Deaths",
+            Some("2024-01-01&x"),
+            ExportFormat::Ndjson,
+        );
+        assert_eq!(
+            url,
+            "/api/v1/bedrock/leaderboards/All/Battle%20Box/Kills%2FDeaths/export\
+             ?format=ndjson&snapshot=2024-01-01%26x"
+        );
+        assert_eq!(encode_component("é"), "%C3%A9");
+    }
+}
@@ -1,2 +1,5 @@
+pub mod compare;
+pub mod export;
 pub mod percent;
 pub mod score_formatter;
+pub mod snapshot_diff;
@@ -15,6 +15,33 @@ pub fn format_percent(value: f64) -> String {
     }
 }
 
+/// How far up a leaderboard of `total` rows `rank` sits, as "top X%".
+///
+/// Rounded up to two significant digits, so the share is never flattered:
+/// rank 31 of 1000 is the top 3.1%, rank 3334 of 100000 the top 3.4%.
+/// `None` for an unranked player or an empty board.
+pub fn format_top_percent(rank: u32, total: u32) -> Option<String> {
+    if rank == 0 || total == 0 {
+        return None;
+    }
+    let rank = u128::from(rank.min(total));
+    let total = u128::from(total);
+
+    // Round in whole steps of 10^exponent percent, in integers so that a share
+    // such as 3.1% is not pushed up to 3.2% by float error. Never finer than
+    // `format_percent` shows.
+    let share = rank as f64 * 100.0 / total as f64;
+    let exponent = (share.log10().floor() as i32 - 1).max(-5);
+    let rounded = if exponent < 0 {
+        let scale = 10u128.pow(exponent.unsigned_abs());
+        (rank * 100 * scale).div_ceil(total) as f64 / scale as f64
+    } else {
+        let step = 10u128.pow(exponent as u32);
+        ((rank * 100).div_ceil(total * step) * step) as f64
+    };
+    Some(format!("top {}", format_percent(rounded)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,4 +63,20 @@ mod tests {
     fn rounds_to_max_decimals() {
         assert_eq!(format_percent(99.999_999), "100%");
     }
+
+    #[test]
+    fn top_percent_rounds_up_to_two_digits() {
+        assert_eq!(format_top_percent(31, 1000).as_deref(), Some("top 3.1%"));
+        assert_eq!(
+            format_top_percent(3334, 100_000).as_deref(),
+            Some("top 3.4%")
+        );
+        assert_eq!(
+            format_top_percent(1, 1_000_000).as_deref(),
+            Some("top 0.0001%")
+        );
+        assert_eq!(format_top_percent(999, 1000).as_deref(), Some("top 100%"));
+        assert_eq!(format_top_percent(0, 1000), None);
+        assert_eq!(format_top_percent(5, 0), None);
+    }
 }
@@ -0,0 +1,206 @@
+use mp_stats_core::models::LeaderboardEntry;
+use smol_str::SmolStr;
+use std::collections::{HashMap, HashSet};
+
+/// A player's place on one snapshot of a leaderboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Standing {
+    pub rank: u32,
+    pub score: u64,
+}
+
+/// One player across two snapshots of the same board/game/stat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffRow {
+    pub uuid: SmolStr,
+    /// The name on the later snapshot, or the earlier one for a player who
+    /// dropped off.
+    pub name: SmolStr,
+    /// `None` for a new entrant.
+    pub before: Option<Standing>,
+    /// `None` for a player who dropped off.
+    pub after: Option<Standing>,
+}
+
+impl DiffRow {
+    pub fn is_new(&self) -> bool {
+        self.before.is_none()
+    }
+
+    pub fn has_dropped(&self) -> bool {
+        self.after.is_none()
+    }
+
+    /// How many places the player climbed (positive) or fell, when they are
+    /// on both snapshots.
+    pub fn rank_change(&self) -> Option<i64> {
+        let (before, after) = (self.before?, self.after?);
+        Some(i64::from(before.rank) - i64::from(after.rank))
+    }
+
+    /// How far the player's score rose (positive) or fell, when they are on
+    /// both snapshots.
+    pub fn score_change(&self) -> Option<i128> {
+        let (before, after) = (self.before?, self.after?);
+        Some(i128::from(after.score) - i128::from(before.score))
+    }
+}
+
+/// Every player on either snapshot, paired up by UUID: those on `after` in
+/// its rank order, then those who dropped off in their `before` rank order.
+///
+/// Entries ranked below `depth` are left out of both sides, so when only the
+/// top of a long board was loaded, a player just past the loaded pages is not
+/// mistaken for a new entrant or for one who dropped off.
+pub fn diff_snapshots(
+    before: &[LeaderboardEntry],
+    after: &[LeaderboardEntry],
+    depth: Option<u32>,
+) -> Vec<DiffRow> {
+    let within = |entry: &&LeaderboardEntry| depth.is_none_or(|depth| entry.rank <= depth);
+    let standing = |entry: &LeaderboardEntry| Standing {
+        rank: entry.rank,
+        score: entry.score,
+    };
+
+    let mut earlier: HashMap<&str, &LeaderboardEntry> = HashMap::new();
+    for entry in before.iter().filter(within) {
+        // Pages come in rank order, so a player listed twice keeps their best place.
+        earlier.entry(entry.uuid.as_str()).or_insert(entry);
+    }
+
+    let mut rows = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut later: Vec<&LeaderboardEntry> = after.iter().filter(within).collect();
+    later.sort_by_key(|entry| entry.rank);
+    for entry in later {
+        if !seen.insert(entry.uuid.as_str()) {
+            continue;
+        }
+        rows.push(DiffRow {
+            uuid: entry.uuid.clone(),
+            name: entry.name.clone(),
+            before: earlier.get(entry.uuid.as_str()).map(|e| standing(e)),
+            after: Some(standing(entry)),
+        });
+    }
+
+    let mut dropped: Vec<&LeaderboardEntry> = earlier
+        .into_values()
+        .filter(|entry| !seen.contains(entry.uuid.as_str()))
+        .collect();
+    dropped.sort_by_key(|entry| entry.rank);
+    rows.extend(dropped.into_iter().map(|entry| DiffRow {
+        uuid: entry.uuid.clone(),
+        name: entry.name.clone(),
+        before: Some(standing(entry)),
+        after: None,
+    }));
+
+    rows
+}
+
+/// The deepest rank both snapshots were loaded to, or `None` when both were
+/// loaded whole. `complete` tells whether each side holds every page.
+pub fn compared_depth(
+    before: &[LeaderboardEntry],
+    before_complete: bool,
+    after: &[LeaderboardEntry],
+    after_complete: bool,
+) -> Option<u32> {
+    let last_rank = |entries: &[LeaderboardEntry]| entries.iter().map(|e| e.rank).max();
+    [(before, before_complete), (after, after_complete)]
+        .into_iter()
+        .filter(|(_, complete)| !complete)
+        .map(|(entries, _)| last_rank(entries).unwrap_or(0))
+        .min()
+}
+
+/// How many players climbed, fell, entered and dropped off.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffSummary {
+    pub climbed: usize,
+    pub fell: usize,
+    pub entered: usize,
+    pub dropped: usize,
+}
+
+pub fn summarize(rows: &[DiffRow]) -> DiffSummary {
+    let mut summary = DiffSummary::default();
+    for row in rows {
+        match (row.is_new(), row.has_dropped(), row.rank_change()) {
+            (true, _, _) => summary.entered += 1,
+            (_, true, _) => summary.dropped += 1,
+            (_, _, Some(places)) if places > 0 => summary.climbed += 1,
+            (_, _, Some(places)) if places < 0 => summary.fell += 1,
+            _ => {}
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(rank: u32, uuid: &str, score: u64) -> LeaderboardEntry {
+        LeaderboardEntry {
+            rank,
+            uuid: uuid.into(),
+            name: uuid.to_uppercase().into(),
+            score,
+        }
+    }
+
+    #[test]
+    fn players_are_paired_by_uuid() {
+        let before = [entry(1, "a", 30), entry(2, "b", 20), entry(3, "c", 10)];
+        let after = [entry(1, "b", 40), entry(2, "d", 35), entry(3, "a", 31)];
+        let rows = diff_snapshots(&before, &after, None);
+
+        let uuids: Vec<_> = rows.iter().map(|r| r.uuid.as_str()).collect();
+        assert_eq!(uuids, ["b", "d", "a", "c"]);
+
+        assert_eq!(rows[0].rank_change(), Some(1));
+        assert_eq!(rows[0].score_change(), Some(20));
+        assert!(rows[1].is_new());
+        assert_eq!(rows[1].rank_change(), None);
+        assert_eq!(rows[2].rank_change(), Some(-2));
+        assert!(rows[3].has_dropped());
+        assert_eq!(rows[3].before, Some(Standing { rank: 3, score: 10 }));
+
+        assert_eq!(
+            summarize(&rows),
+            DiffSummary {
+                climbed: 1,
+                fell: 1,
+                entered: 1,
+                dropped: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn entries_past_the_depth_are_left_out() {
+        let before = [entry(1, "a", 30), entry(2, "b", 20), entry(3, "c", 10)];
+        let after = [entry(1, "a", 30), entry(2, "c", 25)];
+        let rows = diff_snapshots(&before, &after, Some(2));
+
+        // `c` climbed into the compared range; `b` fell out of it.
+        let uuids: Vec<_> = rows.iter().map(|r| r.uuid.as_str()).collect();
+        assert_eq!(uuids, ["a", "c", "b"]);
+        assert!(rows[1].is_new());
+        assert!(rows[2].has_dropped());
+    }
+
+    #[test]
+    fn the_depth_is_set_by_the_shallower_partial_side() {
+        let long = [entry(1, "a", 3), entry(2, "b", 2), entry(3, "c", 1)];
+        let short = [entry(1, "a", 3), entry(2, "b", 2)];
+
+        assert_eq!(compared_depth(&long, true, &short, true), None);
+        assert_eq!(compared_depth(&long, true, &short, false), Some(2));
+        assert_eq!(compared_depth(&long, false, &short, false), Some(2));
+        assert_eq!(compared_depth(&long, false, &[], false), Some(0));
+    }
+}
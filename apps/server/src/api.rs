@@ -0,0 +1,958 @@
+//! The JSON API under `/api/v1`: the converter's output, decoded on the server.
+//!
+//! The frontend reads the `.bin.xz` files under `/data` directly and decodes them in WASM. That
+//! suits a browser that already links the decoder, but not a script, a bot or a spreadsheet,
+//! each of which would need LZMA and postcard to read a single leaderboard page. These routes
+//! read the very same files through [`read_lzma_bin`] and answer with the JSON rendering of the
+//! same models, so the two surfaces cannot disagree: there is no second copy of the data to
+//! drift.
+//!
+//! Each request reads from the generation that was current when it arrived (see
+//! [`crate::generation`]), so a page and the shard it links to cannot come from two different
+//! conversions.
+//!
+//! Every path is resolved through [`mp_stats_core::routes`], the module the frontend fetches
+//! through, rather than spelled out again here.
+
+use crate::generation::{Generation, Generations};
+use axum::body::{Body, Bytes};
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use mp_stats_common::DataError;
+use mp_stats_common::compression::read_lzma_bin;
+use mp_stats_common::shard::{position_shard, uuid_shard};
+use mp_stats_core::models::{
+    GameLeaderboardData, IdMap, LeaderboardEntry, LeaderboardPage, LeaderboardPosition,
+    PlatformEdition, PlatformMeta, PlayerHistory, PlayerProfile, ScorePlacement, place_score,
+    rank_within_page,
+};
+use mp_stats_core::routes;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The routes, relative to wherever they are nested (`/api/v1` in [`crate::router`]).
+pub(crate) fn router(generations: Arc<Generations>) -> Router {
+    let state = ApiState { generations };
+
+    Router::new()
+        .route("/{edition}/games", get(games))
+        .route("/{edition}/games/{game}", get(game))
+        .route(
+            "/{edition}/leaderboards/{board}/{game}/{stat}",
+            get(leaderboard),
+        )
+        .route(
+            "/{edition}/leaderboards/{board}/{game}/{stat}/export",
+            get(export_leaderboard),
+        )
+        .route(
+            "/{edition}/leaderboards/{board}/{game}/{stat}/positions/{uuid}",
+            get(leaderboard_position),
+        )
+        .route(
+            "/{edition}/leaderboards/{board}/{game}/{stat}/rank",
+            get(rank_for_score),
+        )
+        .route("/{edition}/players/{uuid}", get(player))
+        .route("/{edition}/players/{uuid}/history", get(player_history))
+        .with_state(state)
+}
+
+#[derive(Clone)]
+struct ApiState {
+    generations: Arc<Generations>,
+}
+
+impl ApiState {
+    /// What the request at hand reads from: the generation current now, held until the request
+    /// is done with it.
+    fn data(&self) -> Data {
+        Data(self.generations.current())
+    }
+}
+
+/// One generation of the output, read through for the whole of a request.
+#[derive(Clone)]
+struct Data(Arc<Generation>);
+
+impl Data {
+    /// Decode the file at `route` (relative to the generation's root) off the async runtime.
+    ///
+    /// LZMA is CPU-bound and the read is blocking, so both go to the blocking pool rather than
+    /// stalling a worker that is also serving `/data` and the SPA.
+    async fn decode<T>(&self, route: String) -> Result<T, ApiError>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let path = self.0.root.join(route);
+        tokio::task::spawn_blocking(move || read_lzma_bin::<T>(&path))
+            .await
+            .map_err(|e| ApiError::Internal(format!("decoding task failed: {e}")))?
+            .map_err(ApiError::from)
+    }
+}
+
+/// `GET /{edition}/games`: every game of the edition, sorted by name.
+async fn games(
+    State(state): State<ApiState>,
+    UrlPath(edition): UrlPath<String>,
+) -> Result<Json<PlatformMeta>, ApiError> {
+    let data = state.data();
+    let edition = parse_edition(&edition)?;
+    let id_map: IdMap = data.decode(routes::meta_map_bin(&edition)).await?;
+
+    Ok(Json(PlatformMeta::from_id_map(id_map)))
+}
+
+/// `GET /{edition}/games/{game}`: a game's boards, stats and their snapshots.
+async fn game(
+    State(state): State<ApiState>,
+    UrlPath((edition, game)): UrlPath<(String, String)>,
+) -> Result<Json<GameLeaderboardData>, ApiError> {
+    let data = state.data();
+    let edition = parse_edition(&edition)?;
+    let game = segment(&game)?;
+
+    data.decode(routes::game_bin(&edition, game))
+        .await
+        .map(Json)
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaderboardQuery {
+    /// 1-based, like the page numbers in the frontend's URLs.
+    #[serde(default = "LeaderboardQuery::first_page")]
+    page: u32,
+    /// A history snapshot id; the latest snapshot when absent.
+    snapshot: Option<String>,
+}
+
+impl LeaderboardQuery {
+    fn first_page() -> u32 {
+        1
+    }
+}
+
+/// One snapshot of one board/game/stat: where its pages are.
+#[derive(Debug, Clone)]
+struct LeaderboardSnapshot {
+    edition: PlatformEdition,
+    board: String,
+    game: String,
+    stat: String,
+    /// A history snapshot id; the latest snapshot when `None`.
+    snapshot: Option<String>,
+}
+
+impl LeaderboardSnapshot {
+    fn parse(
+        (edition, board, game, stat): (String, String, String, String),
+        snapshot: Option<String>,
+    ) -> Result<Self, ApiError> {
+        let edition = parse_edition(&edition)?;
+        segment(&board)?;
+        segment(&game)?;
+        segment(&stat)?;
+        if let Some(snapshot) = &snapshot {
+            segment(snapshot)?;
+        }
+
+        Ok(Self {
+            edition,
+            board,
+            game,
+            stat,
+            snapshot,
+        })
+    }
+
+    /// The route of the position index shard `shard`.
+    fn positions(&self, shard: &str) -> String {
+        let (board, game, stat) = (&self.board, &self.game, &self.stat);
+        match &self.snapshot {
+            None => routes::leaderboard_positions_bin(&self.edition, board, game, stat, shard),
+            Some(snapshot) => routes::history_leaderboard_positions_bin(
+                &self.edition,
+                board,
+                game,
+                stat,
+                snapshot,
+                shard,
+            ),
+        }
+    }
+
+    /// The route of page `chunk`, counted from 0.
+    fn page(&self, chunk: u32) -> String {
+        let (board, game, stat) = (&self.board, &self.game, &self.stat);
+        match &self.snapshot {
+            None => routes::leaderboard_chunk_bin(&self.edition, board, game, stat, chunk),
+            Some(snapshot) => routes::history_leaderboard_chunk_bin(
+                &self.edition,
+                board,
+                game,
+                stat,
+                snapshot,
+                chunk,
+            ),
+        }
+    }
+}
+
+/// `GET /{edition}/leaderboards/{board}/{game}/{stat}?page=&snapshot=`: one page of entries.
+async fn leaderboard(
+    State(state): State<ApiState>,
+    UrlPath(path): UrlPath<(String, String, String, String)>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<Vec<LeaderboardEntry>>, ApiError> {
+    let data = state.data();
+    let snapshot = LeaderboardSnapshot::parse(path, query.snapshot)?;
+
+    let Some(chunk) = query.page.checked_sub(1) else {
+        return Err(ApiError::BadRequest("`page` starts at 1".into()));
+    };
+
+    let page: LeaderboardPage = data.decode(snapshot.page(chunk)).await?;
+    Ok(Json(page.into_entries()))
+}
+
+#[derive(Debug, Deserialize)]
+struct PositionQuery {
+    /// A history snapshot id; the latest snapshot when absent.
+    snapshot: Option<String>,
+}
+
+/// `GET /{edition}/leaderboards/{board}/{game}/{stat}/positions/{uuid}?snapshot=`: the page,
+/// row and rank a player has on a snapshot.
+async fn leaderboard_position(
+    State(state): State<ApiState>,
+    UrlPath((edition, board, game, stat, uuid)): UrlPath<(String, String, String, String, String)>,
+    Query(query): Query<PositionQuery>,
+) -> Result<Json<LeaderboardPosition>, ApiError> {
+    let data = state.data();
+    let snapshot = LeaderboardSnapshot::parse((edition, board, game, stat), query.snapshot)?;
+    let shard = position_shard(&uuid).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let shard = segment(&shard)?;
+
+    let positions: HashMap<String, LeaderboardPosition> =
+        data.decode(snapshot.positions(shard)).await?;
+
+    positions
+        .get(&uuid)
+        .copied()
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("{uuid} is not on this leaderboard")))
+}
+
+#[derive(Debug, Deserialize)]
+struct RankQuery {
+    score: u64,
+    /// A history snapshot id; the latest snapshot when absent.
+    snapshot: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct ScoreRank {
+    score: u64,
+    rank: u32,
+}
+
+/// `GET /{edition}/leaderboards/{board}/{game}/{stat}/rank?score=&snapshot=`: the competition
+/// rank `score` would have had on a snapshot.
+///
+/// Placed through the page score ranges in the game's metadata, so at most one page is read.
+async fn rank_for_score(
+    State(state): State<ApiState>,
+    UrlPath(path): UrlPath<(String, String, String, String)>,
+    Query(query): Query<RankQuery>,
+) -> Result<Json<ScoreRank>, ApiError> {
+    let data = state.data();
+    let snapshot = LeaderboardSnapshot::parse(path, query.snapshot)?;
+    let score = query.score;
+
+    let game: GameLeaderboardData = data
+        .decode(routes::game_bin(&snapshot.edition, &snapshot.game))
+        .await?;
+    let snapshot_id = snapshot.snapshot.as_deref().unwrap_or("latest");
+    let meta = game
+        .stats
+        .get(snapshot.stat.as_str())
+        .and_then(|boards| boards.get(snapshot.board.as_str()))
+        .and_then(|meta| meta.snapshots.iter().find(|s| s.snapshot_id == snapshot_id))
+        .ok_or_else(|| ApiError::NotFound("no such leaderboard snapshot".into()))?;
+    // Snapshots converted before score ranges were recorded cannot be placed: with no ranges,
+    // every score would land ahead of the whole board.
+    if meta.page_scores.is_empty() && meta.total_entries > 0 {
+        return Err(ApiError::Conflict(
+            "this snapshot has no score ranges; re-convert the data to rank scores".into(),
+        ));
+    }
+
+    let rank = match place_score(&meta.page_scores, score) {
+        ScorePlacement::Rank(rank) => rank,
+        ScorePlacement::InPage { page, ahead } => {
+            let page: LeaderboardPage = data.decode(snapshot.page(page)).await?;
+            rank_within_page(&page.scores, ahead, score)
+        }
+    };
+
+    Ok(Json(ScoreRank { score, rank }))
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    /// A history snapshot id; the latest snapshot when absent.
+    snapshot: Option<String>,
+}
+
+/// What a leaderboard is exported as.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    /// `rank,uuid,name,score` with a header row.
+    #[default]
+    Csv,
+    /// One [`LeaderboardEntry`] JSON object per line.
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    /// `page`'s rows, preceded by the header when it is the first page.
+    fn render(self, page: LeaderboardPage, first: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            ExportFormat::Csv => {
+                if first {
+                    out.extend_from_slice(b"rank,uuid,name,score\n");
+                }
+                for entry in page.into_entries() {
+                    let row = format!(
+                        "{},{},{},{}\n",
+                        entry.rank,
+                        csv_field(&entry.uuid),
+                        csv_field(&entry.name),
+                        entry.score
+                    );
+                    out.extend_from_slice(row.as_bytes());
+                }
+            }
+            ExportFormat::Ndjson => {
+                for entry in page.into_entries() {
+                    // A struct of strings and integers always serializes.
+                    serde_json::to_writer(&mut out, &entry).expect("an entry serializes");
+                    out.push(b'\n');
+                }
+            }
+        }
+        out
+    }
+}
+
+/// `value` as one CSV field, quoted when it holds a separator, a quote or a line break.
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+/// `GET /{edition}/leaderboards/{board}/{game}/{stat}/export?format=&snapshot=`: every page of
+/// a snapshot as one CSV or NDJSON download.
+///
+/// Pages are read one at a time as the body is sent, so a board of a million rows never sits
+/// in memory whole. The first page is read before answering, so a board that does not exist is
+/// still a 404; a page that fails to decode later can only cut the download short, and does.
+/// Every page comes from the generation current when the request arrived, so a conversion
+/// published partway through the download does not mix into it.
+async fn export_leaderboard(
+    State(state): State<ApiState>,
+    UrlPath(path): UrlPath<(String, String, String, String)>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    let data = state.data();
+    let snapshot = LeaderboardSnapshot::parse(path, query.snapshot)?;
+    let format = query.format;
+    let first: LeaderboardPage = data.decode(snapshot.page(0)).await?;
+
+    let filename = format!(
+        "{}-{}-{}-{}.{}",
+        snapshot.game,
+        snapshot.board,
+        snapshot.stat,
+        snapshot.snapshot.as_deref().unwrap_or("latest"),
+        format.extension()
+    )
+    .replace(['"', '\\'], "_");
+
+    // `(page already read, its index)`, or `None` once the last page is out.
+    let cursor = Some((Some(first), 0u32));
+    let pages = futures::stream::unfold(cursor, move |cursor| {
+        let data = data.clone();
+        let snapshot = snapshot.clone();
+        async move {
+            let (read, chunk) = cursor?;
+            let page = match read {
+                Some(page) => page,
+                None => match data.decode::<LeaderboardPage>(snapshot.page(chunk)).await {
+                    Ok(page) => page,
+                    // Past the last page.
+                    Err(ApiError::NotFound(_)) => return None,
+                    Err(e) => {
+                        eprintln!("error: api: export stopped at page {chunk}: {e:?}");
+                        let cut = std::io::Error::other("export stopped");
+                        return Some((Err(cut), None));
+                    }
+                },
+            };
+            let rows = Bytes::from(format.render(page, chunk == 0));
+            Some((Ok::<_, std::io::Error>(rows), Some((None, chunk + 1))))
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        Body::from_stream(pages),
+    )
+        .into_response())
+}
+
+/// `GET /{edition}/players/{uuid}`: a player's profile, looked up in its shard.
+async fn player(
+    State(state): State<ApiState>,
+    UrlPath((edition, uuid)): UrlPath<(String, String)>,
+) -> Result<Json<PlayerProfile>, ApiError> {
+    let data = state.data();
+    let edition = parse_edition(&edition)?;
+    let shard = uuid_shard(&uuid).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let shard = segment(&shard)?;
+
+    let mut profiles: HashMap<String, PlayerProfile> = data
+        .decode(routes::player_shard_bin(&edition, shard))
+        .await?;
+
+    // Answered under the uuid that was asked for, the way the frontend's own lookup does, so
+    // a profile written without one still comes back complete.
+    let mut profile = profiles
+        .remove(&uuid)
+        .ok_or_else(|| ApiError::NotFound(format!("no profile for {uuid}")))?;
+    profile.uuid = uuid.into();
+
+    Ok(Json(profile))
+}
+
+/// `GET /{edition}/players/{uuid}/history`: a player's score and rank at every snapshot.
+async fn player_history(
+    State(state): State<ApiState>,
+    UrlPath((edition, uuid)): UrlPath<(String, String)>,
+) -> Result<Json<PlayerHistory>, ApiError> {
+    let data = state.data();
+    let edition = parse_edition(&edition)?;
+    let shard = uuid_shard(&uuid).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let shard = segment(&shard)?;
+
+    let mut histories: HashMap<String, PlayerHistory> = data
+        .decode(routes::player_history_bin(&edition, shard))
+        .await?;
+
+    let history = histories
+        .remove(&uuid)
+        .ok_or_else(|| ApiError::NotFound(format!("no history for {uuid}")))?;
+
+    Ok(Json(history))
+}
+
+fn parse_edition(edition: &str) -> Result<PlatformEdition, ApiError> {
+    edition
+        .parse()
+        .map_err(|e: Box<dyn std::error::Error>| ApiError::NotFound(e.to_string()))
+}
+
+/// A path parameter that is about to become one component of a file path.
+///
+/// Axum percent-decodes parameters, so `%2e%2e` arrives here as `..`; without this check a
+/// request could walk out of the data directory.
+fn segment(value: &str) -> Result<&str, ApiError> {
+    let traverses =
+        value.is_empty() || value == "." || value == ".." || value.contains(['/', '\\', '\0']);
+
+    if traverses {
+        Err(ApiError::BadRequest(format!(
+            "invalid path segment '{value}'"
+        )))
+    } else {
+        Ok(value)
+    }
+}
+
+/// What a handler answers when it has no model to answer with.
+#[derive(Debug)]
+enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    /// The data exists but was converted by a converter too old to answer the request.
+    Conflict(String),
+    Internal(String),
+}
+
+impl From<DataError> for ApiError {
+    fn from(error: DataError) -> Self {
+        match error {
+            // Raised by `read_lzma_raw` when the file cannot be opened, which for a
+            // well-formed request means the board, page, snapshot or shard does not exist.
+            DataError::FileNotFound(_) => ApiError::NotFound("not found".into()),
+            other => ApiError::Internal(other.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ApiError::Conflict(message) => (StatusCode::CONFLICT, message),
+            ApiError::Internal(message) => {
+                // The detail names files on this host, so it goes to the log and not the client.
+                eprintln!("error: api: {message}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error".into())
+            }
+        };
+
+        (status, Json(ErrorBody { error })).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use mp_stats_common::compression::write_lzma_bin;
+    use mp_stats_common::generation;
+    use mp_stats_core::models::{
+        HistoricalSnapshot, HistoryPoint, IdMapValue, LeaderboardMeta, PageScoreRange, StatHistory,
+        StatRaw,
+    };
+    use std::path::{Path, PathBuf};
+    use tower::ServiceExt as _;
+
+    /// A data directory holding one game, one leaderboard page, one player shard and its
+    /// history shard.
+    fn fixture() -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("mp_stats_api_{nanos}"));
+        let edition = PlatformEdition::Java;
+
+        let value = |name: &str| IdMapValue {
+            name: name.into(),
+            description: None,
+            total_snapshots: 0,
+        };
+        let id_map = IdMap {
+            boards: HashMap::from([(1, value("All"))]),
+            games: HashMap::from([(1, value("Skywars")), (2, value("Bridges"))]),
+            stats: HashMap::from([(1, value("Wins"))]),
+        };
+        write(&dir, routes::meta_map_bin(&edition), &id_map);
+
+        let page = LeaderboardPage {
+            ranks: vec![1, 2],
+            uuids: vec!["aaa-1".into(), "bbb-2".into()],
+            names: vec!["Alpha".into(), "Bravo".into()],
+            scores: vec![20, 10],
+        };
+        write(
+            &dir,
+            routes::leaderboard_chunk_bin(&edition, "All", "Skywars", "Wins", 0),
+            &page,
+        );
+
+        let snapshot = HistoricalSnapshot {
+            snapshot_id: "latest".into(),
+            timestamp: 1,
+            total_pages: 1,
+            total_entries: 2,
+            page_scores: vec![PageScoreRange {
+                max: 20,
+                min: 10,
+                entries: 2,
+            }],
+            distribution: None,
+        };
+        let meta = LeaderboardMeta {
+            snapshots: vec![snapshot],
+            top: None,
+        };
+        let game = GameLeaderboardData {
+            game_id: "1".into(),
+            game_name: "Skywars".into(),
+            description: None,
+            icon: None,
+            stats: HashMap::from([("Wins".into(), HashMap::from([("All".into(), meta)]))]),
+            total_entries: 2,
+            total_snapshots: 1,
+        };
+        write(&dir, routes::game_bin(&edition, "Skywars"), &game);
+
+        let position = |row, rank| LeaderboardPosition { page: 0, row, rank };
+        let positions = HashMap::from([("aaa-1".to_string(), position(0, 1))]);
+        write(
+            &dir,
+            routes::leaderboard_positions_bin(&edition, "All", "Skywars", "Wins", "A"),
+            &positions,
+        );
+        let positions = HashMap::from([("bbb-2".to_string(), position(1, 2))]);
+        write(
+            &dir,
+            routes::leaderboard_positions_bin(&edition, "All", "Skywars", "Wins", "B"),
+            &positions,
+        );
+
+        let profile = PlayerProfile {
+            uuid: "".into(),
+            name: Some("Alpha".into()),
+            stats: vec![StatRaw {
+                board_id: 1,
+                game_id: 1,
+                stat_id: 1,
+                score: 20,
+                rank: 1,
+                save_time: 0,
+            }],
+            name_history: vec![],
+        };
+        let shard = HashMap::from([("aaa-1".to_string(), profile)]);
+        write(&dir, routes::player_shard_bin(&edition, "AAA"), &shard);
+
+        let history = PlayerHistory {
+            uuid: "aaa-1".into(),
+            stats: vec![StatHistory {
+                board: "All".into(),
+                game: "Skywars".into(),
+                stat: "Wins".into(),
+                points: vec![HistoryPoint {
+                    snapshot_id: "latest".into(),
+                    timestamp: 1,
+                    score: 20,
+                    rank: 1,
+                }],
+            }],
+        };
+        let shard = HashMap::from([("aaa-1".to_string(), history)]);
+        write(&dir, routes::player_history_bin(&edition, "AAA"), &shard);
+
+        dir
+    }
+
+    fn write<T: Serialize>(dir: &Path, route: String, data: &T) {
+        write_lzma_bin(&dir.join(route), data).unwrap();
+    }
+
+    async fn get(dir: &Path, uri: &str) -> (StatusCode, Vec<u8>) {
+        let response = router(Arc::new(Generations::open(dir.to_path_buf())))
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn games_are_listed_by_name() {
+        let dir = fixture();
+        let (status, body) = get(&dir, "/java/games").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let meta: PlatformMeta = serde_json::from_slice(&body).unwrap();
+        let names: Vec<_> = meta.games.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["Bridges", "Skywars"]);
+    }
+
+    #[tokio::test]
+    async fn a_leaderboard_page_is_answered_as_rows() {
+        let dir = fixture();
+        let (status, body) = get(&dir, "/java/leaderboards/All/Skywars/Wins?page=1").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let entries: Vec<LeaderboardEntry> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "Alpha");
+        assert_eq!(entries[1].rank, 2);
+    }
+
+    /// A page past the end is a missing file, which is a 404 and not a server error.
+    #[tokio::test]
+    async fn a_missing_page_is_not_found() {
+        let dir = fixture();
+        let (status, _) = get(&dir, "/java/leaderboards/All/Skywars/Wins?page=2").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(&dir, "/java/leaderboards/All/Skywars/Wins?page=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    /// Every page is exported, in order, under one header.
+    #[tokio::test]
+    async fn a_leaderboard_is_exported_as_csv() {
+        let dir = fixture();
+        let page = LeaderboardPage {
+            ranks: vec![3],
+            uuids: vec!["ccc-3".into()],
+            names: vec!["Charlie, \"C\"".into()],
+            scores: vec![5],
+        };
+        write(
+            &dir,
+            routes::leaderboard_chunk_bin(&PlatformEdition::Java, "All", "Skywars", "Wins", 1),
+            &page,
+        );
+
+        let (status, body) = get(&dir, "/java/leaderboards/All/Skywars/Wins/export").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "rank,uuid,name,score\n\
+             1,aaa-1,Alpha,20\n\
+             2,bbb-2,Bravo,10\n\
+             3,ccc-3,\"Charlie, \"\"C\"\"\",5\n"
+        );
+    }
+
+    /// A generation published after the first page went out is not read from.
+    #[tokio::test]
+    async fn an_export_reads_every_page_from_one_generation() {
+        let dir = fixture();
+        let page = |name: &str| LeaderboardPage {
+            ranks: vec![3],
+            uuids: vec!["ccc-3".into()],
+            names: vec![name.into()],
+            scores: vec![5],
+        };
+        let second =
+            routes::leaderboard_chunk_bin(&PlatformEdition::Java, "All", "Skywars", "Wins", 1);
+        for (id, name) in [("1", "Old"), ("2", "New")] {
+            let root = generation::generation_dir(&dir, id);
+            copy_tree(&dir.join("java"), &root.join("java"));
+            write(&root, second.clone(), &page(name));
+        }
+        generation::publish(&dir, "1").unwrap();
+
+        let generations = Arc::new(Generations::open(dir.clone()));
+        let response = router(generations.clone())
+            .oneshot(
+                Request::get("/java/leaderboards/All/Skywars/Wins/export")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        generation::publish(&dir, "2").unwrap();
+        assert!(generations.reload().unwrap().is_some());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        assert!(csv.ends_with("3,ccc-3,Old,5\n"), "{csv}");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    fn copy_tree(from: &Path, to: &Path) {
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let target = to.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_tree(&entry.path(), &target);
+            } else {
+                std::fs::create_dir_all(to).unwrap();
+                std::fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_leaderboard_is_exported_as_ndjson() {
+        let dir = fixture();
+        let (status, body) = get(
+            &dir,
+            "/java/leaderboards/All/Skywars/Wins/export?format=ndjson",
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        let entries: Vec<LeaderboardEntry> = String::from_utf8(body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "Bravo");
+        assert_eq!(entries[1].score, 10);
+    }
+
+    #[tokio::test]
+    async fn exporting_a_missing_board_is_not_found() {
+        let dir = fixture();
+        let (status, _) = get(&dir, "/java/leaderboards/All/Skywars/Kills/export").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(
+            &dir,
+            "/java/leaderboards/All/Skywars/Wins/export?snapshot=2020-01-01",
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(
+            &dir,
+            "/java/leaderboards/All/Skywars/Wins/export?format=xml",
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn a_player_position_is_found_in_its_shard() {
+        let dir = fixture();
+        let (status, body) = get(&dir, "/java/leaderboards/All/Skywars/Wins/positions/bbb-2").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let position: LeaderboardPosition = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            position,
+            LeaderboardPosition {
+                page: 0,
+                row: 1,
+                rank: 2
+            }
+        );
+
+        // In a shard that exists, and in one that does not.
+        let (status, _) = get(&dir, "/java/leaderboards/All/Skywars/Wins/positions/aaa-9").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(&dir, "/java/leaderboards/All/Skywars/Wins/positions/ccc-3").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    /// Scores above or below every page are ranked from the ranges alone; one inside a page
+    /// shares the rank of the rows it ties with.
+    #[tokio::test]
+    async fn a_score_is_ranked_on_the_snapshot() {
+        let dir = fixture();
+        for (score, rank) in [(25, 1), (20, 1), (15, 2), (10, 2), (5, 3)] {
+            let uri = format!("/java/leaderboards/All/Skywars/Wins/rank?score={score}");
+            let (status, body) = get(&dir, &uri).await;
+
+            assert_eq!(status, StatusCode::OK);
+            let ranked: ScoreRank = serde_json::from_slice(&body).unwrap();
+            assert_eq!(ranked, ScoreRank { score, rank });
+        }
+
+        let (status, _) = get(
+            &dir,
+            "/java/leaderboards/All/Skywars/Wins/rank?score=5&snapshot=1700000000",
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(&dir, "/java/leaderboards/All/Skywars/Wins/rank").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    /// Without the page ranges every score would place first, so the snapshot is refused.
+    #[tokio::test]
+    async fn a_snapshot_without_score_ranges_is_not_ranked() {
+        let dir = fixture();
+        let route = routes::game_bin(&PlatformEdition::Java, "Skywars");
+        let mut game: GameLeaderboardData = read_lzma_bin(&dir.join(&route)).unwrap();
+        for meta in game
+            .stats
+            .values_mut()
+            .flat_map(|boards| boards.values_mut())
+        {
+            for snapshot in &mut meta.snapshots {
+                snapshot.page_scores.clear();
+            }
+        }
+        write(&dir, route, &game);
+
+        let (status, _) = get(&dir, "/java/leaderboards/All/Skywars/Wins/rank?score=5").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn a_player_is_found_in_its_shard() {
+        let dir = fixture();
+        let (status, body) = get(&dir, "/java/players/aaa-1").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let profile: PlayerProfile = serde_json::from_slice(&body).unwrap();
+        assert_eq!(profile.uuid, "aaa-1");
+        assert_eq!(profile.stats.len(), 1);
+
+        let (status, _) = get(&dir, "/java/players/aaa-9").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn a_player_history_is_found_in_its_shard() {
+        let dir = fixture();
+        let (status, body) = get(&dir, "/java/players/aaa-1/history").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let history: PlayerHistory = serde_json::from_slice(&body).unwrap();
+        assert_eq!(history.stats[0].points[0].score, 20);
+
+        let (status, _) = get(&dir, "/java/players/bbb-2/history").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn unknown_editions_and_traversal_are_rejected() {
+        let dir = fixture();
+
+        let (status, _) = get(&dir, "/switch/games").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(&dir, "/java/games/%2e%2e").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
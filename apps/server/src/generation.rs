@@ -0,0 +1,276 @@
+//! The converted output the server answers from, and switching to a newer one while running.
+//!
+//! The data directory may hold several complete outputs, with a pointer naming the current one
+//! (see [`mp_stats_common::generation`]). Every request takes the current [`Generation`] once,
+//! up front, and reads from it until it is done, so moving the pointer changes what the next
+//! request sees without pulling files out from under the ones in flight. The server notices a
+//! moved pointer by reading it every few seconds, or at once on `SIGHUP`.
+//!
+//! A generation is only switched to once it passes the readiness check; a pointer moved to a
+//! broken output is logged and ignored, and the server keeps answering from the one it has.
+//! The full check runs once per generation; the readiness probe then only rechecks what is
+//! cheap to (see [`crate::readiness`]).
+
+use crate::readiness;
+use mp_stats_common::generation;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// How often the pointer is read for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// One complete output, as it was when a request started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Generation {
+    /// `None` for a flat data directory, which has no pointer and never changes.
+    pub(crate) id: Option<String>,
+    /// The directory the routes in `mp_stats_core::routes` are relative to.
+    pub(crate) root: PathBuf,
+}
+
+/// The generation being served, shared by every handler.
+#[derive(Debug)]
+pub(crate) struct Generations {
+    data_dir: PathBuf,
+    current: RwLock<Arc<Generation>>,
+    /// The id of the last generation that passed the full readiness check.
+    verified: Mutex<Option<Option<String>>>,
+}
+
+impl Generations {
+    /// Serve whatever `data_dir`'s pointer names now. An unreadable pointer is not fatal: the
+    /// server starts on the flat layout, and readiness says whether there is anything there.
+    pub(crate) fn open(data_dir: PathBuf) -> Self {
+        let id = generation::read_current(&data_dir).unwrap_or_else(|e| {
+            eprintln!("error: generation: reading the pointer: {e}");
+            None
+        });
+        let current = Generation {
+            root: root(&data_dir, id.as_deref()),
+            id,
+        };
+
+        Self {
+            data_dir,
+            current: RwLock::new(Arc::new(current)),
+            verified: Mutex::new(None),
+        }
+    }
+
+    /// Whether `generation` can be served. The full check runs until the generation passes it
+    /// once, and only [`readiness::recheck`] after.
+    pub(crate) fn readiness(&self, generation: &Generation) -> readiness::Readiness {
+        let mut verified = self.verified.lock().unwrap_or_else(|e| e.into_inner());
+        if verified.as_ref() == Some(&generation.id) {
+            drop(verified);
+            return readiness::recheck(&generation.root);
+        }
+
+        let readiness = readiness::check(&generation.root);
+        if readiness.ready {
+            *verified = Some(generation.id.clone());
+        }
+        readiness
+    }
+
+    /// The generation to answer the request at hand from.
+    pub(crate) fn current(&self) -> Arc<Generation> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Switch to the generation the pointer names, if it moved and the output there is ready.
+    ///
+    /// Answers the generation switched to, `None` when the pointer has not moved, or why the
+    /// new generation was refused.
+    pub(crate) fn reload(&self) -> Result<Option<Arc<Generation>>, String> {
+        let id = generation::read_current(&self.data_dir).map_err(|e| e.to_string())?;
+        if self.current().id == id {
+            return Ok(None);
+        }
+
+        let next = Generation {
+            root: root(&self.data_dir, id.as_deref()),
+            id,
+        };
+        let readiness = readiness::check(&next.root);
+        if !readiness.ready {
+            let reasons: Vec<_> = readiness
+                .editions
+                .iter()
+                .filter_map(|e| {
+                    Some(format!(
+                        "{}: {}",
+                        e.edition.display_name(),
+                        e.error.as_ref()?
+                    ))
+                })
+                .collect();
+            return Err(format!(
+                "generation {} is not ready ({})",
+                next.id.as_deref().unwrap_or("(flat)"),
+                reasons.join("; ")
+            ));
+        }
+
+        *self.verified.lock().unwrap_or_else(|e| e.into_inner()) = Some(next.id.clone());
+        let next = Arc::new(next);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = next.clone();
+        Ok(Some(next))
+    }
+}
+
+fn root(data_dir: &Path, id: Option<&str>) -> PathBuf {
+    match id {
+        Some(id) => generation::generation_dir(data_dir, id),
+        None => data_dir.to_path_buf(),
+    }
+}
+
+/// Follow the pointer for as long as the server runs.
+pub(crate) fn watch(generations: Arc<Generations>) {
+    tokio::spawn(async move {
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(signal) => Some(signal),
+            Err(e) => {
+                eprintln!("error: generation: not listening for SIGHUP: {e}");
+                None
+            }
+        };
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+        let mut last_error = None;
+
+        loop {
+            tokio::select! {
+                _ = poll.tick() => {}
+                Some(()) = async { hangup.as_mut()?.recv().await } => {}
+            }
+
+            let generations = generations.clone();
+            let outcome = tokio::task::spawn_blocking(move || generations.reload())
+                .await
+                .unwrap_or_else(|e| Err(format!("reload task failed: {e}")));
+
+            match outcome {
+                Ok(Some(generation)) => {
+                    println!(
+                        "Serving generation {} from {}",
+                        generation.id.as_deref().unwrap_or("(flat)"),
+                        generation.root.display()
+                    );
+                    last_error = None;
+                }
+                Ok(None) => {}
+                // Said once, not every five seconds until someone fixes it.
+                Err(e) if last_error.as_ref() != Some(&e) => {
+                    eprintln!("error: generation: {e}");
+                    last_error = Some(e);
+                }
+                Err(_) => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mp_stats_common::compression::write_lzma_bin;
+    use mp_stats_common::manifest::Manifest;
+    use mp_stats_core::models::{IdMap, PlatformEdition};
+    use mp_stats_core::routes;
+    use std::collections::HashMap;
+
+    fn data_dir() -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("mp_stats_generations_{nanos}"))
+    }
+
+    /// A generation whose editions each carry an empty metadata map.
+    fn write_generation(data_dir: &Path, id: &str) {
+        let id_map = IdMap {
+            boards: HashMap::new(),
+            games: HashMap::new(),
+            stats: HashMap::new(),
+        };
+        for edition in PlatformEdition::iter() {
+            let path = generation::generation_dir(data_dir, id).join(routes::meta_map_bin(edition));
+            write_lzma_bin(&path, &id_map).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_published_generation_is_switched_to_and_held_by_earlier_readers() {
+        let dir = data_dir();
+        write_generation(&dir, "1");
+        generation::publish(&dir, "1").unwrap();
+
+        let generations = Generations::open(dir.clone());
+        let in_flight = generations.current();
+        assert_eq!(in_flight.id.as_deref(), Some("1"));
+        assert_eq!(generations.reload(), Ok(None));
+
+        write_generation(&dir, "2");
+        generation::publish(&dir, "2").unwrap();
+        let switched = generations.reload().unwrap().expect("the pointer moved");
+
+        assert_eq!(switched.id.as_deref(), Some("2"));
+        assert_eq!(
+            generations.current().root,
+            generation::generation_dir(&dir, "2")
+        );
+        assert_eq!(in_flight.root, generation::generation_dir(&dir, "1"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn a_generation_that_is_not_ready_is_refused() {
+        let dir = data_dir();
+        write_generation(&dir, "1");
+        generation::publish(&dir, "1").unwrap();
+        let generations = Generations::open(dir.clone());
+
+        std::fs::create_dir_all(generation::generation_dir(&dir, "2")).unwrap();
+        generation::publish(&dir, "2").unwrap();
+
+        assert!(generations.reload().is_err());
+        assert_eq!(generations.current().id.as_deref(), Some("1"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    /// A generation is walked until it passes once; after that only its maps are decoded.
+    #[test]
+    fn a_generation_is_size_checked_until_it_passes() {
+        let dir = data_dir();
+        std::fs::create_dir_all(generation::generation_dir(&dir, "1")).unwrap();
+        generation::publish(&dir, "1").unwrap();
+        let generations = Generations::open(dir.clone());
+        let current = generations.current();
+        assert!(!generations.readiness(&current).ready);
+
+        write_generation(&dir, "1");
+        let java = current.root.join(PlatformEdition::Java.directory_name());
+        Manifest::build(&java, 1, 0).unwrap().write(&java).unwrap();
+        assert!(generations.readiness(&current).ready);
+
+        std::fs::write(java.join("stray.bin"), b"x").unwrap();
+        assert!(!readiness::check(&current.root).ready);
+        assert!(generations.readiness(&current).ready);
+
+        std::fs::remove_file(
+            current
+                .root
+                .join(routes::meta_map_bin(&PlatformEdition::Java)),
+        )
+        .unwrap();
+        assert!(!generations.readiness(&current).ready);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,13 +1,20 @@
+mod api;
 mod config;
 mod csp;
+mod generation;
+mod readiness;
 
 use crate::config::Config;
+use crate::generation::Generations;
 use anyhow::{Context, Result};
 use axum::Router;
+use axum::extract::{Request, State};
 use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use axum::routing::get;
 use mp_stats_config::ServerConfig;
 use std::path::Path;
+use std::sync::Arc;
 use tower_http::services::{ServeDir, ServeFile};
 
 fn main() -> Result<()> {
@@ -37,10 +44,19 @@ async fn serve() -> Result<()> {
         )
     })?;
 
+    // The output is followed through its generation pointer from here on, so a finished
+    // conversion is picked up without a restart.
+    let generations = Arc::new(Generations::open(server.data_dir.clone()));
+    generation::watch(generations.clone());
+
     // Before the bind, because it reads that same index.html: a shell whose inline scripts
     // cannot be hashed would otherwise take the port and then serve a blank page.
-    let router = csp::attach(router(&server, &index_path), &server.csp, &index_path)
-        .context("assembling the Content-Security-Policy")?;
+    let router = csp::attach(
+        router(&server, &index_path, generations),
+        &server.csp,
+        &index_path,
+    )
+    .context("assembling the Content-Security-Policy")?;
 
     let listener = tokio::net::TcpListener::bind(server.bind_addr)
         .await
@@ -50,26 +66,40 @@ async fn serve() -> Result<()> {
     axum::serve(listener, router).await.context("serving")
 }
 
-/// Health probes, the converter's output under `/data`, and the SPA everywhere else.
-fn router(config: &ServerConfig, index_path: &Path) -> Router {
+/// Health probes (readiness checking the data, see [`readiness`]), the current generation of the
+/// converter's output under `/data` and decoded as JSON under `/api/v1`, and the SPA everywhere
+/// else.
+fn router(config: &ServerConfig, index_path: &Path, generations: Arc<Generations>) -> Router {
     let spa_service = ServeDir::new(&config.dist_dir).not_found_service(ServeFile::new(index_path));
 
     Router::new()
         .route("/health/startup", get(startup_probe))
         .route("/health/live", get(liveness_probe))
-        .route("/health/ready", get(readiness_probe))
-        .nest_service("/data", ServeDir::new(&config.data_dir))
+        .route(
+            "/health/ready",
+            get(readiness::probe).with_state(generations.clone()),
+        )
+        .nest_service("/data", get(data).with_state(generations.clone()))
+        .nest("/api/v1", api::router(generations))
         .fallback_service(spa_service)
 }
 
-async fn startup_probe() -> StatusCode {
-    StatusCode::OK
+/// A file under `/data`, from the generation current when the request arrived.
+async fn data(State(generations): State<Arc<Generations>>, request: Request) -> Response {
+    let generation = generations.current();
+    match ServeDir::new(&generation.root).try_call(request).await {
+        Ok(response) => response.into_response(),
+        Err(e) => {
+            eprintln!("error: data: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
 }
 
-async fn liveness_probe() -> StatusCode {
+async fn startup_probe() -> StatusCode {
     StatusCode::OK
 }
 
-async fn readiness_probe() -> StatusCode {
+async fn liveness_probe() -> StatusCode {
     StatusCode::OK
 }
@@ -0,0 +1,265 @@
+//! `GET /health/ready`: whether the data directory can actually be served.
+//!
+//! A pod whose volume is empty, half-synced or holds another release's files still answers the
+//! SPA and every `/data` request, just with 404s and decode errors. Readiness is the one probe
+//! that looks at the data: every edition the output holds must have a metadata map that decodes,
+//! and, when the converter left an integrity manifest next to it, every file the manifest lists
+//! must be there at its recorded size. Anything less answers 503, so a rollout keeps routing to
+//! the old pods. An output that holds only some editions, because only those are converted or
+//! one had no input, is ready on those alone.
+//!
+//! The probe checks the generation being served (see [`crate::generation`]); the same check
+//! decides whether the server switches to a new one.
+//!
+//! The manifest is checked by size only, and once per generation. Listing and statting every
+//! file of every edition is hundreds of thousands of calls on real data, too many for a probe
+//! that runs every few seconds, so once a generation has passed, the probe only decodes its
+//! metadata maps and manifests again. Hashing the output is left to `converter verify`.
+
+use crate::generation::Generations;
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use mp_stats_common::DataError;
+use mp_stats_common::compression::read_lzma_bin;
+use mp_stats_common::manifest::{self, Manifest};
+use mp_stats_core::models::{IdMap, PlatformEdition};
+use mp_stats_core::routes;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The probe's body: the verdict, and the edition by edition reasons for it.
+#[derive(Debug, Serialize)]
+pub(crate) struct Readiness {
+    pub(crate) ready: bool,
+    /// The generation checked, or `None` for a flat data directory.
+    pub(crate) generation: Option<String>,
+    pub(crate) editions: Vec<EditionReadiness>,
+}
+
+/// One edition's share of [`Readiness`].
+#[derive(Debug, Serialize)]
+pub(crate) struct EditionReadiness {
+    pub(crate) edition: PlatformEdition,
+    pub(crate) ready: bool,
+    /// Games in the metadata map, when it decoded.
+    pub(crate) games: Option<usize>,
+    /// Files the manifest lists, or `None` when the edition has no manifest.
+    pub(crate) manifest_files: Option<usize>,
+    /// Why the edition is not ready. The detail names files on this host, so it goes to the
+    /// log; the body only says which check failed.
+    pub(crate) error: Option<String>,
+}
+
+/// Check every edition, off the async runtime: it is a directory walk and an LZMA decode the
+/// first time, and still the decode after.
+pub(crate) async fn probe(
+    State(generations): State<Arc<Generations>>,
+) -> (StatusCode, Json<Readiness>) {
+    let generation = generations.current();
+    let id = generation.id.clone();
+    let checked = tokio::task::spawn_blocking(move || generations.readiness(&generation)).await;
+    let readiness = match checked {
+        Ok(readiness) => Readiness {
+            generation: id,
+            ..readiness
+        },
+        Err(e) => {
+            eprintln!("error: readiness: check task failed: {e}");
+            Readiness {
+                ready: false,
+                generation: id,
+                editions: vec![],
+            }
+        }
+    };
+
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+/// The readiness of every edition with a directory under `data_dir`, one generation's root.
+/// When there is none, every edition is checked, so an empty volume says what it is missing.
+pub(crate) fn check(data_dir: &Path) -> Readiness {
+    check_with(data_dir, true)
+}
+
+/// [`check`] of a generation that already passed it: every metadata map and manifest must
+/// still decode, but the files the manifests list are not looked at again.
+pub(crate) fn recheck(data_dir: &Path) -> Readiness {
+    check_with(data_dir, false)
+}
+
+fn check_with(data_dir: &Path, sizes: bool) -> Readiness {
+    let present: Vec<_> = PlatformEdition::iter()
+        .filter(|edition| data_dir.join(edition.directory_name()).is_dir())
+        .collect();
+    let checked = if present.is_empty() {
+        PlatformEdition::iter().collect()
+    } else {
+        present
+    };
+    let editions: Vec<_> = checked
+        .into_iter()
+        .map(|edition| check_edition(data_dir, edition, sizes))
+        .collect();
+
+    Readiness {
+        ready: editions.iter().all(|e| e.ready),
+        generation: None,
+        editions,
+    }
+}
+
+fn check_edition(data_dir: &Path, edition: &PlatformEdition, sizes: bool) -> EditionReadiness {
+    let mut readiness = EditionReadiness {
+        edition: edition.clone(),
+        ready: false,
+        games: None,
+        manifest_files: None,
+        error: None,
+    };
+    let fail = |reason: &str, detail: DataError| {
+        eprintln!("error: readiness: {}: {detail}", edition.display_name());
+        Some(reason.to_string())
+    };
+
+    match read_lzma_bin::<IdMap>(&data_dir.join(routes::meta_map_bin(edition))) {
+        Ok(id_map) => readiness.games = Some(id_map.games.len()),
+        Err(e @ DataError::FileNotFound(_)) => {
+            readiness.error = fail("metadata map missing", e);
+            return readiness;
+        }
+        Err(e) => {
+            readiness.error = fail("metadata map does not decode", e);
+            return readiness;
+        }
+    }
+
+    // Output from before the converter wrote manifests has none; the map decoding is then all
+    // there is to go on.
+    let edition_dir = data_dir.join(edition.directory_name());
+    let manifest = match Manifest::read(&edition_dir) {
+        Ok(manifest) => manifest,
+        Err(DataError::FileNotFound(_)) => {
+            readiness.ready = true;
+            return readiness;
+        }
+        Err(e) => {
+            readiness.error = fail("manifest does not decode", e);
+            return readiness;
+        }
+    };
+
+    readiness.manifest_files = Some(manifest.files.len());
+    if !sizes {
+        readiness.ready = true;
+        return readiness;
+    }
+    match manifest::check_sizes(&edition_dir, &manifest) {
+        Ok(()) => readiness.ready = true,
+        Err(e) => readiness.error = fail("files do not match the manifest", e),
+    }
+    readiness
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mp_stats_common::compression::write_lzma_bin;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn data_dir() -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("mp_stats_ready_{nanos}"))
+    }
+
+    /// Every edition with an empty metadata map and one game file under a manifest.
+    fn converted(dir: &Path) {
+        let id_map = IdMap {
+            boards: HashMap::new(),
+            games: HashMap::new(),
+            stats: HashMap::new(),
+        };
+        for edition in PlatformEdition::iter() {
+            write_lzma_bin(&dir.join(routes::meta_map_bin(edition)), &id_map).unwrap();
+            write_lzma_bin(&dir.join(routes::game_bin(edition, "Skywars")), &1u32).unwrap();
+            let edition_dir = dir.join(edition.directory_name());
+            Manifest::build(&edition_dir, 1, 0)
+                .unwrap()
+                .write(&edition_dir)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn an_empty_volume_is_not_ready() {
+        let dir = data_dir();
+        let readiness = check(&dir);
+
+        assert!(!readiness.ready);
+        assert_eq!(readiness.editions.len(), PlatformEdition::VARIANTS.len());
+        assert_eq!(
+            readiness.editions[0].error.as_deref(),
+            Some("metadata map missing")
+        );
+    }
+
+    #[test]
+    fn a_complete_output_is_ready() {
+        let dir = data_dir();
+        converted(&dir);
+        let readiness = check(&dir);
+
+        assert!(readiness.ready, "{readiness:?}");
+        assert_eq!(readiness.editions[0].games, Some(0));
+        assert_eq!(readiness.editions[0].manifest_files, Some(2));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn an_output_of_some_editions_is_ready_on_those() {
+        let dir = data_dir();
+        converted(&dir);
+        fs::remove_dir_all(dir.join(PlatformEdition::Bedrock.directory_name())).unwrap();
+        let readiness = check(&dir);
+
+        assert!(readiness.ready, "{readiness:?}");
+        assert_eq!(readiness.editions.len(), 1);
+        assert_eq!(readiness.editions[0].edition, PlatformEdition::Java);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn a_truncated_or_corrupt_edition_is_not_ready() {
+        let dir = data_dir();
+        converted(&dir);
+        let edition = &PlatformEdition::Java;
+
+        fs::remove_file(dir.join(routes::game_bin(edition, "Skywars"))).unwrap();
+        let readiness = check(&dir);
+        assert!(!readiness.ready);
+        assert_eq!(
+            readiness.editions[0].error.as_deref(),
+            Some("files do not match the manifest")
+        );
+        assert!(readiness.editions[1].ready);
+
+        fs::write(dir.join(routes::meta_map_bin(edition)), b"not xz").unwrap();
+        assert_eq!(
+            check(&dir).editions[0].error.as_deref(),
+            Some("metadata map does not decode")
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -12,12 +12,30 @@ pub mod raw {
     /// Minimum prefix length for sharding
     pub const MIN_PREFIX_LENGTH: usize = 3;
 
-    /// Minimum name length for indexing
+    /// Minimum name length for the prefix and trigram name indexes; shorter
+    /// names go to the short-name index
     pub const MIN_NAME_LENGTH: usize = 3;
 
+    /// Prefix length of a leaderboard snapshot's position index shards: a
+    /// board of 200k players splits into about 16 files for UUIDs
+    pub const POSITION_PREFIX_LENGTH: usize = 1;
+
     /// Dictionary chunk size for player ID sharding (old format: 10k chunks)
     pub const DICTIONARY_CHUNK_SIZE: i32 = 10000;
 }
 
 /// Common file names
 pub const FILE_META: &str = "_meta.json";
+
+/// An edition's integrity manifest, read by [`crate::manifest::verify`].
+pub const FILE_MANIFEST_BIN: &str = "manifest.bin.xz";
+
+/// The same manifest as JSON, for people and scripts.
+pub const FILE_MANIFEST_JSON: &str = "manifest.json";
+
+/// Under a data directory, the directory holding one subdirectory per complete output; see
+/// [`crate::generation`].
+pub const GENERATIONS_DIR: &str = "generations";
+
+/// Under a data directory, the file naming the generation being served.
+pub const CURRENT_FILE: &str = "current";
@@ -0,0 +1,119 @@
+//! Generations: complete converted outputs side by side, and a pointer to the one being served.
+//!
+//! A data directory either holds one output directly (the flat layout, `java/`, `bedrock/`), or
+//! holds several under [`GENERATIONS_DIR`] with [`CURRENT_FILE`] naming the one in use:
+//!
+//! ```text
+//! data/
+//!   current                 <- "1760745600000"
+//!   generations/
+//!     1760659200000/java/...
+//!     1760745600000/java/...
+//! ```
+//!
+//! A new output is written into a directory of its own and only becomes current once it is
+//! complete, by [`publish`] replacing the pointer in one rename. A reader that follows the
+//! pointer therefore sees either the old output or the new one, never a mix of the two.
+
+use crate::error::{DataError, Result};
+use crate::formats::{CURRENT_FILE, GENERATIONS_DIR};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where generation `id` lives under `data_dir`.
+pub fn generation_dir(data_dir: &Path, id: &str) -> PathBuf {
+    data_dir.join(GENERATIONS_DIR).join(id)
+}
+
+/// The generation [`CURRENT_FILE`] names, or `None` for a flat data directory.
+pub fn read_current(data_dir: &Path) -> Result<Option<String>> {
+    let pointer = data_dir.join(CURRENT_FILE);
+    let id = match fs::read_to_string(&pointer) {
+        Ok(id) => id.trim().to_string(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    validate_id(&id)?;
+    Ok(Some(id))
+}
+
+/// The directory the current output is read from: the current generation's, or `data_dir`
+/// itself when there is no pointer.
+pub fn resolve(data_dir: &Path) -> Result<PathBuf> {
+    Ok(match read_current(data_dir)? {
+        Some(id) => generation_dir(data_dir, &id),
+        None => data_dir.to_path_buf(),
+    })
+}
+
+/// Make generation `id` the current one.
+///
+/// The pointer is written next to its final name and renamed over it, so a reader finds the
+/// old id or the new one and never a half-written file.
+pub fn publish(data_dir: &Path, id: &str) -> Result<()> {
+    validate_id(id)?;
+    let dir = generation_dir(data_dir, id);
+    if !dir.is_dir() {
+        return Err(DataError::FileNotFound(format!(
+            "generation {id} at {}",
+            dir.display()
+        )));
+    }
+
+    let pointer = data_dir.join(CURRENT_FILE);
+    let staged = data_dir.join(format!(".{CURRENT_FILE}.tmp"));
+    fs::write(&staged, format!("{id}\n"))?;
+    fs::rename(&staged, &pointer)?;
+    Ok(())
+}
+
+/// An id is one path component: the pointer must not lead out of [`GENERATIONS_DIR`].
+fn validate_id(id: &str) -> Result<()> {
+    let valid = !id.is_empty() && id != "." && id != ".." && !id.contains(['/', '\\', '\0']);
+    if valid {
+        Ok(())
+    } else {
+        Err(DataError::InvalidFormat(format!(
+            "'{id}' is not a generation id"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_dir() -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("mp_stats_generation_{nanos}"))
+    }
+
+    #[test]
+    fn a_flat_directory_resolves_to_itself() {
+        let dir = data_dir();
+        assert_eq!(read_current(&dir).unwrap(), None);
+        assert_eq!(resolve(&dir).unwrap(), dir);
+    }
+
+    #[test]
+    fn publishing_moves_the_pointer() {
+        let dir = data_dir();
+        fs::create_dir_all(generation_dir(&dir, "1")).unwrap();
+        fs::create_dir_all(generation_dir(&dir, "2")).unwrap();
+
+        publish(&dir, "1").unwrap();
+        assert_eq!(resolve(&dir).unwrap(), generation_dir(&dir, "1"));
+        publish(&dir, "2").unwrap();
+        assert_eq!(read_current(&dir).unwrap().as_deref(), Some("2"));
+
+        assert!(publish(&dir, "3").is_err(), "3 was never written");
+        assert!(publish(&dir, "..").is_err());
+        fs::write(dir.join(CURRENT_FILE), "../..").unwrap();
+        assert!(resolve(&dir).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
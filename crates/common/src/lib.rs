@@ -1,6 +1,8 @@
 pub mod compression;
 pub mod error;
 pub mod formats;
+pub mod generation;
+pub mod manifest;
 pub mod shard;
 
 pub use error::{DataError, Result};
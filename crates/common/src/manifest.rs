@@ -0,0 +1,278 @@
+//! Integrity manifests: what a complete converted edition looks like.
+//!
+//! The converter writes one manifest per edition directory, listing every file
+//! under it with its size and SHA-256. [`verify`] checks a directory against
+//! its manifest, so a deployment can tell a complete output from one that was
+//! truncated, half-copied or edited after the fact.
+
+use crate::compression::{read_lzma_bin, write_lzma_bin};
+use crate::error::{DataError, Result};
+use crate::formats::{FILE_MANIFEST_BIN, FILE_MANIFEST_JSON};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+/// How many problems an integrity error spells out; the rest are counted.
+const MAX_REPORTED: usize = 10;
+
+/// One file of a converted edition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Relative to the edition directory, `/`-separated.
+    pub path: String,
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the file's bytes.
+    pub sha256: String,
+}
+
+/// Every file of a converted edition, and the conversion that wrote them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The converter's output schema version the files were written in.
+    pub schema_version: u64,
+    /// When the conversion that wrote the files ran, in Unix seconds.
+    pub created_at_unix: u64,
+    /// Sorted by path. The manifest files themselves are not listed.
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Describe every file under `dir`, hashing several at a time.
+    pub fn build(dir: &Path, schema_version: u64, created_at_unix: u64) -> Result<Self> {
+        let mut files = scan(dir)?
+            .into_par_iter()
+            .map(|(path, full_path)| {
+                let (size, sha256) = hash_file(&full_path)?;
+                Ok(ManifestEntry { path, size, sha256 })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Self {
+            schema_version,
+            created_at_unix,
+            files,
+        })
+    }
+
+    /// Write the manifest into `dir`, as [`FILE_MANIFEST_BIN`] for the readers
+    /// of the data and [`FILE_MANIFEST_JSON`] for people and scripts.
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        write_lzma_bin(&dir.join(FILE_MANIFEST_BIN), self)?;
+        fs::write(
+            dir.join(FILE_MANIFEST_JSON),
+            serde_json::to_vec_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Read the manifest [`Manifest::write`] left in `dir`.
+    pub fn read(dir: &Path) -> Result<Self> {
+        read_lzma_bin(&dir.join(FILE_MANIFEST_BIN))
+    }
+}
+
+/// Check `dir` against the manifest it holds.
+///
+/// Fails with [`DataError::IntegrityCheckFailed`] when a listed file is
+/// missing or differs in size or hash, or when a file is present that the
+/// manifest does not list. Returns the manifest when everything matches.
+pub fn verify(dir: &Path) -> Result<Manifest> {
+    let manifest = Manifest::read(dir)?;
+    verify_against(dir, &manifest)?;
+    Ok(manifest)
+}
+
+/// Check `dir` against `manifest`; see [`verify`].
+pub fn verify_against(dir: &Path, manifest: &Manifest) -> Result<()> {
+    compare(dir, manifest, true)
+}
+
+/// The cheap half of [`verify_against`]: every listed file is present with its recorded size
+/// and nothing else is, but no file is read. Meant for checks that run often, such as a
+/// server's readiness probe, where hashing the whole output each time would cost more than the
+/// answer is worth.
+pub fn check_sizes(dir: &Path, manifest: &Manifest) -> Result<()> {
+    compare(dir, manifest, false)
+}
+
+fn compare(dir: &Path, manifest: &Manifest, hash: bool) -> Result<()> {
+    let mut present = scan(dir)?;
+    let mut problems = Vec::new();
+    let mut to_hash = Vec::new();
+
+    for entry in &manifest.files {
+        let Some(path) = present.remove(&entry.path) else {
+            problems.push(format!("{}: missing", entry.path));
+            continue;
+        };
+
+        let size = fs::metadata(&path)?.len();
+        if size != entry.size {
+            problems.push(format!(
+                "{}: {size} bytes, expected {}",
+                entry.path, entry.size
+            ));
+        } else if hash {
+            to_hash.push((entry, path));
+        }
+    }
+    let differing = to_hash
+        .into_par_iter()
+        .map(|(entry, path)| {
+            let (_, sha256) = hash_file(&path)?;
+            Ok((sha256 != entry.sha256).then(|| format!("{}: content differs", entry.path)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    problems.extend(differing.into_iter().flatten());
+    problems.extend(
+        present
+            .into_keys()
+            .map(|path| format!("{path}: not in manifest")),
+    );
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = format!("{} problem(s) in {}", problems.len(), dir.display());
+    for problem in problems.iter().take(MAX_REPORTED) {
+        let _ = write!(message, "; {problem}");
+    }
+    if problems.len() > MAX_REPORTED {
+        let _ = write!(message, "; and {} more", problems.len() - MAX_REPORTED);
+    }
+    Err(DataError::IntegrityCheckFailed(message))
+}
+
+/// Every file under `dir` but the manifest's own, by `/`-separated relative path.
+fn scan(dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut files = BTreeMap::new();
+    let mut stack = vec![dir.to_path_buf()];
+
+    while let Some(current) = stack.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+
+            let relative = path
+                .strip_prefix(dir)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if relative == FILE_MANIFEST_BIN || relative == FILE_MANIFEST_JSON {
+                continue;
+            }
+            files.insert(relative, path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// The size and lowercase hex SHA-256 of the file at `path`, streamed rather than read whole.
+fn hash_file(path: &Path) -> Result<(u64, String)> {
+    let mut hasher = Hasher(Sha256::new());
+    let size = io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    let sha256 = hasher
+        .0
+        .finalize()
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        });
+    Ok((size, sha256))
+}
+
+/// [`Sha256`] as an [`io::Write`], which sha2 no longer implements itself.
+struct Hasher(Sha256);
+
+impl io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture() -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("mp_stats_manifest_{nanos}"));
+        fs::create_dir_all(dir.join("players")).unwrap();
+        fs::write(dir.join("players/ABC.bin.xz"), b"abc").unwrap();
+        fs::write(dir.join("map.bin.xz"), b"map").unwrap();
+        dir
+    }
+
+    #[test]
+    fn a_written_manifest_verifies() {
+        let dir = fixture();
+        Manifest::build(&dir, 1, 2).unwrap().write(&dir).unwrap();
+
+        let manifest = verify(&dir).unwrap();
+        let paths: Vec<_> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["map.bin.xz", "players/ABC.bin.xz"]);
+        assert_eq!(manifest.files[1].size, 3);
+        assert_eq!(
+            manifest.files[1].sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn changed_missing_and_extra_files_fail() {
+        let dir = fixture();
+        Manifest::build(&dir, 1, 2).unwrap().write(&dir).unwrap();
+
+        fs::write(dir.join("players/ABC.bin.xz"), b"abd").unwrap();
+        fs::remove_file(dir.join("map.bin.xz")).unwrap();
+        fs::write(dir.join("stray.bin.xz"), b"x").unwrap();
+
+        let Err(DataError::IntegrityCheckFailed(message)) = verify(&dir) else {
+            panic!("expected an integrity failure");
+        };
+        assert!(message.starts_with("3 problem(s)"), "{message}");
+        assert!(message.contains("players/ABC.bin.xz: content differs"));
+        assert!(message.contains("map.bin.xz: missing"));
+        assert!(message.contains("stray.bin.xz: not in manifest"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Same size, different bytes: only the full check reads far enough to notice.
+    #[test]
+    fn a_size_check_skips_the_hashes() {
+        let dir = fixture();
+        let manifest = Manifest::build(&dir, 1, 2).unwrap();
+
+        fs::write(dir.join("players/ABC.bin.xz"), b"abd").unwrap();
+        assert!(check_sizes(&dir, &manifest).is_ok());
+        assert!(verify_against(&dir, &manifest).is_err());
+
+        fs::write(dir.join("players/ABC.bin.xz"), b"ab").unwrap();
+        assert!(check_sizes(&dir, &manifest).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,26 +1,96 @@
 use crate::error::{DataError, Result};
 use crate::formats::raw;
 
-/// Calculate shard key from UUID (first 3 characters, uppercase)
+/// The key a player id or name is filed under: its first three characters
+/// with everything but ASCII letters and digits dropped, padded with `_`.
+///
+/// Java ids are UUIDs, whose first three characters are hex digits and come
+/// through unchanged. Bedrock ids are gamertags, which may hold spaces,
+/// punctuation or non-ASCII letters; this keeps their shard files addressable
+/// by URL, and is the rule the Bedrock `names.tar.xz` archive files its names
+/// under (`"C Titan"` under `CT_`, `"[B@8dc801e-"` under `B__`).
+///
+/// `None` when `s` has fewer than `len` characters.
+fn shard_key(s: &str, len: usize) -> Option<String> {
+    if s.chars().count() < len {
+        return None;
+    }
+    let mut key: String = s
+        .chars()
+        .take(len)
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    while key.len() < len {
+        key.push('_');
+    }
+    Some(key)
+}
+
+/// Calculate shard key from a player id (first 3 characters, uppercase)
+///
+/// See [`shard_key`] for how ids that are not UUIDs are filed.
 pub fn uuid_shard(uuid: &str) -> Result<String> {
-    if uuid.len() < raw::MIN_PREFIX_LENGTH {
-        return Err(DataError::Validation(format!(
+    match shard_key(uuid, raw::MIN_PREFIX_LENGTH) {
+        Some(prefix) => Ok(prefix.to_uppercase()),
+        None => Err(DataError::Validation(format!(
             "UUID too short for sharding: '{}'",
             uuid
-        )));
+        ))),
     }
-    Ok(uuid[..raw::MIN_PREFIX_LENGTH].to_uppercase())
 }
 
 /// Calculate shard key from player name (first 3 characters, lowercase)
+///
+/// See [`shard_key`] for how names outside `[A-Za-z0-9]` are filed.
 pub fn name_shard(name: &str) -> Result<String> {
-    if name.len() < raw::MIN_NAME_LENGTH {
-        return Err(DataError::Validation(format!(
+    match shard_key(name, raw::MIN_NAME_LENGTH) {
+        Some(prefix) => Ok(prefix.to_lowercase()),
+        None => Err(DataError::Validation(format!(
             "Name too short for sharding: '{}'",
             name
-        )));
+        ))),
+    }
+}
+
+/// Calculate the position index shard of a player id on a leaderboard
+/// snapshot (first character, uppercase)
+///
+/// Coarser than [`uuid_shard`], since one snapshot holds far fewer players
+/// than an edition. Any id with at least one character has a shard.
+pub fn position_shard(player_id: &str) -> Result<String> {
+    match shard_key(player_id, raw::POSITION_PREFIX_LENGTH) {
+        Some(prefix) => Ok(prefix.to_uppercase()),
+        None => Err(DataError::Validation(
+            "An empty player id has no position shard".to_string(),
+        )),
     }
-    Ok(name[..raw::MIN_NAME_LENGTH].to_lowercase())
+}
+
+/// The trigrams a name is found by in the trigram index: every run of three
+/// characters of the lowercased name, with anything but an ASCII letter or
+/// digit written as `_` so each trigram is also a valid file name.
+///
+/// Sorted and without duplicates; empty for names shorter than three
+/// characters, which the trigram index cannot hold.
+pub fn name_trigrams(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    let mut trigrams: Vec<String> = chars
+        .windows(raw::MIN_NAME_LENGTH)
+        .map(|w| w.iter().collect())
+        .collect();
+    trigrams.sort();
+    trigrams.dedup();
+    trigrams
 }
 
 /// Calculate dictionary chunk ID from player ID
@@ -37,6 +107,27 @@ mod tests {
         assert_eq!(uuid_shard("abc123-456").unwrap(), "ABC");
         assert_eq!(uuid_shard("XyZ789-000").unwrap(), "XYZ");
         assert!(uuid_shard("ab").is_err());
+        // Multi-byte characters are counted as characters, never split.
+        assert_eq!(uuid_shard("ééx1").unwrap(), "X__");
+    }
+
+    /// Bedrock gamertags are filed the way `names.tar.xz` files them.
+    #[test]
+    fn test_bedrock_shards() {
+        assert_eq!(uuid_shard("C Titan").unwrap(), "CT_");
+        assert_eq!(uuid_shard("[B@8dc801e-").unwrap(), "B__");
+        assert_eq!(uuid_shard("xd Lord lol").unwrap(), "XD_");
+        assert_eq!(name_shard("_Afk").unwrap(), "af_");
+        assert_eq!(name_shard("D_Doge").unwrap(), "dd_");
+    }
+
+    #[test]
+    fn test_position_shard() {
+        assert_eq!(position_shard("abc123-456").unwrap(), "A");
+        assert_eq!(position_shard("C Titan").unwrap(), "C");
+        assert_eq!(position_shard("[B@8dc801e-").unwrap(), "_");
+        assert_eq!(position_shard("x").unwrap(), "X");
+        assert!(position_shard("").is_err());
     }
 
     #[test]
@@ -46,6 +137,17 @@ mod tests {
         assert!(name_shard("AB").is_err());
     }
 
+    #[test]
+    fn test_name_trigrams() {
+        assert_eq!(
+            name_trigrams("xXSteve"),
+            ["eve", "ste", "tev", "xst", "xxs"]
+        );
+        assert_eq!(name_trigrams("C Ti"), ["_ti", "c_t"]);
+        assert_eq!(name_trigrams("aaaa"), ["aaa"]);
+        assert!(name_trigrams("Al").is_empty());
+    }
+
     #[test]
     fn test_player_id_chunk() {
         assert_eq!(player_id_chunk(12345), 1);
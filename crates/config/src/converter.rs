@@ -1,4 +1,5 @@
-//! The converter's block: what it reads, where it writes, and how it caches.
+//! The converter's block: what it reads, where it writes, how it caches and compresses, and how
+//! strict it is.
 
 use serde::Deserialize;
 use std::path::PathBuf;
@@ -20,10 +21,34 @@ pub struct ConverterConfig {
     /// [`Self::input_dir`] into itself fails immediately rather than half way through.
     #[serde(default = "ConverterConfig::default_output_dir")]
     pub output_dir: PathBuf,
+    /// Where a run assembles its output before publishing it; empty is beside the output
+    /// directory.
+    ///
+    /// Each run stages in a directory of its own under this one, and the finished output is
+    /// renamed into [`Self::output_dir`], which is only atomic on the same filesystem. Left empty,
+    /// it is `.<output dir name>.staging` next to the output directory. Staging directories left
+    /// behind by a run that crashed are removed by the next one.
+    #[serde(default)]
+    pub staging_dir: PathBuf,
+    /// Complete outputs kept under `generations/` in the output directory, the current one
+    /// included. At least 1.
+    ///
+    /// Each run writes a new generation and moves the `current` pointer to it once it is
+    /// complete; the older ones kept are what `converter rollback` can return to.
+    #[serde(default = "ConverterConfig::default_keep_generations")]
+    pub keep_generations: u32,
     /// Incremental output cache.
     #[serde(default)]
     #[cfg_attr(feature = "config-schema", config(nested))]
     pub cache: CacheConfig,
+    /// XZ encoding of the output.
+    #[serde(default)]
+    #[cfg_attr(feature = "config-schema", config(nested))]
+    pub compression: CompressionConfig,
+    /// Failing the run on lost work.
+    #[serde(default)]
+    #[cfg_attr(feature = "config-schema", config(nested))]
+    pub strict: StrictConfig,
 }
 
 impl ConverterConfig {
@@ -34,6 +59,10 @@ impl ConverterConfig {
     fn default_output_dir() -> PathBuf {
         PathBuf::from("target/converted_data")
     }
+
+    fn default_keep_generations() -> u32 {
+        3
+    }
 }
 
 impl Default for ConverterConfig {
@@ -41,7 +70,11 @@ impl Default for ConverterConfig {
         Self {
             input_dir: Self::default_input_dir(),
             output_dir: Self::default_output_dir(),
+            staging_dir: PathBuf::new(),
+            keep_generations: Self::default_keep_generations(),
             cache: CacheConfig::default(),
+            compression: CompressionConfig::default(),
+            strict: StrictConfig::default(),
         }
     }
 }
@@ -66,6 +99,12 @@ pub struct CacheConfig {
     /// Where cached output and its input fingerprints live.
     #[serde(default = "CacheConfig::default_dir")]
     pub dir: PathBuf,
+    /// Largest the cache directory may grow, in MiB; `0` is unbounded.
+    ///
+    /// After each run the least recently used entries, whole editions and single units alike,
+    /// are evicted until the cache fits.
+    #[serde(default)]
+    pub max_size_mib: u64,
 }
 
 impl CacheConfig {
@@ -79,10 +118,92 @@ impl Default for CacheConfig {
         Self {
             enabled: true,
             dir: Self::default_dir(),
+            max_size_mib: 0,
+        }
+    }
+}
+
+/// How the converter's output is XZ-compressed.
+///
+/// Compression is most of the time a conversion takes. The defaults give the smallest output and
+/// are what a release image is built with; `fast` is for development runs, where a conversion
+/// that finishes in a fraction of the time is worth output a few times larger. The settings are
+/// part of the cache fingerprint, so output written with one never restores into a run with
+/// another.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(
+    feature = "config-schema",
+    derive(serde::Serialize, terrace_config::schema::Describe)
+)]
+pub struct CompressionConfig {
+    /// XZ preset level, 0 (fastest) to 9 (smallest).
+    ///
+    /// Levels above 6 grow the dictionary past the size of most output files and mostly cost
+    /// memory.
+    #[serde(default = "CompressionConfig::default_level")]
+    pub level: u32,
+    /// Worker threads compressing each file; `0` uses every core.
+    ///
+    /// Above one, a file is cut into independent blocks that are compressed in parallel. Files
+    /// no larger than one block, which is most pages, are written on one thread either way.
+    #[serde(default = "CompressionConfig::default_threads")]
+    pub threads: u32,
+    /// Size of each parallel block in MiB; `0` is three dictionaries, as `xz` picks.
+    ///
+    /// Smaller blocks parallelize more files at some cost in size. Never smaller than the
+    /// level's dictionary.
+    #[serde(default)]
+    pub block_size_mib: u64,
+    /// Compress for speed on every core, ignoring `level`, `threads` and `block_size_mib`.
+    #[serde(default)]
+    pub fast: bool,
+}
+
+impl CompressionConfig {
+    fn default_level() -> u32 {
+        6
+    }
+
+    fn default_threads() -> u32 {
+        1
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            level: Self::default_level(),
+            threads: Self::default_threads(),
+            block_size_mib: 0,
+            fast: false,
         }
     }
 }
 
+/// Whether a run that lost work still replaces the output.
+///
+/// Every run writes `conversion_report.json` at the root of the generation it publishes, counting
+/// the chunks, pages, snapshots and shards each edition processed and lost. By default a run that
+/// lost some still replaces the output; in strict mode it stops before touching the output
+/// instead, and only the report is written, as `failed_conversion_report.json` at the root of
+/// the output directory.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(
+    feature = "config-schema",
+    derive(serde::Serialize, terrace_config::schema::Describe)
+)]
+pub struct StrictConfig {
+    /// Fail the run when it loses more than `max_errors` units of work.
+    #[serde(default)]
+    pub enabled: bool,
+    /// How many lost chunks, pages, snapshots and shards a strict run tolerates.
+    ///
+    /// Player ids missing from the dictionary are reported but never counted here: they are a
+    /// gap in the input, not work the converter lost.
+    #[serde(default)]
+    pub max_errors: u64,
+}
+
 fn default_true() -> bool {
     true
 }
@@ -23,7 +23,7 @@ mod csp;
 mod loader;
 mod server;
 
-pub use converter::{CacheConfig, ConverterConfig};
+pub use converter::{CacheConfig, CompressionConfig, ConverterConfig, StrictConfig};
 pub use csp::{CloudflareConfig, CspConfig};
 pub use loader::{ConfigError, load, terrace};
 pub use server::ServerConfig;
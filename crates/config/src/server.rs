@@ -27,7 +27,7 @@ pub struct ServerConfig {
     /// server refuses to start without it.
     #[serde(default = "ServerConfig::default_dist_dir")]
     pub dist_dir: PathBuf,
-    /// Directory holding the converter's output, mounted at `/data`.
+    /// Directory holding the converter's output, mounted at `/data` and decoded under `/api/v1`.
     #[serde(default = "ServerConfig::default_data_dir")]
     pub data_dir: PathBuf,
     /// The `Content-Security-Policy` attached to every document the server answers with. The
@@ -95,6 +95,27 @@ pub struct PlatformMeta {
     pub games: Vec<Game>,
 }
 
+impl PlatformMeta {
+    /// The game list of an edition, derived from its id map and sorted by name.
+    pub fn from_id_map(id_map: IdMap) -> Self {
+        let mut games: Vec<Game> = id_map
+            .games
+            .into_values()
+            .map(|value| Game {
+                id: value.name.clone(),
+                name: value.name,
+                description: value.description,
+                icon: None,
+                total_snapshots: value.total_snapshots,
+            })
+            .collect();
+
+        games.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self { games }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct LeaderboardChunk {
     pub rank: u32,
@@ -113,6 +134,24 @@ pub struct LeaderboardPage {
     pub scores: Vec<u64>,
 }
 
+impl LeaderboardPage {
+    /// Zip the columns back into one row per entry, in page order.
+    pub fn into_entries(self) -> Vec<LeaderboardEntry> {
+        self.ranks
+            .into_iter()
+            .zip(self.uuids)
+            .zip(self.names)
+            .zip(self.scores)
+            .map(|(((rank, uuid), name), score)| LeaderboardEntry {
+                rank,
+                uuid,
+                name,
+                score,
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct LeaderboardEntry {
     pub rank: u32,
@@ -121,6 +160,17 @@ pub struct LeaderboardEntry {
     pub score: u64,
 }
 
+/// Where a player sits on one leaderboard snapshot, as filed in its position
+/// index (see `routes::leaderboard_positions_bin`), keyed by uuid.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct LeaderboardPosition {
+    /// The page, counted from 0 like the chunk files.
+    pub page: u32,
+    /// The row within the page, counted from 0.
+    pub row: u32,
+    pub rank: u32,
+}
+
 /// Highlighted leaderboard entry used for the per-category "game list" stats,
 /// i.e. the `#1 holder` of a board's latest snapshot and their top score.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -189,6 +239,20 @@ pub struct PlayerProfile {
     pub uuid: SmolStr,
     pub name: Option<SmolStr>,
     pub stats: Vec<StatRaw>,
+    /// Every name the player has been known by, oldest first; the current
+    /// name is the last one.
+    #[serde(default)]
+    pub name_history: Vec<NameRecord>,
+}
+
+/// One name in a [`PlayerProfile::name_history`], and the span of leaderboard
+/// snapshots (as `save_time_unix`) the player was ranked under it. A name that
+/// was never ranked, only listed in the dictionary, has both at 0.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct NameRecord {
+    pub name: SmolStr,
+    pub first_seen: u64,
+    pub last_seen: u64,
 }
 
 /// Aggregated, ready-to-display ranking metrics derived from a
@@ -253,6 +317,26 @@ pub struct NameLookup {
     pub shard_path: SmolStr,
 }
 
+/// A player listed in a name trigram file: a name containing the trigram, and
+/// who carries it. Only players with a profile are listed.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct NameMatch {
+    pub name: SmolStr,
+    pub uuid: SmolStr,
+}
+
+/// One player carrying a name in the names index. A name can be carried by
+/// several players, at once or one after another, so the index lists every
+/// one of them, most recently seen first.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct NameCandidate {
+    pub uuid: SmolStr,
+    /// Whether the player has a profile to open.
+    pub has_profile: bool,
+    /// The latest `save_time` on the player's profile, or 0 without one.
+    pub last_seen: u64,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct MetaFile {
     pub save_time: String,
@@ -270,6 +354,228 @@ pub struct HistoricalSnapshot {
     pub timestamp: u64,
     pub total_pages: u32,
     pub total_entries: u32,
+    /// The score range of every written page, in page order, for
+    /// [`place_score`]. Empty for legacy payloads.
+    #[serde(default)]
+    pub page_scores: Vec<PageScoreRange>,
+    /// How the snapshot's scores are spread. `None` for legacy payloads and
+    /// for snapshots without a written row.
+    #[serde(default)]
+    pub distribution: Option<ScoreDistribution>,
+}
+
+/// The highest and lowest score on one leaderboard page, and how many rows
+/// it holds.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct PageScoreRange {
+    pub max: u64,
+    pub min: u64,
+    pub entries: u32,
+}
+
+/// Where an arbitrary score would have placed on a snapshot, as far as its
+/// [`PageScoreRange`]s tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScorePlacement {
+    /// The competition rank, settled by the ranges alone.
+    Rank(u32),
+    /// The score falls inside page `page`'s range, behind the `ahead` rows of
+    /// the pages before it; [`rank_within_page`] on that page settles it.
+    InPage { page: u32, ahead: u32 },
+}
+
+/// Place `score` on a snapshot by binary search over its page ranges, which
+/// run best page first.
+///
+/// The competition rank of a score is one more than the number of rows
+/// scoring strictly higher (see [`competition_ranks_by_score`]). Every page
+/// whose lowest score is above `score` is wholly ahead of it; if the next page
+/// holds nothing above it either, that is the rank, and otherwise only that
+/// one page needs reading.
+pub fn place_score(pages: &[PageScoreRange], score: u64) -> ScorePlacement {
+    let page = pages.partition_point(|range| range.min > score);
+    let ahead: u32 = pages[..page].iter().map(|range| range.entries).sum();
+
+    match pages.get(page) {
+        Some(range) if range.max > score => ScorePlacement::InPage {
+            page: page as u32,
+            ahead,
+        },
+        _ => ScorePlacement::Rank(ahead + 1),
+    }
+}
+
+/// The competition rank `score` would have had, given the `scores` of the
+/// page [`place_score`] pointed at and the `ahead` rows before it.
+pub fn rank_within_page(scores: &[u64], ahead: u32, score: u64) -> u32 {
+    let higher = scores.iter().filter(|&&s| s > score).count() as u32;
+    ahead + higher + 1
+}
+
+/// How many bands [`ScoreDistribution::buckets`] splits a snapshot into.
+pub const DISTRIBUTION_BUCKETS: usize = 24;
+
+/// Summary statistics of the scores on one leaderboard snapshot.
+///
+/// Percentiles use the nearest-rank method over every row, so `p90` is the
+/// lowest score the best tenth of the board reached.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ScoreDistribution {
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    /// Rows sharing their score with at least one other row.
+    pub ties: u32,
+    /// Rows per score band, lowest band first. The bands are contiguous and
+    /// widen geometrically, as leaderboard scores pile up at the low end.
+    pub buckets: Vec<ScoreBucket>,
+}
+
+/// The rows whose score lies in `min..=max`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreBucket {
+    pub min: u64,
+    pub max: u64,
+    pub count: u32,
+}
+
+impl ScoreDistribution {
+    /// The distribution of `scores`, in any order; `None` when empty.
+    pub fn from_scores(scores: &[u64]) -> Option<Self> {
+        let mut sorted = scores.to_vec();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        let (&high, &low) = (sorted.first()?, sorted.last()?);
+        Self::from_descending(sorted.len(), high, low, sorted)
+    }
+
+    /// The distribution of `count` scores running from `high` down to `low`,
+    /// yielded highest first as a leaderboard's rows are, in one pass and
+    /// without holding them. `None` when `count` is zero or the scores are not
+    /// what they were said to be.
+    pub fn from_descending(
+        count: usize,
+        high: u64,
+        low: u64,
+        scores: impl IntoIterator<Item = u64>,
+    ) -> Option<Self> {
+        if count == 0 || low > high {
+            return None;
+        }
+        // Nearest rank, counted from the lowest score, as a position from the
+        // highest.
+        let from_top = |p: usize| count - (p * count).div_ceil(100).max(1);
+        let wanted = [from_top(50), from_top(90), from_top(99)];
+        let mut percentiles = [0; 3];
+
+        let starts = band_starts(low, high);
+        let mut counts = vec![0u32; starts.len()];
+        let mut band = starts.len() - 1;
+
+        let (mut sum, mut ties, mut seen) = (0u128, 0u32, 0usize);
+        let (mut previous, mut run) = (None, 0u32);
+        for score in scores {
+            let out_of_order = match previous {
+                Some(previous) => score > previous,
+                None => score != high,
+            };
+            if seen == count || out_of_order || score < low {
+                return None;
+            }
+            for (at, value) in wanted.iter().zip(&mut percentiles) {
+                if *at == seen {
+                    *value = score;
+                }
+            }
+
+            if previous == Some(score) {
+                run += 1;
+            } else {
+                ties += if run > 1 { run } else { 0 };
+                run = 1;
+            }
+            previous = Some(score);
+
+            while score < starts[band] {
+                band -= 1;
+            }
+            counts[band] += 1;
+            sum += u128::from(score);
+            seen += 1;
+        }
+        ties += if run > 1 { run } else { 0 };
+        if seen != count || previous != Some(low) {
+            return None;
+        }
+
+        let buckets = starts
+            .iter()
+            .zip(counts)
+            .enumerate()
+            .map(|(i, (&min, count))| ScoreBucket {
+                min,
+                max: starts.get(i + 1).map_or(high, |next| next - 1),
+                count,
+            })
+            .collect();
+        let [p50, p90, p99] = percentiles;
+        Some(Self {
+            mean: sum as f64 / count as f64,
+            p50,
+            p90,
+            p99,
+            ties,
+            buckets,
+        })
+    }
+}
+
+/// The lower bounds of up to [`DISTRIBUTION_BUCKETS`] contiguous bands over
+/// `low..=high`, evenly spaced on `ln(1 + score)` and strictly increasing;
+/// rounding can merge narrow bands at the low end.
+fn band_starts(low: u64, high: u64) -> Vec<u64> {
+    let (from, to) = ((low as f64).ln_1p(), (high as f64).ln_1p());
+
+    let mut starts = vec![low];
+    for band in 1..DISTRIBUTION_BUCKETS {
+        let at = (from + (to - from) * band as f64 / DISTRIBUTION_BUCKETS as f64).exp_m1();
+        let start = (at.round() as u64).min(high);
+        if start > *starts.last().unwrap() {
+            starts.push(start);
+        }
+    }
+    starts
+}
+
+/// A player's score and rank on one leaderboard at one snapshot.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct HistoryPoint {
+    /// The history snapshot id, or `latest`.
+    pub snapshot_id: SmolStr,
+    pub timestamp: u64,
+    pub score: u64,
+    pub rank: u32,
+}
+
+/// A player's time series on one board/game/stat leaderboard, oldest point
+/// first. A snapshot the player was not ranked in has no point.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct StatHistory {
+    pub board: SmolStr,
+    pub game: SmolStr,
+    pub stat: SmolStr,
+    pub points: Vec<HistoryPoint>,
+}
+
+/// How a player's scores and ranks evolved across snapshots.
+///
+/// Shipped in its own shard next to the player shard rather than inside
+/// [`PlayerProfile`], so the pages that only need the current stats do not
+/// download every snapshot the player ever appeared in.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PlayerHistory {
+    pub uuid: SmolStr,
+    pub stats: Vec<StatHistory>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
@@ -365,6 +671,7 @@ mod tests {
                 stat(2, 200, 0),  // unranked, new game
                 stat(3, 25, 150), // ranked but outside top 100
             ],
+            name_history: vec![],
         };
 
         let summary = profile.summary();
@@ -383,6 +690,7 @@ mod tests {
             uuid: SmolStr::new("abc"),
             name: None,
             stats: vec![],
+            name_history: vec![],
         };
 
         assert_eq!(profile.summary(), PlayerSummary::default());
@@ -394,6 +702,7 @@ mod tests {
             uuid: SmolStr::new("abc"),
             name: None,
             stats: vec![stat(1, 10, 0), stat(1, 20, 0)],
+            name_history: vec![],
         };
 
         let summary = profile.summary();
@@ -440,6 +749,94 @@ mod tests {
         assert_eq!(table.get(&70), Some(&7));
     }
 
+    #[test]
+    fn distribution_percentiles_ties_and_buckets() {
+        let scores: Vec<u64> = (1..=100).rev().collect();
+        let spread = ScoreDistribution::from_scores(&scores).unwrap();
+        assert_eq!((spread.p50, spread.p90, spread.p99), (50, 90, 99));
+        assert_eq!(spread.mean, 50.5);
+        assert_eq!(spread.ties, 0);
+
+        // Contiguous from the lowest score to the highest, every row counted once.
+        assert_eq!(spread.buckets.first().map(|b| b.min), Some(1));
+        assert_eq!(spread.buckets.last().map(|b| b.max), Some(100));
+        for pair in spread.buckets.windows(2) {
+            assert_eq!(pair[0].max + 1, pair[1].min);
+        }
+        let counted: u32 = spread.buckets.iter().map(|b| b.count).sum();
+        assert_eq!(counted, 100);
+
+        let tied = ScoreDistribution::from_scores(&[5, 5, 3, 3, 3, 1]).unwrap();
+        assert_eq!(tied.ties, 5);
+        assert_eq!(tied.p50, 3);
+
+        let flat = ScoreDistribution::from_scores(&[7, 7]).unwrap();
+        assert_eq!(
+            flat.buckets,
+            [ScoreBucket {
+                min: 7,
+                max: 7,
+                count: 2
+            }]
+        );
+        assert_eq!(ScoreDistribution::from_scores(&[]), None);
+    }
+
+    /// Streamed highest first, the distribution matches the one over every
+    /// score at once, and scores out of order or miscounted give none.
+    #[test]
+    fn descending_distribution_matches_the_sorted_one() {
+        let scores: Vec<u64> = [900, 900, 450, 300, 300, 300, 120, 40, 7, 7, 1, 0].into();
+        let streamed =
+            ScoreDistribution::from_descending(scores.len(), 900, 0, scores.iter().copied());
+        assert_eq!(streamed, ScoreDistribution::from_scores(&scores));
+
+        let shuffled = [300, 900, 0];
+        assert_eq!(
+            ScoreDistribution::from_descending(3, 900, 0, shuffled),
+            None
+        );
+        assert_eq!(
+            ScoreDistribution::from_descending(4, 900, 0, [900, 300, 0]),
+            None
+        );
+        assert_eq!(ScoreDistribution::from_descending(0, 0, 0, []), None);
+    }
+
+    /// Placing a score through the page ranges agrees with ranking it among
+    /// every row, for scores above, between, on and below the rows.
+    #[test]
+    fn placed_scores_match_a_full_ranking() {
+        let pages: [&[u64]; 3] = [&[100, 100, 90], &[90, 80, 80], &[70]];
+        let ranges: Vec<PageScoreRange> = pages
+            .iter()
+            .map(|scores| PageScoreRange {
+                max: scores[0],
+                min: *scores.last().unwrap(),
+                entries: scores.len() as u32,
+            })
+            .collect();
+        let all: Vec<u64> = pages.concat();
+
+        for score in [120, 100, 95, 90, 85, 80, 75, 70, 0] {
+            let rank = match place_score(&ranges, score) {
+                ScorePlacement::Rank(rank) => rank,
+                ScorePlacement::InPage { page, ahead } => {
+                    rank_within_page(pages[page as usize], ahead, score)
+                }
+            };
+            assert_eq!(rank, rank_within_page(&all, 0, score), "score {score}");
+        }
+
+        assert_eq!(place_score(&ranges, 100), ScorePlacement::Rank(1));
+        assert_eq!(
+            place_score(&ranges, 95),
+            ScorePlacement::InPage { page: 0, ahead: 0 }
+        );
+        assert_eq!(place_score(&ranges, 0), ScorePlacement::Rank(8));
+        assert_eq!(place_score(&[], 5), ScorePlacement::Rank(1));
+    }
+
     #[test]
     fn competition_ranks_by_score_handles_empty_input() {
         let table = competition_ranks_by_score(&HashMap::new());
@@ -1,9 +1,10 @@
-use crate::models::PlatformEdition;
+//! Centralized data path definitions shared between frontend client and backend server.
+//! All functions return **relative** paths (no leading slash).
+//! - The WASM client prefixes with `/data/`
+//! - The server joins with its `data_root` directory
 
-/// Centralized data path definitions shared between frontend client and backend server.
-/// All functions return **relative** paths (no leading slash).
-/// - The WASM client prefixes with `/data/`
-/// - The server joins with its `data_root` directory
+use crate::models::PlatformEdition;
+use mp_stats_common::formats::{FILE_MANIFEST_BIN, FILE_MANIFEST_JSON};
 
 /// Format chunk filename using standard pattern
 fn chunk_filename(index: u32) -> String {
@@ -47,10 +48,68 @@ pub fn history_leaderboard_chunk_bin(
     )
 }
 
+/// One shard of the latest snapshot's position index; see
+/// `mp_stats_common::shard::position_shard`.
+pub fn leaderboard_positions_bin(
+    edition: &PlatformEdition,
+    board: &str,
+    game: &str,
+    stat: &str,
+    shard: &str,
+) -> String {
+    format!(
+        "{}/leaderboards/{board}/{game}/{stat}/latest/positions/{shard}.bin.xz",
+        edition.directory_name()
+    )
+}
+
+/// One shard of a history snapshot's position index, laid out like
+/// [`leaderboard_positions_bin`].
+pub fn history_leaderboard_positions_bin(
+    edition: &PlatformEdition,
+    board: &str,
+    game: &str,
+    stat: &str,
+    snapshot_id: &str,
+    shard: &str,
+) -> String {
+    format!(
+        "{}/leaderboards/{board}/{game}/{stat}/history/{snapshot_id}/positions/{shard}.bin.xz",
+        edition.directory_name()
+    )
+}
+
 pub fn player_shard_bin(edition: &PlatformEdition, shard: &str) -> String {
     format!("{}/players/{shard}.bin.xz", edition.directory_name())
 }
 
+/// The per-player score history, sharded exactly like [`player_shard_bin`].
+pub fn player_history_bin(edition: &PlatformEdition, shard: &str) -> String {
+    format!("{}/player_history/{shard}.bin.xz", edition.directory_name())
+}
+
 pub fn names_index_bin(edition: &PlatformEdition, prefix: &str) -> String {
     format!("{}/names_index/{prefix}.bin.xz", edition.directory_name())
 }
+
+/// Every name too short for [`names_index_bin`], in one file of the same layout.
+pub fn names_short_bin(edition: &PlatformEdition) -> String {
+    format!("{}/names_index/short.bin.xz", edition.directory_name())
+}
+
+/// The players whose names contain `trigram`; see `mp_stats_common::shard::name_trigrams`.
+pub fn name_trigram_bin(edition: &PlatformEdition, trigram: &str) -> String {
+    format!(
+        "{}/names_trigram/{trigram}.bin.xz",
+        edition.directory_name()
+    )
+}
+
+/// The edition's integrity manifest; see `mp_stats_common::manifest`.
+pub fn manifest_bin(edition: &PlatformEdition) -> String {
+    format!("{}/{FILE_MANIFEST_BIN}", edition.directory_name())
+}
+
+pub fn manifest_json(edition: &PlatformEdition) -> String {
+    format!("{}/{FILE_MANIFEST_JSON}", edition.directory_name())
+}